@@ -0,0 +1,58 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use deck::{html, slides};
+use std::io;
+
+/// Deck made of the given number of slides mixing text, lists, tables and highlighted code
+fn deck(slides: usize) -> String {
+    let mut input = String::from("---\ntitle: Benchmark\n---\n");
+    for i in 0..slides {
+        if i > 0 {
+            input.push_str("\n---\n\n");
+        }
+        input.push_str(&format!(
+            "# Slide {}\n\nSome *emphasis*, **strong** text and a [link](https://example.com).\n\n\
+             - First point\n- Second point with `inline code`\n\n\
+             | Name | Value |\n|------|-------|\n| a    | {}    |\n\n\
+             ```rust\nfn slide_{}() -> usize {{\n    (0..{}).map(|x| x * 2).sum()\n}}\n```\n\n\
+             <!-- notes: What to say about slide {} -->\n",
+            i + 1,
+            i,
+            i,
+            i,
+            i + 1
+        ));
+    }
+    input
+}
+
+fn bench_render(c: &mut Criterion) {
+    let renderer =
+        html::Renderer::try_new(html::Options::default()).expect("Failed to create renderer");
+    let mut group = c.benchmark_group("render");
+    for &size in &[10, 100, 1000] {
+        let input = deck(size);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::new("parse", size), &input, |b, input| {
+            b.iter(|| slides::parse(input))
+        });
+        group.bench_with_input(BenchmarkId::new("string", size), &input, |b, input| {
+            b.iter(|| {
+                renderer
+                    .render(input.clone(), None, None)
+                    .expect("Failed to render")
+                    .to_string()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("stream", size), &input, |b, input| {
+            b.iter(|| {
+                renderer
+                    .render_to(input.clone(), None, None, io::sink())
+                    .expect("Failed to render")
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_render);
+criterion_main!(benches);
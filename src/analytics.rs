@@ -0,0 +1,133 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    time::Instant,
+};
+use tokio::sync::mpsc;
+
+/// Limit protecting the server from clients reporting slides that don't exist
+const MAX_SLIDES: usize = 4096;
+
+/// Slide a viewer has active and since when
+struct Active {
+    index: usize,
+    since: Instant,
+}
+
+#[derive(Default)]
+struct Views {
+    /// Viewers who had the slide active, identified by their connection. Only their number is
+    /// ever reported.
+    viewers: HashSet<usize>,
+    seconds: f64,
+}
+
+#[derive(Serialize)]
+struct SlideViews {
+    index: usize,
+    /// Number of viewers who had the slide active
+    viewers: usize,
+    /// Total time the viewers spent on the slide, in seconds
+    seconds: f64,
+}
+
+/// Audience of the presentation, aggregated so that nothing identifies the viewers
+#[derive(Serialize)]
+pub struct Report {
+    /// Viewers currently following the presentation, with the page visible
+    viewers: usize,
+    /// Largest number of viewers following the presentation at once
+    peak_viewers: usize,
+    slides: Vec<SlideViews>,
+}
+
+/// Records the slides the viewers of a served presentation have active, to see where a remote
+/// audience dropped off
+pub struct Analytics {
+    /// Notifies the task writing the report to a file of each change, the views being only
+    /// recorded in memory here
+    changes: mpsc::UnboundedSender<()>,
+    active: HashMap<usize, Active>,
+    slides: BTreeMap<usize, Views>,
+    peak_viewers: usize,
+}
+
+impl Analytics {
+    pub fn new(changes: mpsc::UnboundedSender<()>) -> Analytics {
+        Analytics {
+            changes,
+            active: HashMap::new(),
+            slides: BTreeMap::new(),
+            peak_viewers: 0,
+        }
+    }
+
+    /// Record the slide a viewer now has active, `None` once the presentation is hidden or
+    /// closed
+    pub fn view(&mut self, viewer: usize, index: Option<usize>) {
+        let now = Instant::now();
+        if let Some(active) = self.active.remove(&viewer) {
+            let views = self.slides.entry(active.index).or_default();
+            views.seconds += now.duration_since(active.since).as_secs_f64();
+        }
+        if let Some(index) = index.filter(|index| *index < MAX_SLIDES) {
+            self.slides.entry(index).or_default().viewers.insert(viewer);
+            self.active.insert(viewer, Active { index, since: now });
+            self.peak_viewers = self.peak_viewers.max(self.active.len());
+        }
+        self.changes.send(()).ok();
+    }
+
+    /// Views of each slide so far, including the time spent on the slides still active
+    pub fn report(&self) -> Report {
+        let now = Instant::now();
+        let slides = self
+            .slides
+            .iter()
+            .map(|(index, views)| {
+                let active: f64 = self
+                    .active
+                    .values()
+                    .filter(|active| active.index == *index)
+                    .map(|active| now.duration_since(active.since).as_secs_f64())
+                    .sum();
+                SlideViews {
+                    index: *index,
+                    viewers: views.viewers.len(),
+                    seconds: views.seconds + active,
+                }
+            })
+            .collect();
+        Report {
+            viewers: self.active.len(),
+            peak_viewers: self.peak_viewers,
+            slides,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let (changes, mut notified) = mpsc::unbounded_channel();
+        let mut analytics = Analytics::new(changes);
+        analytics.view(1, Some(0));
+        analytics.view(2, Some(0));
+        analytics.view(1, Some(1));
+        analytics.view(2, None);
+        analytics.view(3, Some(MAX_SLIDES));
+        assert!(notified.try_recv().is_ok());
+        let report = analytics.report();
+        assert_eq!(1, report.viewers);
+        assert_eq!(2, report.peak_viewers);
+        let viewers: Vec<(usize, usize)> = report
+            .slides
+            .iter()
+            .map(|slide| (slide.index, slide.viewers))
+            .collect();
+        assert_eq!(vec![(0, 2), (1, 1)], viewers);
+    }
+}
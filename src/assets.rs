@@ -0,0 +1,267 @@
+use crate::{error::Error, slides::Slide};
+use pulldown_cmark::{Event, Tag};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+use tracing::warn;
+
+/// Directory of shared assets served under a prefix of the URLs, e.g.
+/// `common=~/slides/common-assets` for the decks to reference `common/logo.png`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetDir {
+    /// Segments of the URLs leading to the directory, separated by slashes
+    pub prefix: String,
+    pub dir: PathBuf,
+}
+
+impl AssetDir {
+    /// File of the directory a local URL points to, if it starts with the prefix
+    pub fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = path.strip_prefix(&self.prefix)?.strip_prefix('/')?;
+        Some(self.dir.join(path))
+    }
+}
+
+impl FromStr for AssetDir {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AssetDir, String> {
+        let (prefix, dir) = s.split_once('=').ok_or_else(|| {
+            format!(
+                "Expected a prefix and a directory, e.g. common=assets: {}",
+                s
+            )
+        })?;
+        let segments: Vec<&str> = prefix.split('/').filter(|s| !s.is_empty()).collect();
+        if segments.is_empty() || segments.iter().any(|s| *s == "." || *s == "..") {
+            return Err(format!(
+                "Invalid prefix of the asset directory: {:?}",
+                prefix
+            ));
+        }
+        // The shell doesn't expand the home directory after the `=`
+        let dir = match (dir.strip_prefix("~/"), env::var_os("HOME")) {
+            (Some(dir), Some(home)) => Path::new(&home).join(dir),
+            _ => PathBuf::from(dir),
+        };
+        Ok(AssetDir {
+            prefix: segments.join("/"),
+            dir,
+        })
+    }
+}
+
+/// Whether the URL points to a file relative to the document
+pub fn is_local(url: &str) -> bool {
+    !(url.is_empty()
+        || url.starts_with('/')
+        || url.starts_with('#')
+        || url.starts_with("data:")
+        || url.starts_with("mailto:")
+        || url.contains("://"))
+}
+
+/// Decode a segment of a URL path, `None` when it holds an invalid escape or isn't valid UTF-8
+pub fn percent_decode(segment: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(segment.len());
+    let mut rest = segment.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' && after.len() >= 2 {
+            if !after[..2].iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            let hex = std::str::from_utf8(&after[..2]).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// File a local URL points to relative to its document, without the query or fragment and with
+/// its escapes decoded, e.g. `my%20photo.png?v=2` points to `my photo.png`
+pub fn url_path(url: &str) -> PathBuf {
+    let path = url
+        .split(|c: char| c == '?' || c == '#')
+        .next()
+        .unwrap_or(url);
+    PathBuf::from(percent_decode(path).unwrap_or_else(|| path.to_owned()))
+}
+
+/// Copies local assets next to a generated document and keeps track of their new location
+pub struct Copier<'a> {
+    output_dir: &'a Path,
+    copied: HashMap<PathBuf, String>,
+}
+
+impl<'a> Copier<'a> {
+    pub fn new(output_dir: &'a Path) -> Copier<'a> {
+        Copier {
+            output_dir,
+            copied: HashMap::new(),
+        }
+    }
+
+    /// Copy the file pointed by the URL, relative to the given directory, and return its URL
+    /// relative to the output directory. Files nested under the base directory keep the same
+    /// relative path while the others are moved to an `assets` directory.
+    pub fn copy(&mut self, base_dir: &Path, url: &str) -> Result<Option<String>, Error> {
+        let path = url
+            .split(|c: char| c == '?' || c == '#')
+            .next()
+            .unwrap_or(url);
+        let suffix = &url[path.len()..];
+        let source = base_dir.join(url_path(path));
+        let source = match source.canonicalize() {
+            Ok(source) if source.is_file() => source,
+            _ => {
+                warn!("Asset not found: {}", source.to_string_lossy());
+                return Ok(None);
+            }
+        };
+        if let Some(url) = self.copied.get(&source) {
+            return Ok(Some(format!("{}{}", url, suffix)));
+        }
+
+        let is_nested = Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        let destination_url = if is_nested {
+            path.to_owned()
+        } else {
+            let file_name = source
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            format!("assets/{}-{}", self.copied.len(), file_name)
+        };
+        let destination = self.output_dir.join(url_path(&destination_url));
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Copying a file onto itself would truncate it
+        if destination.canonicalize().ok().as_ref() != Some(&source) {
+            fs::copy(&source, &destination)?;
+        }
+        self.copied.insert(source, destination_url.clone());
+        Ok(Some(format!("{}{}", destination_url, suffix)))
+    }
+}
+
+/// Copy the local images referenced by the slides and return the URLs that must be rewritten
+pub fn copy_images(
+    slides: &[Slide],
+    base_dir: &Path,
+    copier: &mut Copier,
+) -> Result<HashMap<String, String>, Error> {
+    let mut urls = HashMap::new();
+    for slide in slides {
+        for event in &slide.events {
+            if let Event::Start(Tag::Image(_, url, _)) = event {
+                if !is_local(url) || urls.contains_key(&**url) {
+                    continue;
+                }
+                if let Some(new_url) = copier.copy(base_dir, url)? {
+                    urls.insert(url.to_string(), new_url);
+                }
+            }
+        }
+    }
+    Ok(urls)
+}
+
+/// Copy the local files referenced by `url()` in the stylesheet, such as fonts or background
+/// images, and return the stylesheet pointing to their new location
+pub fn copy_stylesheet_urls(
+    css: &str,
+    base_dir: &Path,
+    copier: &mut Copier,
+) -> Result<String, Error> {
+    let mut output = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("url(") {
+        let (before, after) = rest.split_at(start + "url(".len());
+        output.push_str(before);
+        let end = match after.find(')') {
+            Some(end) => end,
+            None => {
+                rest = after;
+                break;
+            }
+        };
+        let raw = &after[..end];
+        let url = raw.trim().trim_matches(|c: char| c == '"' || c == '\'');
+        let new_url = if is_local(url) {
+            copier.copy(base_dir, url)?
+        } else {
+            None
+        };
+        match new_url {
+            Some(new_url) => output.push_str(&format!("\"{}\"", new_url)),
+            None => output.push_str(raw),
+        }
+        rest = &after[end..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_local() {
+        assert!(is_local("images/logo.png"));
+        assert!(is_local("../shared/logo.png"));
+        assert!(!is_local(
+            "https://www.rust-lang.org/logos/rust-logo-512x512.png"
+        ));
+        assert!(!is_local("/logo.png"));
+        assert!(!is_local("data:image/png;base64,AAAA"));
+    }
+
+    #[test]
+    fn test_url_path() {
+        assert_eq!(
+            PathBuf::from("my photo.png"),
+            url_path("my%20photo.png?v=2")
+        );
+        assert_eq!(PathBuf::from("100%.png"), url_path("100%.png#top"));
+    }
+
+    #[test]
+    fn test_copy() {
+        let root = std::env::temp_dir().join(format!("deck-assets-{}", std::process::id()));
+        let (deck, output) = (root.join("deck"), root.join("output"));
+        fs::create_dir_all(deck.join("images")).unwrap();
+        fs::write(deck.join("images/my photo.png"), "").unwrap();
+        let mut copier = Copier::new(&output);
+        assert_eq!(
+            Some("images/my%20photo.png?v=2".to_owned()),
+            copier.copy(&deck, "images/my%20photo.png?v=2").unwrap()
+        );
+        assert!(output.join("images/my photo.png").is_file());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_asset_dir() {
+        let asset_dir: AssetDir = "/shared/images/=common-assets".parse().unwrap();
+        assert_eq!("shared/images", asset_dir.prefix);
+        assert_eq!(
+            Some(PathBuf::from("common-assets/logo.png")),
+            asset_dir.resolve("shared/images/logo.png")
+        );
+        assert_eq!(None, asset_dir.resolve("shared/imageslogo.png"));
+        assert_eq!(None, asset_dir.resolve("images/logo.png"));
+        assert!("common-assets".parse::<AssetDir>().is_err());
+        assert!("../common=common-assets".parse::<AssetDir>().is_err());
+    }
+}
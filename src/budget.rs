@@ -0,0 +1,134 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// Number of embedded assets listed when the presentation exceeds its budget
+const LARGEST_ASSETS: usize = 5;
+
+const UNITS: [(&str, u64); 4] = [
+    ("GB", 1024 * 1024 * 1024),
+    ("MB", 1024 * 1024),
+    ("KB", 1024),
+    ("B", 1),
+];
+
+/// Size in bytes, parsed from and displayed with a unit such as `5MB` or `500KB`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Size(pub u64);
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Size, String> {
+        let s = s.trim();
+        let upper = s.to_ascii_uppercase();
+        let (number, multiplier) = UNITS
+            .iter()
+            .find(|(unit, _)| upper.ends_with(unit))
+            .map(|&(unit, multiplier)| (&s[..s.len() - unit.len()], multiplier))
+            .unwrap_or((s, 1));
+        match number.trim().parse::<f64>() {
+            Ok(number) if number >= 0.0 => Ok(Size((number * multiplier as f64) as u64)),
+            _ => Err(format!("Invalid size {:?}, expected e.g. 5MB or 500KB", s)),
+        }
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (unit, multiplier) = UNITS
+            .iter()
+            .find(|(_, multiplier)| self.0 >= *multiplier)
+            .unwrap_or(&("B", 1));
+        if *multiplier == 1 {
+            write!(f, "{} B", self.0)
+        } else {
+            write!(f, "{:.1} {}", self.0 as f64 / *multiplier as f64, unit)
+        }
+    }
+}
+
+/// Asset embedded in the self-contained presentation
+pub struct Embedded {
+    pub name: String,
+    pub size: Size,
+}
+
+/// Assets embedded in the presentation, from the largest to the smallest: media inlined as data
+/// URLs, inlined SVG images and data URLs of the custom css such as fonts
+pub fn embedded(
+    assets: &HashMap<String, String>,
+    svgs: &HashMap<String, String>,
+    css: Option<&str>,
+) -> Vec<Embedded> {
+    let mut embedded: Vec<Embedded> = assets
+        .iter()
+        .filter(|(_, new_url)| new_url.starts_with("data:"))
+        .chain(svgs.iter())
+        .map(|(url, data)| Embedded {
+            name: url.clone(),
+            size: Size(data.len() as u64),
+        })
+        .collect();
+    let css = css.unwrap_or("");
+    for (i, _) in css.match_indices("data:") {
+        let data = &css[i..];
+        let end = data
+            .find(|c: char| c == ')' || c == '"' || c == '\'')
+            .unwrap_or(data.len());
+        let mime_type = data[5..].split(|c: char| c == ';' || c == ',').next();
+        embedded.push(Embedded {
+            name: format!("{} in the custom css", mime_type.unwrap_or("data")),
+            size: Size(end as u64),
+        });
+    }
+    embedded.sort_by(|a, b| b.size.0.cmp(&a.size.0).then_with(|| a.name.cmp(&b.name)));
+    embedded
+}
+
+/// Describe how far the presentation exceeds its budget along with the largest assets embedded
+/// in it, if it does
+pub fn exceeded(size: Size, max_size: Size, embedded: &[Embedded]) -> Option<String> {
+    if size.0 <= max_size.0 {
+        return None;
+    }
+    let mut message = format!(
+        "The presentation weighs {}, more than the budget of {}",
+        size, max_size
+    );
+    if !embedded.is_empty() {
+        message.push_str(", largest embedded assets:");
+        for asset in embedded.iter().take(LARGEST_ASSETS) {
+            message.push_str(&format!("\n  {}: {}", asset.name, asset.size));
+        }
+    }
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size() {
+        assert_eq!(Ok(Size(5 * 1024 * 1024)), "5MB".parse());
+        assert_eq!(Ok(Size(1536)), "1.5 kb".parse());
+        assert_eq!(Ok(Size(300)), "300".parse());
+        assert!("5 MiB".parse::<Size>().is_err());
+        assert_eq!("5.0 MB", Size(5 * 1024 * 1024).to_string());
+        assert_eq!("300 B", Size(300).to_string());
+
+        let mut assets = HashMap::new();
+        assets.insert(
+            "demo.mp4".to_owned(),
+            "data:video/mp4;base64,AAAA".to_owned(),
+        );
+        assets.insert("logo.png".to_owned(), "logo.png".to_owned());
+        let css = "@font-face { src: url(data:font/woff2;base64,AAAAAAAAAAAAAAAA) }";
+        let embedded = embedded(&assets, &HashMap::new(), Some(css));
+        let names: Vec<&str> = embedded.iter().map(|asset| asset.name.as_str()).collect();
+        assert_eq!(vec!["font/woff2 in the custom css", "demo.mp4"], names);
+        assert!(exceeded(Size(100), Size(100), &embedded).is_none());
+        assert!(exceeded(Size(101), Size(100), &embedded)
+            .unwrap()
+            .contains("\n  demo.mp4: 26 B"));
+    }
+}
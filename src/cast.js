@@ -0,0 +1,216 @@
+// Player of the asciicast v2 recordings produced by `asciinema rec`. Output events are replayed on
+// a small terminal emulator that supports the cursor movements and erasures commonly used by
+// shells, colors and other text attributes are dropped.
+class CastPlayer {
+  constructor(element) {
+    this.element = element;
+    this.screen = element.querySelector('.cast-screen');
+    this.autoplay = element.hasAttribute('data-autoplay');
+    this.loop = element.hasAttribute('data-loop');
+    this.events = null;
+    this.timeout = null;
+    this.loaded = fetch(element.dataset.src)
+      .then(response => response.text())
+      .then(text => this.parse(text))
+      .catch(err => console.error('Failed to load cast', element.dataset.src, err));
+    element.addEventListener('click', () => {
+      if (this.playing()) {
+        this.pause();
+      } else {
+        this.play();
+      }
+    }, false);
+  }
+
+  parse(text) {
+    const lines = text.split('\n').filter(line => line.trim() !== '');
+    const header = JSON.parse(lines[0]);
+    this.width = header.width || 80;
+    this.height = header.height || 24;
+    // Long pauses in the recording are shortened
+    this.idleLimit = header.idle_time_limit || 2;
+    this.events = lines
+      .slice(1)
+      .map(line => JSON.parse(line))
+      .filter(event => event[1] === 'o');
+    this.reset();
+  }
+
+  reset() {
+    this.grid = [];
+    for (let i = 0; i < this.height; i++) {
+      this.grid.push(new Array(this.width).fill(' '));
+    }
+    this.row = 0;
+    this.col = 0;
+    this.pending = '';
+    this.position = 0;
+    this.render();
+  }
+
+  playing() {
+    return this.timeout !== null;
+  }
+
+  play() {
+    if (this.events === null) {
+      this.loaded.then(() => this.events !== null && this.play());
+      return;
+    }
+    if (this.position >= this.events.length) {
+      this.reset();
+    }
+    this.element.classList.add('playing');
+    this.schedule(0);
+  }
+
+  pause() {
+    clearTimeout(this.timeout);
+    this.timeout = null;
+    this.element.classList.remove('playing');
+  }
+
+  schedule(delay) {
+    this.timeout = setTimeout(() => this.step(), Math.min(delay, this.idleLimit) * 1000);
+  }
+
+  step() {
+    const [time, , data] = this.events[this.position];
+    this.write(data);
+    this.render();
+    this.position++;
+    if (this.position < this.events.length) {
+      this.schedule(this.events[this.position][0] - time);
+    } else if (this.loop) {
+      this.timeout = setTimeout(() => {
+        this.reset();
+        this.schedule(0);
+      }, this.idleLimit * 1000);
+    } else {
+      this.pause();
+    }
+  }
+
+  render() {
+    this.screen.textContent = this.grid.map(line => line.join('').trimEnd()).join('\n');
+  }
+
+  clearLine(row, start, end) {
+    for (let col = start; col < end; col++) {
+      this.grid[row][col] = ' ';
+    }
+  }
+
+  newline() {
+    this.row++;
+    if (this.row >= this.height) {
+      this.grid.shift();
+      this.grid.push(new Array(this.width).fill(' '));
+      this.row = this.height - 1;
+    }
+  }
+
+  write(data) {
+    data = this.pending + data;
+    this.pending = '';
+    for (let i = 0; i < data.length; i++) {
+      const c = data[i];
+      if (c === '\x1b') {
+        // Escape sequences split across events are completed by the next one
+        let end = i + 2;
+        if (data[i + 1] === '[') {
+          // Control sequence ended by a byte in the @ to ~ range
+          while (end < data.length && (data.charCodeAt(end) < 0x40 || data.charCodeAt(end) > 0x7e)) {
+            end++;
+          }
+          if (end < data.length) {
+            this.control(data.slice(i + 2, end), data[end]);
+          }
+        } else if (data[i + 1] === ']') {
+          // Operating system command, such as setting the window title, ended by BEL or ST
+          while (end < data.length && data[end] !== '\x07' && data[end] !== '\x1b') {
+            end++;
+          }
+          if (data[end] === '\x1b') {
+            end++;
+          }
+        } else {
+          end = i + 1;
+        }
+        if (end >= data.length) {
+          this.pending = data.slice(i);
+          return;
+        }
+        i = end;
+      } else if (c === '\n') {
+        this.newline();
+      } else if (c === '\r') {
+        this.col = 0;
+      } else if (c === '\b') {
+        this.col = Math.max(0, this.col - 1);
+      } else if (c >= ' ') {
+        if (this.col >= this.width) {
+          this.col = 0;
+          this.newline();
+        }
+        this.grid[this.row][this.col] = c;
+        this.col++;
+      }
+    }
+  }
+
+  control(params, command) {
+    const args = params.replace('?', '').split(';').map(arg => parseInt(arg) || 0);
+    const n = Math.max(args[0], 1);
+    switch (command) {
+      case 'A':
+        this.row -= n;
+        break;
+      case 'B':
+        this.row += n;
+        break;
+      case 'C':
+        this.col += n;
+        break;
+      case 'D':
+        this.col -= n;
+        break;
+      case 'G':
+        this.col = n - 1;
+        break;
+      case 'H':
+      case 'f':
+        this.row = Math.max(args[0], 1) - 1;
+        this.col = Math.max(args[1] || 0, 1) - 1;
+        break;
+      case 'J':
+        if (args[0] === 0) {
+          this.clearLine(this.row, this.col, this.width);
+          for (let row = this.row + 1; row < this.height; row++) {
+            this.clearLine(row, 0, this.width);
+          }
+        } else if (args[0] === 1) {
+          this.clearLine(this.row, 0, this.col + 1);
+          for (let row = 0; row < this.row; row++) {
+            this.clearLine(row, 0, this.width);
+          }
+        } else {
+          for (let row = 0; row < this.height; row++) {
+            this.clearLine(row, 0, this.width);
+          }
+        }
+        break;
+      case 'K':
+        if (args[0] === 0) {
+          this.clearLine(this.row, this.col, this.width);
+        } else if (args[0] === 1) {
+          this.clearLine(this.row, 0, this.col + 1);
+        } else {
+          this.clearLine(this.row, 0, this.width);
+        }
+        break;
+    }
+    this.row = Math.min(Math.max(this.row, 0), this.height - 1);
+    this.col = Math.min(Math.max(this.col, 0), this.width);
+  }
+}
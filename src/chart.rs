@@ -0,0 +1,395 @@
+use crate::{code::Info, html::escape_html};
+use serde::Deserialize;
+use std::f64::consts::PI;
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 400.0;
+const MARGIN_LEFT: f64 = 60.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_BOTTOM: f64 = 40.0;
+/// Colors given to the series, or to the slices of a pie chart, in order
+const PALETTE: [&str; 8] = [
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Type {
+    Bar,
+    Line,
+    Pie,
+}
+
+impl Type {
+    fn parse(s: &str) -> Option<Type> {
+        match s {
+            "bar" => Some(Type::Bar),
+            "line" => Some(Type::Line),
+            "pie" => Some(Type::Pie),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Series {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub values: Vec<f64>,
+}
+
+/// Chart written in a ```` ```chart ```` block, either as JSON
+///
+/// ```text
+/// {"type": "line", "title": "Users", "labels": ["Q1", "Q2"],
+///  "series": [{"name": "2020", "values": [3, 5]}]}
+/// ```
+///
+/// or as CSV whose first row holds the names of the series and first column the labels, the type
+/// then being set with the `type` attribute of the fence, e.g. ```` ```chart {type=pie} ````
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Chart {
+    #[serde(rename = "type")]
+    pub kind: Option<Type>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub series: Vec<Series>,
+    /// Values of a chart made of a single series
+    #[serde(default)]
+    values: Vec<f64>,
+}
+
+impl Chart {
+    pub fn parse(source: &str, info: &Info) -> Result<Chart, String> {
+        let mut chart = if source.trim_start().starts_with('{') {
+            serde_json::from_str(source).map_err(|err| err.to_string())?
+        } else {
+            parse_csv(source)?
+        };
+        if chart.series.is_empty() && !chart.values.is_empty() {
+            chart.series.push(Series {
+                name: None,
+                values: std::mem::take(&mut chart.values),
+            });
+        }
+        if chart.kind.is_none() {
+            chart.kind = match info.get("type") {
+                Some(kind) => Some(
+                    Type::parse(kind).ok_or_else(|| format!("unknown chart type {:?}", kind))?,
+                ),
+                None => Some(Type::Bar),
+            };
+        }
+        if chart.title.is_none() {
+            chart.title = info.get("title").map(String::from);
+        }
+        if chart.series.is_empty() {
+            return Err("no values to plot".to_owned());
+        }
+        Ok(chart)
+    }
+
+    pub fn to_svg(&self) -> String {
+        let mut svg = format!(
+            "<svg class=\"chart\" viewBox=\"0 0 {} {}\" role=\"img\" \
+             font-size=\"14\" fill=\"currentColor\">\n",
+            WIDTH, HEIGHT
+        );
+        let mut top = 20.0;
+        if let Some(ref title) = self.title {
+            svg.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"24\" text-anchor=\"middle\" font-size=\"18\">{}</text>\n",
+                WIDTH / 2.0,
+                escape_html(title)
+            ));
+            top += 24.0;
+        }
+        match self.kind.unwrap_or(Type::Bar) {
+            Type::Pie => self.push_pie(&mut svg, top),
+            kind => {
+                if self.series.iter().any(|series| series.name.is_some()) {
+                    self.push_legend(&mut svg, top);
+                    top += 24.0;
+                }
+                self.push_axes(&mut svg, kind, top);
+            }
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+
+    fn label(&self, index: usize) -> String {
+        self.labels
+            .get(index)
+            .cloned()
+            .unwrap_or_else(|| (index + 1).to_string())
+    }
+
+    fn push_legend(&self, svg: &mut String, top: f64) {
+        let mut x = MARGIN_LEFT;
+        for (i, series) in self.series.iter().enumerate() {
+            let name = series.name.clone().unwrap_or_default();
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"12\" height=\"12\" fill=\"{}\"/>\
+                 <text x=\"{}\" y=\"{}\">{}</text>\n",
+                x,
+                top,
+                PALETTE[i % PALETTE.len()],
+                x + 18.0,
+                top + 11.0,
+                escape_html(&name)
+            ));
+            x += 40.0 + 8.0 * name.chars().count() as f64;
+        }
+    }
+
+    fn push_axes(&self, svg: &mut String, kind: Type, top: f64) {
+        let count = self
+            .series
+            .iter()
+            .map(|series| series.values.len())
+            .max()
+            .unwrap_or(0)
+            .max(self.labels.len())
+            .max(1);
+        let values = self.series.iter().flat_map(|series| series.values.iter());
+        let (min, max) = values.fold((0.0f64, 0.0f64), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        });
+        let step = nice_step((max - min) / 5.0);
+        let min = (min / step).floor() * step;
+        let max = ((max / step).ceil() * step).max(min + step);
+
+        let bottom = HEIGHT - MARGIN_BOTTOM;
+        let right = WIDTH - MARGIN_RIGHT;
+        let y = |value: f64| bottom - (value - min) / (max - min) * (bottom - top);
+        let band = (right - MARGIN_LEFT) / count as f64;
+
+        // Grid lines and their values
+        let decimals = (-step.log10().floor()).max(0.0) as usize;
+        let ticks = ((max - min) / step).round() as usize;
+        for i in 0..=ticks {
+            let value = min + i as f64 * step;
+            svg.push_str(&format!(
+                "<line x1=\"{}\" y1=\"{y:.1}\" x2=\"{}\" y2=\"{y:.1}\" stroke=\"currentColor\" \
+                 stroke-opacity=\"0.2\"/>\
+                 <text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\">{:.*}</text>\n",
+                MARGIN_LEFT,
+                right,
+                MARGIN_LEFT - 8.0,
+                y(value) + 5.0,
+                decimals,
+                value,
+                y = y(value)
+            ));
+        }
+        for i in 0..count {
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+                MARGIN_LEFT + band * (i as f64 + 0.5),
+                bottom + 24.0,
+                escape_html(&self.label(i))
+            ));
+        }
+
+        for (s, series) in self.series.iter().enumerate() {
+            let color = PALETTE[s % PALETTE.len()];
+            match kind {
+                Type::Line => {
+                    let points: Vec<(f64, f64)> = series
+                        .values
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &value)| (MARGIN_LEFT + band * (i as f64 + 0.5), y(value)))
+                        .collect();
+                    let path: Vec<String> = points
+                        .iter()
+                        .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+                        .collect();
+                    svg.push_str(&format!(
+                        "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"3\"/>\n",
+                        path.join(" "),
+                        color
+                    ));
+                    for (x, y) in points {
+                        svg.push_str(&format!(
+                            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"{}\"/>\n",
+                            x, y, color
+                        ));
+                    }
+                }
+                _ => {
+                    let width = band * 0.8 / self.series.len() as f64;
+                    for (i, &value) in series.values.iter().enumerate() {
+                        let x = MARGIN_LEFT + band * (i as f64 + 0.1) + width * s as f64;
+                        let (y1, y2) = (y(value.max(0.0)), y(value.min(0.0)));
+                        svg.push_str(&format!(
+                            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+                             fill=\"{}\"/>\n",
+                            x,
+                            y1,
+                            width,
+                            y2 - y1,
+                            color
+                        ));
+                    }
+                }
+            }
+        }
+        svg.push_str(&format!(
+            "<line x1=\"{}\" y1=\"{:.1}\" x2=\"{}\" y2=\"{:.1}\" stroke=\"currentColor\"/>\n",
+            MARGIN_LEFT,
+            y(0.0),
+            right,
+            y(0.0)
+        ));
+    }
+
+    /// Plot the first series as a pie whose slices are listed in a legend on the right
+    fn push_pie(&self, svg: &mut String, top: f64) {
+        let values: Vec<f64> = self.series[0]
+            .values
+            .iter()
+            .map(|value| value.max(0.0))
+            .collect();
+        let total: f64 = values.iter().sum();
+        let radius = (HEIGHT - top - 20.0) / 2.0;
+        let (cx, cy) = (MARGIN_LEFT + radius, top + radius);
+        let mut angle = -PI / 2.0;
+        for (i, &value) in values.iter().enumerate() {
+            let color = PALETTE[i % PALETTE.len()];
+            if total > 0.0 && value > 0.0 {
+                let sweep = value / total * 2.0 * PI;
+                if value == total {
+                    svg.push_str(&format!(
+                        "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\"/>\n",
+                        cx, cy, radius, color
+                    ));
+                } else {
+                    svg.push_str(&format!(
+                        "<path d=\"M{:.1},{:.1} L{:.1},{:.1} A{:.1},{:.1} 0 {} 1 {:.1},{:.1} Z\" \
+                         fill=\"{}\"/>\n",
+                        cx,
+                        cy,
+                        cx + radius * angle.cos(),
+                        cy + radius * angle.sin(),
+                        radius,
+                        radius,
+                        if sweep > PI { 1 } else { 0 },
+                        cx + radius * (angle + sweep).cos(),
+                        cy + radius * (angle + sweep).sin(),
+                        color
+                    ));
+                }
+                angle += sweep;
+            }
+            let y = top + 24.0 * i as f64;
+            svg.push_str(&format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"12\" height=\"12\" fill=\"{}\"/>\
+                 <text x=\"{:.1}\" y=\"{:.1}\">{}</text>\n",
+                cx + radius + 40.0,
+                y,
+                color,
+                cx + radius + 58.0,
+                y + 11.0,
+                escape_html(&self.label(i))
+            ));
+        }
+    }
+}
+
+/// Round the step between two grid lines to 1, 2 or 5 times a power of ten
+fn nice_step(raw: f64) -> f64 {
+    if raw <= 0.0 || !raw.is_finite() {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let residual = raw / magnitude;
+    let nice = if residual <= 1.0 {
+        1.0
+    } else if residual <= 2.0 {
+        2.0
+    } else if residual <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    nice * magnitude
+}
+
+fn parse_csv(source: &str) -> Result<Chart, String> {
+    let mut lines = source.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next().ok_or_else(|| "empty chart".to_owned())?;
+    let mut series: Vec<Series> = header
+        .split(',')
+        .skip(1)
+        .map(|name| Series {
+            name: Some(name.trim().to_owned()).filter(|name| !name.is_empty()),
+            values: Vec::new(),
+        })
+        .collect();
+    let mut labels = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let mut cells = line.split(',').map(str::trim);
+        labels.push(cells.next().unwrap_or_default().to_owned());
+        let values: Vec<&str> = cells.collect();
+        if values.len() != series.len() {
+            return Err(format!(
+                "row {} has {} values instead of {}",
+                i + 2,
+                values.len(),
+                series.len()
+            ));
+        }
+        for (series, value) in series.iter_mut().zip(values) {
+            let value = value
+                .parse()
+                .map_err(|_| format!("row {} has an invalid number {:?}", i + 2, value))?;
+            series.values.push(value);
+        }
+    }
+    Ok(Chart {
+        kind: None,
+        title: None,
+        labels,
+        series,
+        values: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let chart = Chart::parse("year,a,b\n2019,1,2\n2020,3,4.5\n", &Info::parse("chart"))
+            .expect("Failed to parse CSV");
+        assert_eq!(Some(Type::Bar), chart.kind);
+        assert_eq!(vec!["2019", "2020"], chart.labels);
+        assert_eq!(vec![3.0, 4.5], chart.series[1].values);
+        assert_eq!(4, chart.to_svg().matches("<rect").count() - 2);
+
+        let chart = Chart::parse(
+            "{\"type\": \"pie\", \"labels\": [\"x\", \"y\"], \"values\": [1, 3]}",
+            &Info::parse("chart {type=line}"),
+        )
+        .expect("Failed to parse JSON");
+        assert_eq!(Some(Type::Pie), chart.kind);
+        assert_eq!(2, chart.to_svg().matches("<path").count());
+
+        assert!(Chart::parse("year,a\n2019,x\n", &Info::default()).is_err());
+        assert!(Chart::parse("year,a\n2019,1\n", &Info::parse("chart {type=radar}")).is_err());
+    }
+
+    #[test]
+    fn test_nice_step() {
+        assert_eq!(1.0, nice_step(0.8));
+        assert_eq!(20.0, nice_step(13.0));
+        assert_eq!(0.5, nice_step(0.3));
+    }
+}
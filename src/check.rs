@@ -0,0 +1,509 @@
+use crate::{assets, error::Error, slides::Slide};
+use futures::{stream, StreamExt};
+use pulldown_cmark::{Event, Tag};
+use reqwest::Client;
+use ring::digest;
+use std::{collections::HashSet, fmt, path::Path, time::Duration};
+
+/// Link or image source found in a slide
+struct Link {
+    slide: usize,
+    url: String,
+}
+
+/// Broken link reported by `check`
+#[derive(Debug)]
+pub struct Issue {
+    pub slide: usize,
+    pub url: String,
+    pub reason: String,
+}
+
+impl Issue {
+    fn new<R: Into<String>>(link: Link, reason: R) -> Issue {
+        Issue {
+            slide: link.slide,
+            url: link.url,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "slide {}: {} ({})",
+            self.slide + 1,
+            self.url,
+            self.reason
+        )
+    }
+}
+
+/// Resource that the generated page would fetch from the network, the ones loaded by the custom
+/// css or javascript don't belong to any slide
+#[derive(Debug, PartialEq)]
+pub struct ExternalRequest {
+    pub slide: Option<usize>,
+    pub url: String,
+}
+
+impl fmt::Display for ExternalRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.slide {
+            Some(slide) => write!(f, "slide {}: {} (external request)", slide + 1, self.url),
+            None => write!(f, "{} (external request)", self.url),
+        }
+    }
+}
+
+/// Stylesheet or script of a generated page loaded from another host
+#[derive(Debug, PartialEq)]
+pub struct LinkedAsset {
+    pub url: String,
+    /// Value of the `integrity` attribute, which may list several hashes
+    pub integrity: Option<String>,
+}
+
+/// Linked asset whose content can't be trusted, reported by `check --integrity`
+#[derive(Debug)]
+pub struct IntegrityIssue {
+    pub url: String,
+    pub reason: String,
+}
+
+impl fmt::Display for IntegrityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.url, self.reason)
+    }
+}
+
+/// Settings of the HTTP requests used to check external links
+pub struct ExternalOptions {
+    pub timeout: Duration,
+    pub concurrency: usize,
+}
+
+fn collect(slides: &[Slide]) -> Vec<Link> {
+    let mut links = Vec::new();
+    for slide in slides {
+        for event in &slide.events {
+            match event {
+                Event::Start(Tag::Link(_, url, _)) | Event::Start(Tag::Image(_, url, _))
+                    if !url.is_empty() =>
+                {
+                    links.push(Link {
+                        slide: slide.index,
+                        url: url.to_string(),
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+    links
+}
+
+async fn check_url(client: &Client, url: &str) -> Option<String> {
+    // Some servers don't support HEAD requests, retry with a GET before reporting an error
+    let response = match client.head(url).send().await {
+        Ok(response) if response.status().is_success() => return None,
+        _ => client.get(url).send().await,
+    };
+    match response {
+        Ok(response) if response.status().is_success() => None,
+        Ok(response) => Some(format!("HTTP status {}", response.status())),
+        Err(err) => Some(err.to_string()),
+    }
+}
+
+/// Verify that links to other slides point to existing ones, that local files exist relative to
+/// the base directory and, if enabled, that external URLs can be reached
+pub async fn check(
+    slides: &[Slide<'_>],
+    base_dir: &Path,
+    external: Option<ExternalOptions>,
+) -> Result<Vec<Issue>, Error> {
+    let ids: HashSet<&str> = slides.iter().map(|slide| slide.id.as_str()).collect();
+    // Links to a slide by its position, e.g. `#slide-3`, are still followed
+    let is_position = |id: &str| {
+        id.strip_prefix("slide-")
+            .and_then(|n| n.parse::<usize>().ok())
+            .map_or(false, |n| n >= 1 && n <= slides.len())
+    };
+    let mut issues = Vec::new();
+    let mut external_links = Vec::new();
+    for link in collect(slides) {
+        if let Some(id) = link.url.strip_prefix('#') {
+            if !ids.contains(id) && !is_position(id) {
+                issues.push(Issue::new(link, "no slide with this id"));
+            }
+        } else if link.url.starts_with("http://") || link.url.starts_with("https://") {
+            external_links.push(link);
+        } else if assets::is_local(&link.url) {
+            if !base_dir.join(assets::url_path(&link.url)).exists() {
+                issues.push(Issue::new(link, "file not found"));
+            }
+        }
+    }
+
+    if let Some(options) = external {
+        let client = Client::builder().timeout(options.timeout).build()?;
+        let client = &client;
+        let results: Vec<(Link, Option<String>)> = stream::iter(external_links)
+            .map(move |link| async move {
+                let reason = check_url(client, &link.url).await;
+                (link, reason)
+            })
+            .buffer_unordered(options.concurrency.max(1))
+            .collect()
+            .await;
+        for (link, reason) in results {
+            if let Some(reason) = reason {
+                issues.push(Issue::new(link, reason));
+            }
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.slide);
+    Ok(issues)
+}
+
+fn is_external(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    url.starts_with("http://") || url.starts_with("https://") || url.starts_with("//")
+}
+
+/// Attributes of an HTML tag along with their values, quoted or not
+fn attributes(tag: &str) -> Vec<(String, &str)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq]
+            .trim_end()
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let value = rest[eq + 1..].trim_start();
+        let start = rest.len() - value.len();
+        let (value, consumed) = match value.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => match value[1..].find(quote) {
+                Some(end) => (&value[1..end + 1], end + 2),
+                None => break,
+            },
+            _ => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                (&value[..end], end)
+            }
+        };
+        attributes.push((name, value));
+        rest = &rest[start + consumed..];
+    }
+    attributes
+}
+
+/// URLs loaded by a stylesheet through `url()` and `@import`
+fn css_urls(css: &str) -> Vec<&str> {
+    let mut urls = Vec::new();
+    for (i, _) in css.match_indices("url(") {
+        let rest = &css[i + 4..];
+        if let Some(end) = rest.find(')') {
+            urls.push(rest[..end].trim().trim_matches(|c| c == '"' || c == '\''));
+        }
+    }
+    for (i, _) in css.match_indices("@import") {
+        let rest = css[i + 7..].trim_start();
+        if let Some(quote) = rest.chars().next().filter(|&c| c == '"' || c == '\'') {
+            if let Some(end) = rest[1..].find(quote) {
+                urls.push(&rest[1..end + 1]);
+            }
+        }
+    }
+    urls
+}
+
+/// Subresource integrity hash of the content of a file, as embedded by `build --integrity`
+pub fn integrity(content: &[u8]) -> String {
+    let hash = digest::digest(&digest::SHA384, content);
+    format!("sha384-{}", base64::encode(hash.as_ref()))
+}
+
+/// Whether the content matches one of the hashes listed by an `integrity` attribute
+fn matches_integrity(integrity: &str, content: &[u8]) -> bool {
+    integrity.split_whitespace().any(|hash| {
+        let (algorithm, expected) = match hash.split_once('-') {
+            Some(parts) => parts,
+            None => return false,
+        };
+        let algorithm = match algorithm {
+            "sha256" => &digest::SHA256,
+            "sha384" => &digest::SHA384,
+            "sha512" => &digest::SHA512,
+            _ => return false,
+        };
+        // Options may follow the digest, e.g. `sha384-...?foo`
+        let expected = expected.split('?').next().unwrap_or_default();
+        base64::encode(digest::digest(algorithm, content).as_ref()) == expected
+    })
+}
+
+/// Download the file at the given URL, failing on HTTP errors
+pub async fn fetch(client: &Client, url: &str) -> Result<Vec<u8>, Error> {
+    let response = client.get(url).send().await?.error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Find the stylesheets and scripts that the HTML page loads from other hosts
+pub fn linked_assets(html: &str) -> Vec<LinkedAsset> {
+    let mut assets = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if rest.starts_with("!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let attributes = attributes(tag);
+        let value = |name: &str| {
+            attributes
+                .iter()
+                .find(|(attribute, _)| attribute == name)
+                .map(|(_, value)| value.replace("&amp;", "&"))
+        };
+        let url = match name.as_str() {
+            "link"
+                if value("rel").map_or(false, |rel| {
+                    rel.split_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("stylesheet"))
+                }) =>
+            {
+                value("href")
+            }
+            "script" => value("src"),
+            _ => None,
+        };
+        if let Some(url) = url.filter(|url| is_external(url)) {
+            assets.push(LinkedAsset {
+                url,
+                integrity: value("integrity"),
+            });
+        }
+        // The content of scripts and stylesheets isn't markup
+        if name == "style" || name == "script" {
+            let end = rest.find(&format!("</{}", name)).unwrap_or(rest.len());
+            rest = &rest[end..];
+        }
+    }
+    assets
+}
+
+/// Verify that every linked asset has an integrity hash and that its current content matches it
+pub async fn check_integrity(
+    assets: Vec<LinkedAsset>,
+    timeout: Duration,
+) -> Result<Vec<IntegrityIssue>, Error> {
+    let client = Client::builder().timeout(timeout).build()?;
+    let mut issues = Vec::new();
+    for asset in assets {
+        let expected = match asset.integrity {
+            Some(expected) => expected,
+            None => {
+                issues.push(IntegrityIssue {
+                    url: asset.url,
+                    reason: "no integrity hash".to_owned(),
+                });
+                continue;
+            }
+        };
+        let reason = match fetch(&client, &asset.url).await {
+            Ok(content) if matches_integrity(&expected, &content) => continue,
+            Ok(content) => format!(
+                "integrity hash mismatch, the file now hashes to {}",
+                integrity(&content)
+            ),
+            Err(err) => err.to_string(),
+        };
+        issues.push(IntegrityIssue {
+            url: asset.url,
+            reason,
+        });
+    }
+    Ok(issues)
+}
+
+/// Find the resources that the HTML page would request from other hosts when opened, such as
+/// images, videos, iframes, stylesheets and fonts. Links are only followed when clicked and
+/// aren't reported, neither are the requests sent by scripts.
+pub fn external_requests(html: &str) -> Vec<ExternalRequest> {
+    let mut requests = Vec::new();
+    let mut slides = 0;
+    let mut slide = None;
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if rest.starts_with("!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let mut urls = Vec::new();
+        for (attribute, value) in attributes(tag) {
+            match attribute.as_str() {
+                "src" | "data-src" | "poster" | "data" => urls.push(value),
+                "srcset" | "data-srcset" => urls.extend(
+                    value
+                        .split(',')
+                        .filter_map(|candidate| candidate.split_whitespace().next()),
+                ),
+                "href" if name == "link" => urls.push(value),
+                "style" => urls.extend(css_urls(value)),
+                "class" if name == "div" && value.split_whitespace().next() == Some("slide") => {
+                    slide = Some(slides);
+                    slides += 1;
+                }
+                _ => {}
+            }
+        }
+        // The content of scripts and stylesheets isn't markup
+        if name == "style" || name == "script" {
+            let end = rest.find(&format!("</{}", name)).unwrap_or(rest.len());
+            if name == "style" {
+                urls.extend(css_urls(&rest[..end]));
+            }
+            rest = &rest[end..];
+        }
+        requests.extend(urls.into_iter().filter(|url| is_external(url)).map(|url| {
+            ExternalRequest {
+                slide,
+                url: url.replace("&amp;", "&"),
+            }
+        }));
+    }
+    requests
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slides;
+
+    #[tokio::test]
+    async fn test_check() {
+        let input = r#"
+# Slide 1
+
+[Next](#slide-2) [Missing](#slide-3) [Remote](https://example.com)
+
+---
+
+![Logo](does-not-exist.png) [Cargo](Cargo.toml)
+"#;
+        let slides = slides::parse(input);
+        let issues = check(&slides, Path::new(env!("CARGO_MANIFEST_DIR")), None)
+            .await
+            .expect("Failed to check links");
+        assert_eq!(2, issues.len());
+        assert_eq!((0, "#slide-3"), (issues[0].slide, issues[0].url.as_str()));
+        assert_eq!(
+            (1, "does-not-exist.png"),
+            (issues[1].slide, issues[1].url.as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_escaped() {
+        let dir = std::env::temp_dir().join(format!("deck-check-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("my photo.png"), "").unwrap();
+        let slides = slides::parse("![Photo](my%20photo.png?v=2) [Video](my%20video.mp4)");
+        let issues = check(&slides, &dir, None)
+            .await
+            .expect("Failed to check links");
+        std::fs::remove_dir_all(&dir).unwrap();
+        let urls: Vec<&str> = issues.iter().map(|issue| issue.url.as_str()).collect();
+        assert_eq!(vec!["my%20video.mp4"], urls);
+    }
+
+    #[test]
+    fn test_integrity() {
+        let css = b"body { color: red; }";
+        assert_eq!(
+            "sha384-BN8siYsJqlPeNsRFs2pYbTW0uiUBy9v6JVVKpHaS+KNqD0ZFotD5OFKMkI6/s6sb",
+            integrity(css)
+        );
+        assert!(matches_integrity(
+            "sha256-XeYlw2NVzOfB1UCIJqCyGr+0n7bA4fFslFpvKu84IAw= md5-abc",
+            css
+        ));
+        assert!(!matches_integrity(&integrity(b"body {}"), css));
+
+        let html = r#"<link rel="stylesheet" href="https://example.com/deck.css" integrity="sha384-abc" crossorigin="anonymous">
+<link rel="icon" href="https://example.com/favicon.ico">
+<script src="https://example.com/deck.js"></script>
+<script>const a = '<script src="https://example.com/b.js">';</script>
+<script src="local.js"></script>"#;
+        assert_eq!(
+            vec![
+                LinkedAsset {
+                    url: "https://example.com/deck.css".to_owned(),
+                    integrity: Some("sha384-abc".to_owned()),
+                },
+                LinkedAsset {
+                    url: "https://example.com/deck.js".to_owned(),
+                    integrity: None,
+                },
+            ],
+            linked_assets(html)
+        );
+    }
+
+    #[test]
+    fn test_external_requests() {
+        let html = r#"<style>@import "https://fonts.example.com/css";body{background:url(bg.png)}</style>
+<script>if (a < b) { fetch("https://example.com"); }</script>
+<div class="slide" id="slide-1"><div class="content">
+<p><a href="https://example.com">Link</a> <img src="logo.png"> <!-- <img src="https://example.com/a.png"> --></p>
+</div></div>
+<div class="slide layout-image" id="slide-2"><div class="content">
+<p><img data-src="https://example.com/b.png?w=1&amp;h=2" srcset='https://example.com/b-640w.png 640w, b.png 1000w'>
+<span style="background: url('//cdn.example.com/c.png')"></span></p>
+</div></div>"#;
+        let requests = external_requests(html);
+        let requests: Vec<(Option<usize>, &str)> = requests
+            .iter()
+            .map(|request| (request.slide, request.url.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (None, "https://fonts.example.com/css"),
+                (Some(1), "https://example.com/b.png?w=1&h=2"),
+                (Some(1), "https://example.com/b-640w.png"),
+                (Some(1), "//cdn.example.com/c.png"),
+            ],
+            requests
+        );
+    }
+}
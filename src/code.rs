@@ -0,0 +1,383 @@
+use crate::{error::Error, slides::Slide};
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    process::{self, Command, Output},
+};
+use syntect::parsing::SyntaxSet;
+
+/// Information string of a fenced code block such as `rust {file=main.rs}`. The language comes
+/// first and is followed by attributes which are either flags or `key=value` pairs.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Info {
+    pub lang: String,
+    pub attributes: Vec<(String, Option<String>)>,
+}
+
+impl Info {
+    pub fn parse(info: &str) -> Info {
+        let info = info.trim();
+        let lang_end = info
+            .find(|c: char| c.is_whitespace() || c == '{')
+            .unwrap_or(info.len());
+        let (lang, rest) = info.split_at(lang_end);
+        let attributes = rest
+            .trim()
+            .trim_start_matches('{')
+            .trim_end_matches('}')
+            .split_whitespace()
+            .map(|attribute| match attribute.find('=') {
+                Some(i) => (
+                    attribute[..i].to_owned(),
+                    Some(attribute[i + 1..].to_owned()),
+                ),
+                None => (attribute.to_owned(), None),
+            })
+            .collect();
+        Info {
+            lang: lang.to_owned(),
+            attributes,
+        }
+    }
+
+    /// Value of the attribute with the given key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    /// Whether the attribute with the given key is present, with or without a value
+    pub fn has(&self, key: &str) -> bool {
+        self.attributes.iter().any(|(k, _)| k == key)
+    }
+
+    /// Key of the first attribute given more than once, only the first value being used
+    pub fn duplicate(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .enumerate()
+            .find(|(i, (key, _))| self.attributes[..*i].iter().any(|(k, _)| k == key))
+            .map(|(_, (key, _))| key.as_str())
+    }
+}
+
+/// Fenced code block found in a slide
+#[derive(Debug)]
+pub struct CodeBlock {
+    pub slide: usize,
+    pub info: Info,
+    pub code: String,
+}
+
+/// Collect all fenced code blocks of the given slides
+pub fn collect(slides: &[Slide]) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    for slide in slides {
+        let mut current: Option<CodeBlock> = None;
+        for event in &slide.events {
+            match event {
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                    current = Some(CodeBlock {
+                        slide: slide.index,
+                        info: Info::parse(info),
+                        code: String::new(),
+                    });
+                }
+                Event::Text(text) => {
+                    if let Some(ref mut block) = current {
+                        block.code.push_str(text);
+                    }
+                }
+                Event::End(Tag::CodeBlock(_)) => {
+                    if let Some(block) = current.take() {
+                        blocks.push(block);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    blocks
+}
+
+#[derive(Serialize)]
+struct ManifestEntry<'a> {
+    slide: usize,
+    lang: &'a str,
+    file: PathBuf,
+}
+
+/// Write each code block to its own file in the output directory along with a `manifest.json`
+/// listing them. Blocks are written to the path given by their `file` attribute, or named after
+/// their slide and language otherwise.
+pub fn extract(
+    blocks: &[CodeBlock],
+    syntax_set: &SyntaxSet,
+    output_dir: &Path,
+) -> Result<(), Error> {
+    let files = output_files(blocks, syntax_set)?;
+    fs::create_dir_all(output_dir)?;
+    let mut manifest = Vec::with_capacity(blocks.len());
+    for (block, file) in blocks.iter().zip(files) {
+        let path = output_dir.join(&file);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, &block.code)?;
+        manifest.push(ManifestEntry {
+            slide: block.slide + 1,
+            lang: &block.info.lang,
+            file,
+        });
+    }
+    let manifest = serde_json::to_string_pretty(&manifest)?;
+    fs::write(output_dir.join(MANIFEST), manifest)?;
+    Ok(())
+}
+
+/// Name of the manifest written next to the extracted code blocks
+const MANIFEST: &str = "manifest.json";
+
+/// Path of each code block relative to the output directory, either given by its `file`
+/// attribute or named after its slide. Two blocks, or a block and the manifest, can't share a
+/// path since one would silently overwrite the other.
+fn output_files(blocks: &[CodeBlock], syntax_set: &SyntaxSet) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::with_capacity(blocks.len());
+    let mut slides = HashMap::new();
+    let mut previous_slide = None;
+    let mut index_in_slide = 0;
+    for block in blocks {
+        // A second `file` would silently be ignored
+        if let Some(name) = block.info.duplicate() {
+            return Err(Error::DuplicateAttribute {
+                slide: block.slide + 1,
+                name: name.to_owned(),
+            });
+        }
+        if previous_slide == Some(block.slide) {
+            index_in_slide += 1;
+        } else {
+            previous_slide = Some(block.slide);
+            index_in_slide = 1;
+        }
+        let file = match block.info.get("file") {
+            Some(file) => {
+                let file = PathBuf::from(file);
+                let is_relative = file
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+                if !is_relative {
+                    return Err(Error::InvalidCodeBlockPath(file));
+                }
+                // `./main.rs` and `main.rs` are the same file
+                file.components()
+                    .filter(|c| *c != Component::CurDir)
+                    .collect()
+            }
+            None => {
+                let extension = syntax_set
+                    .find_syntax_by_token(&block.info.lang)
+                    .and_then(|syntax| syntax.file_extensions.first())
+                    .map(String::as_str)
+                    .unwrap_or("txt");
+                PathBuf::from(format!(
+                    "slide-{}-{}.{}",
+                    block.slide + 1,
+                    index_in_slide,
+                    extension
+                ))
+            }
+        };
+        if file == Path::new(MANIFEST) {
+            return Err(Error::DuplicateCodeBlockPath {
+                path: file,
+                slide: block.slide + 1,
+                previous: None,
+            });
+        }
+        if let Some(previous) = slides.insert(file.clone(), block.slide + 1) {
+            return Err(Error::DuplicateCodeBlockPath {
+                path: file,
+                slide: block.slide + 1,
+                previous: Some(previous),
+            });
+        }
+        files.push(file);
+    }
+    Ok(files)
+}
+
+/// Compile, if needed, and run a code block from the given working directory
+pub fn run(block: &CodeBlock, dir: &Path) -> Result<Output, Error> {
+    fs::create_dir_all(dir)?;
+    let output = match block.info.lang.as_str() {
+        "rust" | "rs" => {
+            let source = dir.join("main.rs");
+            fs::write(&source, &block.code)?;
+            let binary = dir.join("main");
+            let output = Command::new("rustc")
+                .arg("--edition=2018")
+                .arg("-o")
+                .arg(&binary)
+                .arg(&source)
+                .output()?;
+            if !output.status.success() {
+                return Ok(output);
+            }
+            Command::new(&binary).current_dir(dir).output()?
+        }
+        lang @ "sh" | lang @ "bash" | lang @ "python" | lang @ "py" => {
+            let interpreter = match lang {
+                "sh" => "sh",
+                "bash" => "bash",
+                _ => "python3",
+            };
+            let script = dir.join("script");
+            fs::write(&script, &block.code)?;
+            Command::new(interpreter)
+                .arg(&script)
+                .current_dir(dir)
+                .output()?
+        }
+        lang => return Err(Error::UnsupportedTestLanguage(lang.to_owned())),
+    };
+    Ok(output)
+}
+
+/// Run all code blocks marked with the `test` attribute, report their outcome on stdout and
+/// return the number of failures
+pub fn test(blocks: &[CodeBlock]) -> Result<usize, Error> {
+    let dir = env::temp_dir().join(format!("deck-test-{}", process::id()));
+    let blocks: Vec<&CodeBlock> = blocks.iter().filter(|b| b.info.has("test")).collect();
+    println!("running {} tests", blocks.len());
+    let mut failed = 0;
+    for (i, block) in blocks.iter().enumerate() {
+        let name = format!("slide {} ({})", block.slide + 1, block.info.lang);
+        match run(block, &dir.join(i.to_string())) {
+            Ok(ref output) if output.status.success() => {
+                println!("test {} ... ok", name);
+            }
+            Ok(output) => {
+                failed += 1;
+                println!("test {} ... FAILED", name);
+                let mut stdout = io::stdout();
+                stdout.write_all(&output.stdout)?;
+                stdout.write_all(&output.stderr)?;
+            }
+            Err(err) => {
+                failed += 1;
+                println!("test {} ... FAILED: {}", name, err);
+            }
+        }
+    }
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    println!(
+        "test result: {}. {} passed; {} failed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        blocks.len() - failed,
+        failed
+    );
+    Ok(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slides;
+
+    #[test]
+    fn test_parse_info() {
+        assert_eq!(Info::default(), Info::parse(""));
+        assert_eq!(
+            Info {
+                lang: "rust".into(),
+                attributes: vec![
+                    ("file".into(), Some("src/main.rs".into())),
+                    ("test".into(), None)
+                ],
+            },
+            Info::parse("rust {file=src/main.rs test}")
+        );
+        assert_eq!(
+            Some("main.py"),
+            Info::parse("python file=main.py").get("file")
+        );
+        assert!(Info::parse("{test}").has("test"));
+        assert_eq!(None, Info::parse("rust {file=a.rs test}").duplicate());
+        assert_eq!(
+            Some("file"),
+            Info::parse("rust {file=a.rs test file=b.rs}").duplicate()
+        );
+    }
+
+    #[test]
+    fn test_output_files() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let files = |input| output_files(&collect(&slides::parse(input)), &syntax_set);
+        assert_eq!(
+            vec![
+                PathBuf::from("slide-1-1.rs"),
+                PathBuf::from("slide-1-2.txt"),
+                PathBuf::from("src/lib.rs")
+            ],
+            files("```rust\n```\n\n```\n```\n\n---\n\n```rust {file=./src/lib.rs}\n```\n").unwrap()
+        );
+        assert!(matches!(
+            files("```rust {file=main.rs}\n```\n\n---\n\n```rust {file=./main.rs}\n```\n"),
+            Err(Error::DuplicateCodeBlockPath {
+                slide: 2,
+                previous: Some(1),
+                ..
+            })
+        ));
+        assert!(matches!(
+            files("```rust\n```\n\n---\n\n```rust {file=slide-1-1.rs}\n```\n"),
+            Err(Error::DuplicateCodeBlockPath {
+                slide: 2,
+                previous: Some(1),
+                ..
+            })
+        ));
+        assert!(matches!(
+            files("```json {file=manifest.json}\n```\n"),
+            Err(Error::DuplicateCodeBlockPath {
+                slide: 1,
+                previous: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_collect() {
+        let input = r#"
+```rust
+fn main() {}
+```
+
+---
+
+    indented
+
+```
+plain
+```
+"#;
+        let slides = slides::parse(input);
+        let blocks = collect(&slides);
+        assert_eq!(2, blocks.len());
+        assert_eq!(0, blocks[0].slide);
+        assert_eq!("rust", blocks[0].info.lang);
+        assert_eq!("fn main() {}\n", blocks[0].code);
+        assert_eq!(1, blocks[1].slide);
+        assert_eq!("plain\n", blocks[1].code);
+    }
+}
@@ -0,0 +1,485 @@
+use crate::{front_matter, html::escape_html, slides::parse_directive};
+use serde_yaml::{Mapping, Value};
+use std::str::FromStr;
+
+/// Markdown dialect of another presentation tool that can be converted to the one of deck
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compat {
+    Remark,
+    Marp,
+    Pandoc,
+}
+
+impl Compat {
+    pub fn variants() -> [&'static str; 3] {
+        ["remark", "marp", "pandoc"]
+    }
+}
+
+impl FromStr for Compat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Compat, String> {
+        match s {
+            "remark" => Ok(Compat::Remark),
+            "marp" => Ok(Compat::Marp),
+            "pandoc" => Ok(Compat::Pandoc),
+            _ => Err(format!("Unknown compatibility mode: {}", s)),
+        }
+    }
+}
+
+/// Directives of deck that are kept as is in Marp decks, the other comments being notes
+const DECK_DIRECTIVES: [&str; 4] = ["notes", "layout", "time", "class"];
+
+/// Global and local directives of Marp, they have no equivalent and are dropped
+const MARP_DIRECTIVES: [&str; 12] = [
+    "theme",
+    "style",
+    "headingDivider",
+    "size",
+    "math",
+    "marp",
+    "paginate",
+    "header",
+    "footer",
+    "color",
+    "backgroundColor",
+    "backgroundImage",
+];
+
+/// Lines of a slide and whether it continues the previous one, as remark does with `--`
+struct Source<'a> {
+    lines: Vec<&'a str>,
+    is_continuation: bool,
+}
+
+/// Convert a deck written for another tool to the markdown understood by deck, the front matter
+/// is kept as is. The slide level only applies to pandoc decks.
+pub fn convert(input: &str, compat: Compat, slide_level: Option<usize>) -> String {
+    let (front_matter, body) = front_matter::split(input);
+    let mut output = input[..input.len() - body.len()].to_owned();
+    if compat == Compat::Pandoc {
+        output.push_str(&convert_pandoc(body, slide_level, front_matter.is_none()));
+        return output;
+    }
+    let sources = split(body, compat == Compat::Remark);
+    let slides = match compat {
+        Compat::Remark => convert_remark(sources),
+        Compat::Marp => convert_marp(sources, front_matter.as_ref()),
+    };
+    output.push_str(&slides.join("\n\n---\n\n"));
+    output.push('\n');
+    output
+}
+
+/// Split the markdown into slides on `---` lines, and `--` ones when continuations are enabled,
+/// that are not part of code blocks
+fn split(body: &str, continuations: bool) -> Vec<Source> {
+    let mut sources = vec![Source {
+        lines: Vec::new(),
+        is_continuation: false,
+    }];
+    let mut fence: Option<&str> = None;
+    for line in body.lines() {
+        let trimmed = line.trim();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+        } else if trimmed.starts_with("```") {
+            fence = Some("```");
+        } else if trimmed.starts_with("~~~") {
+            fence = Some("~~~");
+        } else if trimmed == "---" || (continuations && trimmed == "--") {
+            sources.push(Source {
+                lines: Vec::new(),
+                is_continuation: trimmed == "--",
+            });
+            continue;
+        }
+        if let Some(source) = sources.last_mut() {
+            source.lines.push(line);
+        }
+    }
+    sources
+}
+
+/// Comment holding the notes of a slide, the text is altered so that it cannot close it early
+fn notes_directive(notes: &str) -> String {
+    format!("<!-- notes: {} -->", notes.trim().replace("-->", "- ->"))
+}
+
+/// Class directive with the classes written as `center, middle` or `lead invert`
+fn class_directive(classes: &str) -> String {
+    let classes: Vec<&str> = classes
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|class| !class.is_empty())
+        .collect();
+    format!("<!-- class: {} -->", classes.join(" "))
+}
+
+/// Splits the leading `name: value` properties of a remark slide from its content
+fn remark_properties<'a>(lines: &[&'a str]) -> (Vec<(&'a str, &'a str)>, usize) {
+    let mut properties = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() && properties.is_empty() {
+            continue;
+        }
+        let property = line.find(':').and_then(|separator| {
+            let name = &line[..separator];
+            let is_name = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if is_name {
+                Some((name, line[separator + 1..].trim()))
+            } else {
+                None
+            }
+        });
+        match property {
+            Some(property) => properties.push(property),
+            None => return (properties, i),
+        }
+    }
+    (properties, lines.len())
+}
+
+/// Remark slides may start with properties, such as `class: center, middle`, and end with notes
+/// introduced by `???`. Slides continued with `--` repeat the content of the previous one, and
+/// templates set with `layout: true` or slides set with `exclude: true` are dropped.
+fn convert_remark(sources: Vec<Source>) -> Vec<String> {
+    let mut slides = Vec::new();
+    let mut classes = None;
+    let mut excluded = false;
+    let mut previous = String::new();
+    for source in sources {
+        let mut lines = &source.lines[..];
+        if !source.is_continuation {
+            let (properties, start) = remark_properties(lines);
+            lines = &lines[start..];
+            classes = None;
+            excluded = false;
+            for (name, value) in properties {
+                match name {
+                    "class" => classes = Some(value),
+                    "layout" | "exclude" if value == "true" => excluded = true,
+                    _ => {}
+                }
+            }
+            previous.clear();
+        }
+        if excluded {
+            continue;
+        }
+        let (content, notes) = match lines.iter().position(|line| line.trim() == "???") {
+            Some(i) => (&lines[..i], Some(lines[i + 1..].join("\n"))),
+            None => (lines, None),
+        };
+        if !previous.is_empty() {
+            previous.push('\n');
+        }
+        previous.push_str(&content.join("\n"));
+
+        let mut slide = String::new();
+        if let Some(classes) = classes {
+            slide.push_str(&class_directive(classes));
+            slide.push_str("\n\n");
+        }
+        slide.push_str(previous.trim_matches('\n'));
+        if let Some(notes) = notes.filter(|notes| !notes.trim().is_empty()) {
+            slide.push_str("\n\n");
+            slide.push_str(&notes_directive(&notes));
+        }
+        slides.push(slide);
+    }
+    slides
+}
+
+/// Marp directives are written as comments, the classes set with `class` apply to the following
+/// slides as well while those set with `_class` only apply to the current one. Other comments
+/// are the notes of the slide.
+fn convert_marp(sources: Vec<Source>, front_matter: Option<&Mapping>) -> Vec<String> {
+    let mut classes = front_matter
+        .and_then(|mapping| mapping.get(&Value::String("class".to_owned())))
+        .and_then(Value::as_str)
+        .map(String::from);
+    let mut slides = Vec::new();
+    for source in sources {
+        let mut spot_classes = None;
+        let mut content = Vec::new();
+        let mut comment: Option<String> = None;
+        let mut fence: Option<&str> = None;
+        for line in source.lines {
+            let trimmed = line.trim();
+            if comment.is_none() && fence.is_none() && trimmed.starts_with("<!--") {
+                comment = Some(String::new());
+            }
+            let text = match comment {
+                Some(ref mut text) => text,
+                None => {
+                    if let Some(marker) = fence {
+                        if trimmed.starts_with(marker) {
+                            fence = None;
+                        }
+                    } else if trimmed.starts_with("```") {
+                        fence = Some("```");
+                    } else if trimmed.starts_with("~~~") {
+                        fence = Some("~~~");
+                    }
+                    content.push(line.to_owned());
+                    continue;
+                }
+            };
+            text.push_str(line);
+            text.push('\n');
+            if !trimmed.ends_with("-->") {
+                continue;
+            }
+            let text = comment.take().unwrap_or_default();
+            match parse_directive(&text) {
+                Some(("class", value)) => classes = Some(value.to_owned()),
+                Some(("_class", value)) => spot_classes = Some(value.to_owned()),
+                Some((key, _)) if DECK_DIRECTIVES.contains(&key) => {
+                    content.push(text.trim_end().to_owned())
+                }
+                Some((key, _)) if key.starts_with('_') || MARP_DIRECTIVES.contains(&key) => {}
+                _ => {
+                    let notes = text
+                        .trim()
+                        .trim_start_matches("<!--")
+                        .trim_end_matches("-->");
+                    if !notes.trim().is_empty() {
+                        content.push(notes_directive(notes));
+                    }
+                }
+            }
+        }
+        if let Some(text) = comment {
+            content.push(text);
+        }
+
+        let mut slide = String::new();
+        if let Some(classes) = spot_classes.as_ref().or_else(|| classes.as_ref()) {
+            slide.push_str(&class_directive(classes));
+            slide.push_str("\n\n");
+        }
+        slide.push_str(content.join("\n").trim_matches('\n'));
+        slides.push(slide);
+    }
+    slides
+}
+
+/// Level of an ATX heading such as `## Title`
+fn heading_level(line: &str) -> Option<usize> {
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    let rest = &trimmed[level..];
+    if (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(char::is_whitespace)) {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+/// Default slide level of pandoc, the highest level of the headings directly followed by content
+fn default_slide_level(lines: &[&str]) -> usize {
+    let mut slide_level = None;
+    let mut fence: Option<&str> = None;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        } else if trimmed.starts_with("```") {
+            fence = Some("```");
+        } else if trimmed.starts_with("~~~") {
+            fence = Some("~~~");
+        }
+        let level = match heading_level(line) {
+            Some(level) => level,
+            None => continue,
+        };
+        let next = lines[i + 1..].iter().find(|line| !line.trim().is_empty());
+        if next.map_or(false, |next| heading_level(next).is_none()) {
+            slide_level =
+                Some(slide_level.map_or(level, |slide_level: usize| slide_level.min(level)));
+        }
+    }
+    slide_level.unwrap_or(1)
+}
+
+/// Block opened by a pandoc fenced div
+enum Div {
+    /// Speaker notes being collected
+    Notes(String),
+    Html,
+}
+
+/// Convert the attributes of a fenced div, such as `{.column width="40%"}` or `incremental`, to
+/// the attributes of an HTML element. Returns None for speaker notes.
+fn div_attributes(attributes: &str) -> Option<String> {
+    let attributes = attributes.trim().trim_end_matches(':').trim();
+    let mut classes = Vec::new();
+    let mut html = String::new();
+    match attributes
+        .strip_prefix('{')
+        .and_then(|attributes| attributes.strip_suffix('}'))
+    {
+        Some(attributes) => {
+            for attribute in attributes.split_whitespace() {
+                if let Some(class) = attribute.strip_prefix('.') {
+                    classes.push(class);
+                } else if let Some(id) = attribute.strip_prefix('#') {
+                    html.push_str(&format!(" id=\"{}\"", escape_html(id)));
+                } else if let Some(width) = attribute.strip_prefix("width=") {
+                    let width = width.trim_matches(|c| c == '"' || c == '\'');
+                    html.push_str(&format!(" style=\"flex:0 0 {}\"", escape_html(width)));
+                }
+            }
+        }
+        None => classes.extend(attributes.split_whitespace()),
+    }
+    if classes.contains(&"notes") {
+        return None;
+    }
+    if !classes.is_empty() {
+        html.insert_str(
+            0,
+            &format!(" class=\"{}\"", escape_html(&classes.join(" "))),
+        );
+    }
+    Some(html)
+}
+
+/// Push a line to the speaker notes being collected, if any, or to the output
+fn push_line(divs: &mut [Div], output: &mut Vec<String>, line: String) {
+    match divs.last_mut() {
+        Some(Div::Notes(notes)) => {
+            notes.push_str(&line);
+            notes.push('\n');
+        }
+        _ => output.push(line),
+    }
+}
+
+/// Pandoc starts a new slide at each heading up to the slide level, in addition to horizontal
+/// rules, and uses fenced divs such as `::: notes` for speaker notes or `:::: columns` for
+/// layouts. A title block made of `%` lines is turned into a front matter.
+fn convert_pandoc(body: &str, slide_level: Option<usize>, allow_title_block: bool) -> String {
+    let mut lines: Vec<&str> = body.lines().collect();
+    let mut output = Vec::new();
+    if allow_title_block {
+        let title_block = lines
+            .iter()
+            .take(3)
+            .take_while(|line| line.starts_with('%'))
+            .count();
+        if let Some(title) = lines.first().and_then(|line| line.strip_prefix('%')) {
+            let title = serde_json::to_string(title.trim()).unwrap_or_default();
+            output.push(format!("---\ntitle: {}\n---", title));
+        }
+        lines.drain(..title_block);
+    }
+    let slide_level = slide_level.unwrap_or_else(|| default_slide_level(&lines));
+
+    let mut divs: Vec<Div> = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut in_poll = false;
+    let mut has_content = false;
+    for line in lines {
+        let trimmed = line.trim();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            push_line(&mut divs, &mut output, line.to_owned());
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+        } else if in_poll {
+            in_poll = trimmed != ":::";
+        } else if trimmed.starts_with(":::poll") {
+            in_poll = true;
+        } else if trimmed.starts_with(":::") {
+            let attributes = trimmed.trim_start_matches(':').trim();
+            if attributes.is_empty() {
+                match divs.pop() {
+                    Some(Div::Notes(notes)) => {
+                        push_line(&mut divs, &mut output, notes_directive(&notes))
+                    }
+                    Some(Div::Html) => output.push("\n</div>\n".to_owned()),
+                    None => push_line(&mut divs, &mut output, line.to_owned()),
+                }
+            } else {
+                match div_attributes(attributes) {
+                    Some(html) => {
+                        output.push(format!("<div{}>\n", html));
+                        divs.push(Div::Html);
+                    }
+                    None => divs.push(Div::Notes(String::new())),
+                }
+            }
+            continue;
+        } else if trimmed == "---" {
+            has_content = false;
+        } else if let Some(level) = heading_level(line) {
+            if level <= slide_level && divs.is_empty() && has_content {
+                output.push("\n---\n".to_owned());
+            }
+        }
+        if !trimmed.is_empty() && divs.is_empty() {
+            has_content = true;
+        }
+        push_line(&mut divs, &mut output, line.to_owned());
+    }
+    let mut output = output.join("\n");
+    output.push('\n');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_remark() {
+        let input = "class: center, middle\n\n# Title\n\n???\nSay hi\n\n---\nlayout: true\n\
+                     Template\n\n---\n- One\n--\n- Two\n\n```\n--\n```\n";
+        assert_eq!(
+            "<!-- class: center middle -->\n\n# Title\n\n<!-- notes: Say hi -->\n\n---\n\n\
+             - One\n\n---\n\n- One\n- Two\n\n```\n--\n```\n",
+            convert(input, Compat::Remark, None)
+        );
+    }
+
+    #[test]
+    fn test_convert_marp() {
+        let input = "---\nmarp: true\nclass: invert\n---\n# Title\n<!-- paginate: true -->\n\
+                     <!-- Speak\nslowly -->\n\n---\n<!-- _class: lead -->\n<!-- time: 2m -->\n\
+                     # Two\n";
+        assert_eq!(
+            "---\nmarp: true\nclass: invert\n---\n<!-- class: invert -->\n\n# Title\n\
+             <!-- notes: Speak\nslowly -->\n\n---\n\n<!-- class: lead -->\n\n\
+             <!-- time: 2m -->\n# Two\n",
+            convert(input, Compat::Marp, None)
+        );
+    }
+
+    #[test]
+    fn test_convert_pandoc() {
+        let input = "% Talk\n% Me\n\n# Part\n\n## One\n\nText\n\n::: notes\nSay hi\n:::\n\n\
+                     ## Two\n\n:::: {.columns}\n::: {.column width=\"40%\"}\n## Left\n:::\n::::\n";
+        assert_eq!(
+            "---\ntitle: \"Talk\"\n---\n\n# Part\n\n\n---\n\n## One\n\nText\n\n\
+             <!-- notes: Say hi -->\n\n\n---\n\n## Two\n\n<div class=\"columns\">\n\n\
+             <div class=\"column\" style=\"flex:0 0 40%\">\n\n## Left\n\n</div>\n\n\n</div>\n\n",
+            convert(input, Compat::Pandoc, None)
+        );
+        assert_eq!(2, default_slide_level(&["# Part", "## One", "Text"]));
+    }
+}
@@ -0,0 +1,322 @@
+use crate::{html::escape_html, slides};
+use pulldown_cmark::html;
+use std::fmt;
+
+/// Slide of a deck rendered to be compared with the slides of another version
+#[derive(PartialEq)]
+struct Rendered {
+    title: Option<String>,
+    html: String,
+    notes: Option<String>,
+}
+
+impl Rendered {
+    fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or("(untitled)")
+    }
+}
+
+fn render(input: &str) -> Vec<Rendered> {
+    slides::parse(input)
+        .into_iter()
+        .map(|slide| {
+            let mut content = String::new();
+            html::push_html(&mut content, slide.events.into_iter());
+            Rendered {
+                title: slide.title,
+                html: content,
+                notes: slide.notes,
+            }
+        })
+        .collect()
+}
+
+/// Difference between the slides of two versions of a deck, identified by their index
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Added {
+        new: usize,
+    },
+    Removed {
+        old: usize,
+    },
+    Changed {
+        old: usize,
+        new: usize,
+        content: bool,
+        notes: bool,
+    },
+    /// Identical slide found at another position
+    Moved {
+        old: usize,
+        new: usize,
+    },
+}
+
+/// Align the slides of both versions on the longest sequence of identical slides, then pair the
+/// slides found in between: reordered slides first, those keeping their title and finally the
+/// remaining slides of each gap in order
+fn align(old: &[Rendered], new: &[Rendered]) -> Vec<Change> {
+    let (n, m) = (old.len(), new.len());
+    let mut lengths = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    // Slides missing from the other version along with the gap between identical slides they
+    // were found in, removed slides also keep the position in the new version they were at
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let mut gap = 0;
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            gap += 1;
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            removed.push((i, gap, j));
+            i += 1;
+        } else {
+            added.push((j, gap));
+            j += 1;
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let matchers: [fn(&Rendered, &Rendered, bool) -> bool; 3] = [
+        |o, a, _| o == a,
+        |o, a, _| o.title.is_some() && o.title == a.title,
+        |_, _, same_gap| same_gap,
+    ];
+    for matches in matchers.iter() {
+        removed.retain(|&(o, removed_gap, _)| {
+            let position = added
+                .iter()
+                .position(|&(a, added_gap)| matches(&old[o], &new[a], removed_gap == added_gap));
+            match position {
+                Some(position) => {
+                    pairs.push((o, added.remove(position).0));
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+
+    let mut changes: Vec<(usize, Change)> = pairs
+        .into_iter()
+        .map(|(o, a)| {
+            let change = if old[o] == new[a] {
+                Change::Moved { old: o, new: a }
+            } else {
+                Change::Changed {
+                    old: o,
+                    new: a,
+                    content: old[o].html != new[a].html,
+                    notes: old[o].notes != new[a].notes,
+                }
+            };
+            (a, change)
+        })
+        .collect();
+    changes.extend(
+        removed
+            .into_iter()
+            .map(|(old, _, position)| (position, Change::Removed { old })),
+    );
+    changes.extend(
+        added
+            .into_iter()
+            .map(|(new, _)| (new, Change::Added { new })),
+    );
+    // Changes are listed in the order of the new version, removed slides before the slide that
+    // followed them
+    changes.sort_by_key(|(position, change)| {
+        let rank = match change {
+            Change::Removed { old } => (0, *old),
+            _ => (1, 0),
+        };
+        (*position, rank)
+    });
+    changes.into_iter().map(|(_, change)| change).collect()
+}
+
+/// Slide-level differences between two versions of a deck
+pub struct Diff {
+    old: Vec<Rendered>,
+    new: Vec<Rendered>,
+    pub changes: Vec<Change>,
+}
+
+pub fn diff(old: &str, new: &str) -> Diff {
+    let old = render(old);
+    let new = render(new);
+    let changes = align(&old, &new);
+    Diff { old, new, changes }
+}
+
+impl Diff {
+    /// Short description of a change, e.g. `slide 3 → 4 changed (notes): Title`
+    fn describe(&self, change: &Change) -> String {
+        match *change {
+            Change::Added { new } => {
+                format!("slide {} added: {}", new + 1, self.new[new].title())
+            }
+            Change::Removed { old } => {
+                format!("slide {} removed: {}", old + 1, self.old[old].title())
+            }
+            Change::Changed {
+                old,
+                new,
+                content,
+                notes,
+            } => {
+                let what: Vec<&str> = [(content, "content"), (notes, "notes")]
+                    .iter()
+                    .filter(|(changed, _)| *changed)
+                    .map(|(_, what)| *what)
+                    .collect();
+                format!(
+                    "slide {} → {} changed ({}): {}",
+                    old + 1,
+                    new + 1,
+                    what.join(", "),
+                    self.new[new].title()
+                )
+            }
+            Change::Moved { old, new } => format!(
+                "slide {} → {} moved: {}",
+                old + 1,
+                new + 1,
+                self.new[new].title()
+            ),
+        }
+    }
+
+    /// Page showing the old and new versions of each slide that differs side by side
+    pub fn to_html(&self, old_name: &str, new_name: &str) -> String {
+        let mut page = String::from("<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        page.push_str(&format!(
+            "<title>{} → {}</title>\n<style>{}</style>\n</head>\n<body>\n",
+            escape_html(old_name),
+            escape_html(new_name),
+            DIFF_CSS
+        ));
+        page.push_str(&format!(
+            "<h1>{} → {}</h1>\n",
+            escape_html(old_name),
+            escape_html(new_name)
+        ));
+        for change in &self.changes {
+            let (class, old, new) = match *change {
+                Change::Added { new } => ("added", None, Some(new)),
+                Change::Removed { old } => ("removed", Some(old), None),
+                Change::Changed { old, new, .. } => ("changed", Some(old), Some(new)),
+                Change::Moved { old, new } => ("moved", Some(old), Some(new)),
+            };
+            page.push_str(&format!(
+                "<section class=\"{}\">\n<h2>{}</h2>\n<div class=\"sides\">\n",
+                class,
+                escape_html(&self.describe(change))
+            ));
+            for slide in &[old.map(|i| &self.old[i]), new.map(|i| &self.new[i])] {
+                page.push_str("<div class=\"slide\">");
+                if let Some(slide) = slide {
+                    page.push_str(&slide.html);
+                    if let Some(ref notes) = slide.notes {
+                        page.push_str(&format!(
+                            "<aside class=\"notes\">{}</aside>",
+                            escape_html(notes)
+                        ));
+                    }
+                }
+                page.push_str("</div>\n");
+            }
+            page.push_str("</div>\n</section>\n");
+        }
+        page.push_str("</body>\n</html>\n");
+        page
+    }
+}
+
+const DIFF_CSS: &str = "body { font-family: sans-serif; margin: 2em; }
+.sides { display: flex; gap: 1em; }
+.slide { flex: 1; border: 1px solid #ccc; padding: 1em; overflow: auto; }
+.slide img { max-width: 100%; }
+.notes { margin-top: 1em; color: #666; white-space: pre-wrap; }
+.added h2 { color: #2a7d2a; }
+.removed h2 { color: #b22; }
+.changed h2 { color: #b8860b; }
+.moved h2 { color: #36c; }";
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        for change in &self.changes {
+            writeln!(f, "{}", self.describe(change))?;
+        }
+        let count = |kind: fn(&Change) -> bool| self.changes.iter().filter(|c| kind(c)).count();
+        writeln!(
+            f,
+            "{} added, {} removed, {} changed, {} moved",
+            count(|change| matches!(change, Change::Added { .. })),
+            count(|change| matches!(change, Change::Removed { .. })),
+            count(|change| matches!(change, Change::Changed { .. })),
+            count(|change| matches!(change, Change::Moved { .. })),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old = "# Intro\n\nHello\n\n---\n\n# Agenda\n\n- A\n\n---\n\n# Demo\n\nRun it\n\n---\n\n# Questions\n";
+        let new = "# Agenda\n\n- A\n- B\n\n---\n\n# Intro\n\nHello\n\n---\n\n# Setup\n\nInstall\n\n---\n\n# Questions\n";
+        let changes = diff(old, new);
+        assert_eq!(
+            vec![
+                Change::Changed {
+                    old: 1,
+                    new: 0,
+                    content: true,
+                    notes: false
+                },
+                Change::Changed {
+                    old: 2,
+                    new: 2,
+                    content: true,
+                    notes: false
+                },
+            ],
+            changes.changes
+        );
+        let report = changes.to_string();
+        assert!(report.contains("slide 3 → 3 changed (content): Setup\n"));
+        assert!(report.ends_with("0 added, 0 removed, 2 changed, 0 moved\n"));
+
+        let changes = diff(
+            "# A\n\n---\n\n# B\n\n---\n\n# C\n",
+            "# C\n\n---\n\n# A\n\n---\n\n# D\n",
+        );
+        assert_eq!(
+            vec![
+                Change::Removed { old: 1 },
+                Change::Moved { old: 0, new: 1 },
+                Change::Added { new: 2 },
+            ],
+            changes.changes
+        );
+    }
+}
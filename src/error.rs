@@ -1,4 +1,5 @@
-use std::{error::Error as StdError, fmt, io};
+use std::{error::Error as StdError, fmt, io, path::PathBuf};
+#[cfg(feature = "cli")]
 use warp::reject;
 
 #[derive(Debug)]
@@ -8,8 +9,44 @@ pub enum Error {
     Syntect(syntect::LoadingError),
     JsonSerialization(serde_json::error::Error),
     ThemeNotFound,
+    #[cfg(feature = "cli")]
+    Mdns(mdns_sd::Error),
+    InvalidCodeBlockPath(PathBuf),
+    DuplicateAttribute {
+        slide: usize,
+        name: String,
+    },
+    /// Two code blocks are extracted to the same file, or one to the manifest when there is no
+    /// previous slide
+    DuplicateCodeBlockPath {
+        path: PathBuf,
+        slide: usize,
+        previous: Option<usize>,
+    },
+    UnsupportedTestLanguage(String),
+    Yaml(serde_yaml::Error),
+    #[cfg(feature = "cli")]
+    Http(reqwest::Error),
+    InvalidChart {
+        slide: usize,
+        reason: String,
+    },
+    InvalidQuery {
+        name: String,
+        reason: String,
+    },
+    NoEditor,
+    /// A client sent more requests than the server accepts per minute
+    TooManyRequests,
+    /// The server already has as many websocket connections as it accepts
+    TooManyConnections,
+    #[cfg(feature = "cli")]
+    Image(image::ImageError),
+    #[cfg(feature = "cli")]
+    Task(tokio::task::JoinError),
 }
 
+#[cfg(feature = "cli")]
 impl reject::Reject for Error {}
 
 impl StdError for Error {}
@@ -23,6 +60,58 @@ impl fmt::Display for Error {
             Syntect(err) => err.fmt(f),
             JsonSerialization(err) => err.fmt(f),
             ThemeNotFound => write!(f, "Theme not found"),
+            #[cfg(feature = "cli")]
+            Mdns(err) => err.fmt(f),
+            InvalidCodeBlockPath(path) => write!(
+                f,
+                "Code block path must be relative to the output directory: {}",
+                path.to_string_lossy()
+            ),
+            DuplicateAttribute { slide, name } => write!(
+                f,
+                "Code block on slide {} has the attribute {:?} more than once",
+                slide, name
+            ),
+            DuplicateCodeBlockPath {
+                path,
+                slide,
+                previous: Some(previous),
+            } => write!(
+                f,
+                "Code blocks on slides {} and {} are both extracted to {}",
+                previous,
+                slide,
+                path.to_string_lossy()
+            ),
+            DuplicateCodeBlockPath {
+                path,
+                slide,
+                previous: None,
+            } => write!(
+                f,
+                "Code block on slide {} would overwrite the manifest: {}",
+                slide,
+                path.to_string_lossy()
+            ),
+            UnsupportedTestLanguage(lang) => {
+                write!(f, "Don't know how to run code blocks written in {:?}", lang)
+            }
+            Yaml(err) => err.fmt(f),
+            #[cfg(feature = "cli")]
+            Http(err) => err.fmt(f),
+            InvalidChart { slide, reason } => {
+                write!(f, "Invalid chart on slide {}: {}", slide, reason)
+            }
+            InvalidQuery { name, reason } => {
+                write!(f, "Invalid query parameter {:?}: {}", name, reason)
+            }
+            NoEditor => write!(f, "Set $VISUAL or $EDITOR to open the slides in an editor"),
+            TooManyRequests => write!(f, "Too many requests, try again in a minute"),
+            TooManyConnections => write!(f, "Too many viewers are connected, try again later"),
+            #[cfg(feature = "cli")]
+            Image(err) => err.fmt(f),
+            #[cfg(feature = "cli")]
+            Task(err) => err.fmt(f),
         }
     }
 }
@@ -44,3 +133,37 @@ impl From<serde_json::error::Error> for Error {
         Error::JsonSerialization(err)
     }
 }
+
+#[cfg(feature = "cli")]
+impl From<mdns_sd::Error> for Error {
+    fn from(err: mdns_sd::Error) -> Error {
+        Error::Mdns(err)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(err: serde_yaml::Error) -> Error {
+        Error::Yaml(err)
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Error {
+        Error::Http(err)
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<image::ImageError> for Error {
+    fn from(err: image::ImageError) -> Error {
+        Error::Image(err)
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<tokio::task::JoinError> for Error {
+    fn from(err: tokio::task::JoinError) -> Error {
+        Error::Task(err)
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{error::Error, markup, slides};
+
+/// Number of lines shown before and after the line at fault
+const CONTEXT_LINES: usize = 2;
+
+/// Place in the markdown a rendering error comes from, lines and columns starting at 1
+#[derive(Debug, PartialEq)]
+pub struct Location {
+    /// Slide the error was found on, starting at 1, `None` for the front matter
+    pub slide: Option<usize>,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Lines of the markdown with their number, starting at 1
+fn numbered(markdown: &str) -> impl Iterator<Item = (usize, &str)> {
+    markdown.lines().enumerate().map(|(i, line)| (i + 1, line))
+}
+
+fn indentation(line: &str) -> usize {
+    line.chars().take_while(|c| c.is_whitespace()).count()
+}
+
+/// Find the first chart of the slide and, when the reason names one of its rows, that row
+fn locate_chart(markdown: &str, slide: usize, reason: &str) -> Option<Location> {
+    // The slides are parsed the way they are rendered, without the abbreviation definitions
+    let (input, _, removed) = markup::abbreviations(markdown);
+    let (first, last) = slides::parse(&input).get(slide.checked_sub(1)?)?.lines;
+    let first = markup::original_line(first, &removed);
+    let last = markup::original_line(last, &removed);
+    let mut lines = numbered(markdown)
+        .skip(first.saturating_sub(1))
+        .take(last + 1 - first);
+    let (fence, text) = lines
+        .find(|(_, line)| line.trim_start().starts_with("```chart"))
+        .unwrap_or((first, ""));
+    let location = |(line, text): (usize, &str)| Location {
+        slide: Some(slide),
+        line,
+        column: indentation(text) + 1,
+    };
+    // Rows are counted from the header of the chart, blank lines aside
+    let row = reason
+        .strip_prefix("row ")
+        .and_then(|rest| rest.split(' ').next())
+        .and_then(|row| row.parse::<usize>().ok());
+    let row = row.and_then(|row| {
+        lines
+            .take_while(|(_, line)| !line.trim_start().starts_with("```"))
+            .filter(|(_, line)| !line.trim().is_empty())
+            .nth(row.checked_sub(1)?)
+    });
+    Some(location(row.unwrap_or((fence, text))))
+}
+
+/// Locate the cause of a rendering error in the markdown of the deck, if it comes from the deck
+/// itself rather than from its assets or the theme
+pub fn locate(err: &Error, markdown: &str) -> Option<Location> {
+    match err {
+        Error::InvalidChart { slide, reason } => locate_chart(markdown, *slide, reason),
+        Error::Yaml(err) => {
+            // Lines of the front matter follow its opening `---` line, the errors found while
+            // converting the mapping to the front matter have no location and point at it
+            let (line, column) = err
+                .location()
+                .map_or((1, 1), |location| (location.line() + 1, location.column()));
+            Some(Location {
+                slide: None,
+                line,
+                column,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Lines around the location with their number, the column at fault marked by a caret under it
+pub fn excerpt(markdown: &str, location: &Location) -> String {
+    let first = location.line.saturating_sub(CONTEXT_LINES).max(1);
+    let last = location.line + CONTEXT_LINES;
+    let width = last.to_string().len();
+    let mut excerpt = String::new();
+    for (number, line) in numbered(markdown)
+        .skip(first.saturating_sub(1))
+        .take(last + 1 - first)
+    {
+        excerpt.push_str(&format!("{:>w$} | {}\n", number, line, w = width));
+        if number == location.line {
+            excerpt.push_str(&format!(
+                "{:>w$} | {:>c$}\n",
+                "",
+                "^",
+                w = width,
+                c = location.column
+            ));
+        }
+    }
+    excerpt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excerpt() {
+        let markdown = "*[CLI]: Command Line Interface\n# Intro\n\n---\n\n# Visits\n\n  ```chart\n  month,visits\n\n  jan,3\n  feb,x\n  ```\n";
+        let err = Error::InvalidChart {
+            slide: 2,
+            reason: "row 3 has an invalid number \"x\"".to_owned(),
+        };
+        let location = locate(&err, markdown).expect("Failed to locate the chart");
+        assert_eq!(
+            Location {
+                slide: Some(2),
+                line: 12,
+                column: 3
+            },
+            location
+        );
+        assert_eq!(
+            "10 | \n11 |   jan,3\n12 |   feb,x\n   |   ^\n13 |   ```\n",
+            excerpt(markdown, &location)
+        );
+
+        let err = Error::InvalidChart {
+            slide: 2,
+            reason: "no values to plot".to_owned(),
+        };
+        assert_eq!(8, locate(&err, markdown).unwrap().line);
+        assert!(locate(&Error::ThemeNotFound, markdown).is_none());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{error::Error, keys::Keys, variables::Variables};
+use serde::{Deserialize, Deserializer};
+use serde_yaml::{Mapping, Value};
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// Metadata of a deck written in YAML at the very beginning of the markdown document, between
+/// two `---` lines
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    /// Author of the deck, written to the metadata of the page and of the PDFs saved from it
+    pub author: Option<String>,
+    /// Add a slide listing the abbreviations defined in the deck
+    pub glossary: bool,
+    /// Keys bound to the actions of the presentation, e.g. `next: [ArrowRight, PageDown]`
+    pub keys: BTreeMap<String, Keys>,
+    /// Swap the keys of the next and previous actions, for remotes whose buttons are reversed
+    pub swap_navigation: bool,
+    /// Custom css files, relative to the deck, added after the ones given on the command line
+    #[serde(deserialize_with = "one_or_many")]
+    pub css: Vec<PathBuf>,
+    /// Custom js files, relative to the deck, added after the ones given on the command line
+    #[serde(deserialize_with = "one_or_many")]
+    pub js: Vec<PathBuf>,
+    /// Colors, fonts and spacing of the style, e.g. `theme: {colors: {accent: "#ff6600"}}`
+    pub theme: Variables,
+}
+
+/// Accept either a single file or a list of files
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(PathBuf),
+        Many(Vec<PathBuf>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(path) => vec![path],
+        OneOrMany::Many(paths) => paths,
+    })
+}
+
+/// Separate the front matter from the slides. The block delimited by the first two `---` lines
+/// is only considered as a front matter if it holds a YAML mapping, otherwise the lines are left
+/// untouched and act as slide separators.
+pub fn split(input: &str) -> (Option<Mapping>, &str) {
+    let rest = match input
+        .strip_prefix("---\n")
+        .or_else(|| input.strip_prefix("---\r\n"))
+    {
+        Some(rest) => rest,
+        None => return (None, input),
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            let yaml = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            return match serde_yaml::from_str(yaml) {
+                Ok(Value::Mapping(mapping)) => (Some(mapping), body),
+                _ => (None, input),
+            };
+        }
+        offset += line.len();
+    }
+    (None, input)
+}
+
+/// Parse the front matter, if any, and return it along with the rest of the document
+pub fn parse(input: &str) -> Result<(FrontMatter, &str), Error> {
+    match split(input) {
+        (Some(mapping), body) => {
+            let front_matter = serde_yaml::from_value(Value::Mapping(mapping))?;
+            Ok((front_matter, body))
+        }
+        (None, body) => Ok((FrontMatter::default(), body)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let input = "---\ntitle: Deck\ncss: brand.css\njs: [a.js, b.js]\n---\n# Slide 1\n";
+        let (front_matter, body) = parse(input).expect("Failed to parse front matter");
+        assert_eq!(Some("Deck"), front_matter.title.as_deref());
+        assert_eq!(vec![PathBuf::from("brand.css")], front_matter.css);
+        assert_eq!(
+            vec![PathBuf::from("a.js"), PathBuf::from("b.js")],
+            front_matter.js
+        );
+        assert_eq!("# Slide 1\n", body);
+    }
+
+    #[test]
+    fn test_parse_without_front_matter() {
+        let input = "---\n# Slide 2\n---\n# Slide 3\n";
+        let (front_matter, body) = parse(input).expect("Failed to parse front matter");
+        assert_eq!(None, front_matter.title);
+        assert_eq!(input, body);
+    }
+}
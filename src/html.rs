@@ -1,55 +1,557 @@
-use crate::error::Error;
-use pulldown_cmark::{html, CodeBlockKind, Event, Options as MarkdownOptions, Parser, Tag};
-use std::{fmt, path::PathBuf};
+use crate::{
+    assets,
+    chart::Chart,
+    code::Info,
+    error::Error,
+    front_matter, iframe, keys, markup, media, slides,
+    timings::{Instant, Timings},
+};
+use pulldown_cmark::{html, CodeBlockKind, Event, Tag};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, io,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 use syntect::{
     easy::HighlightLines,
     highlighting::{Theme, ThemeSet},
     html::{start_highlighted_html_snippet, styled_line_to_highlighted_html, IncludeBackground},
     parsing::SyntaxSet,
 };
+use tracing::{debug_span, field};
 
 const DEFAULT_THEME: &str = "base16-ocean.dark";
+/// Style added when balancing the headings, browsers without `text-wrap` support ignore it
+const BALANCED_HEADINGS_CSS: &str = "h1, h2, h3, h4, h5, h6 { text-wrap: balance; }";
+
+/// Same rules as the `prefers-reduced-motion` media query of the style, applied regardless of
+/// the preference of the viewer
+const NO_ANIMATIONS_CSS: &str =
+    "*, *::before, *::after { transition: none !important; animation: none !important; }";
+
+/// Highlighting theme bundled with deck, built from a palette distinguishable with any color
+/// vision deficiency
+const COLORBLIND_THEME: (&str, &str) = ("colorblind-safe", include_str!("colorblind-safe.tmTheme"));
+
+/// Palettes of the high contrast deck theme, applied before the theme of the front matter so
+/// that it can still be overridden
+const HIGH_CONTRAST_CSS: &str = ":root {
+  --deck-color-background: #ffffff;
+  --deck-color-text: #000000;
+  --deck-color-accent: #0000c8;
+  --deck-font-size: 220%;
+}
+:root.dark {
+  --deck-color-background: #000000;
+  --deck-color-text: #ffffff;
+  --deck-color-accent: #ffd700;
+}
+a { color: var(--deck-color-accent); text-decoration-thickness: 0.1em; }
+:focus-visible { outline: 0.15em solid var(--deck-color-accent); outline-offset: 0.1em; }";
 
 pub struct Output {
-    title: Option<String>,
+    metadata: Metadata,
     style: String,
     script: String,
+    /// Stylesheets and scripts referenced rather than inlined
+    links: String,
     body: String,
+    /// Time spent in each phase of the rendering
+    pub timings: Timings,
+    pub warnings: Vec<Warning>,
+}
+
+/// Non-fatal problem found while rendering a slide, such as an image missing from the disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    /// Index of the slide
+    pub slide: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Slide {}: {}", self.slide + 1, self.message)
+    }
+}
+
+/// Languages of the code blocks left unhighlighted on purpose
+const PLAIN_LANGS: [&str; 3] = ["text", "plain", "txt"];
+
+/// Metadata of the document, browsers also write it to the PDFs saved from the page
+#[derive(Debug, Default)]
+struct Metadata {
+    title: Option<String>,
+    author: Option<String>,
+}
+
+/// Markup preceding the slides, the linked stylesheets follow the inline style so that they
+/// override it
+fn head(metadata: &Metadata, style: &str, script: &str, links: &str) -> String {
+    let mut head = String::from("<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    if let Some(ref title) = metadata.title {
+        head.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+    }
+    if let Some(ref author) = metadata.author {
+        head.push_str(&format!(
+            "<meta name=\"author\" content=\"{}\">\n",
+            escape_html(author)
+        ));
+    }
+    head.push_str(&format!(
+        "<style>\n{}\n</style>\n<script type=\"text/javascript\">\n{}\n</script>\n{}<body>\n",
+        style, script, links
+    ));
+    head
+}
+
+/// Markup following the slides
+const FOOTER: &str = "\n</body>\n</head>\n</html>\n";
+
+/// Part of the page, handed over as soon as it is rendered
+enum Part {
+    /// Metadata, style, script and links of the page
+    Head(Metadata, String, String, String),
+    /// Markup of the slide at the given position in the page
+    Slide(usize, String),
+    /// Markup of the warnings listed after the slides
+    Warnings(String),
 }
 
 impl fmt::Display for Output {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "<html>")?;
-        writeln!(f, "<head>")?;
+        f.write_str(&head(
+            &self.metadata,
+            &self.style,
+            &self.script,
+            &self.links,
+        ))?;
+        f.write_str(&self.body)?;
+        f.write_str(FOOTER)
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Separator of the custom scripts, so that a script can't continue the last statement of the
+/// previous one
+pub const JS_SEPARATOR: &str = "\n;\n";
+
+/// Join the custom stylesheets or scripts in the order they were given, if there are any
+pub fn join_custom(sources: Vec<String>, separator: &str) -> Option<String> {
+    if sources.is_empty() {
+        None
+    } else {
+        Some(sources.join(separator))
+    }
+}
+
+/// Title of a deck given in its front matter, falling back to its first `#` heading rather than
+/// the heading of a section
+pub fn deck_title(front_matter_title: Option<String>, slides: &[slides::Slide]) -> Option<String> {
+    front_matter_title.or_else(|| {
+        slides
+            .iter()
+            .filter(|slide| slide.title_level == Some(1))
+            .find_map(|slide| slide.title.clone())
+    })
+}
+
+/// List of the warnings shown over the slides, each one can be dismissed
+fn warnings_html(warnings: &[Warning]) -> String {
+    let mut html = String::from("\n<ul class=\"toasts\">\n");
+    for warning in warnings {
+        html.push_str(&format!(
+            "<li class=\"toast\" data-slide=\"{}\">{}\
+             <button class=\"toast-close\" title=\"Dismiss\">×</button></li>\n",
+            warning.slide,
+            escape_html(&warning.to_string())
+        ));
+    }
+    html.push_str("</ul>");
+    html
+}
+
+/// Inline style of a code block set with the `fontsize` and `wrap` attributes of its fence
+fn block_style(info: &Info) -> String {
+    let mut style = String::new();
+    if let Some(size) = info.get("fontsize").and_then(|s| s.parse::<f32>().ok()) {
+        if size > 0.0 {
+            style.push_str(&format!("font-size:{}em;", size));
+        }
+    }
+    if info.has("wrap") {
+        style.push_str("white-space:pre-wrap;overflow-wrap:anywhere;");
+    }
+    style
+}
+
+/// Replace the ```` ```chart ```` blocks by the SVG plotting their data
+fn render_charts(events: Vec<Event>, slide: usize) -> Result<Vec<Event>, Error> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut events = events.into_iter();
+    while let Some(event) = events.next() {
+        let info = match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref info))) => Info::parse(info),
+            event => {
+                output.push(event);
+                continue;
+            }
+        };
+        if info.lang != "chart" {
+            output.push(event);
+            continue;
+        }
+        let mut source = String::new();
+        for event in &mut events {
+            match event {
+                Event::Text(text) => source.push_str(&text),
+                _ => break,
+            }
+        }
+        let chart =
+            Chart::parse(&source, &info).map_err(|reason| Error::InvalidChart { slide, reason })?;
+        output.push(Event::Html(chart.to_svg().into()));
+    }
+    Ok(output)
+}
+
+/// Elements whose `src` and `srcset` attributes are deferred
+const DEFERRED_ELEMENTS: [&str; 5] = ["img", "video", "audio", "iframe", "source"];
+
+/// Defer the loading of the media of a slide by moving their `src` and `srcset` attributes to
+/// data attributes, the script restores them when the slide is about to be shown
+fn defer_media(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let (before, tag) = rest.split_at(start);
+        output.push_str(before);
+        let end = tag.find('>').map_or(tag.len(), |i| i + 1);
+        let (tag, after) = tag.split_at(end);
+        let name = tag[1..]
+            .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .next()
+            .unwrap_or_default();
+        if DEFERRED_ELEMENTS.contains(&name) {
+            output.push_str(
+                &tag.replace(" src=\"", " data-src=\"")
+                    .replace(" srcset=\"", " data-srcset=\""),
+            );
+        } else {
+            output.push_str(tag);
+        }
+        rest = after;
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Splits the text following inline code into the language of a leading `{.lang}` attribute and
+/// the rest of the text
+fn parse_inline_lang(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix("{.")?;
+    let end = rest.find('}')?;
+    let lang = &rest[..end];
+    let is_valid = !lang.is_empty()
+        && lang
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '+' || c == '#');
+    if !is_valid {
+        return None;
+    }
+    Some((lang, &rest[end + 1..]))
+}
+
+/// Normalizes the `steps` attribute of a fence such as `[1-3][4-8][10]`, where each step lists
+/// the lines highlighted when it is reached, to `1-3|4-8|10`
+fn parse_steps(steps: &str) -> Option<String> {
+    let steps = steps.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let mut normalized = Vec::new();
+    for step in steps.split("][") {
+        let is_valid = !step.is_empty()
+            && step.split(',').all(|range| {
+                let mut bounds = range.splitn(2, '-');
+                bounds.all(|bound| !bound.is_empty() && bound.chars().all(|c| c.is_ascii_digit()))
+            });
+        if !is_valid {
+            return None;
+        }
+        normalized.push(step);
+    }
+    Some(normalized.join("|"))
+}
 
-        // Meta
-        writeln!(f, "<meta charset=\"utf-8\">")?;
-        if let Some(ref title) = self.title {
-            writeln!(f, "<title>{}</title>", title)?;
+/// Corner of the slides reserved for the webcam overlay of recorded talks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    pub fn variants() -> [&'static str; 4] {
+        ["top-left", "top-right", "bottom-left", "bottom-right"]
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Corner::TopLeft => "top-left",
+            Corner::TopRight => "top-right",
+            Corner::BottomLeft => "bottom-left",
+            Corner::BottomRight => "bottom-right",
         }
+    }
+}
 
-        // Style
-        writeln!(f, "<style>")?;
-        writeln!(f, "{}", self.style)?;
-        writeln!(f, "</style>")?;
-        writeln!(f, "<script type=\"text/javascript\">")?;
-        writeln!(f, "{}", self.script)?;
-        writeln!(f, "</script>")?;
+impl FromStr for Corner {
+    type Err = String;
 
-        writeln!(f, "<body>")?;
-        writeln!(f, "{}", self.body)?;
-        writeln!(f, "</body>")?;
+    fn from_str(s: &str) -> Result<Corner, String> {
+        match s {
+            "top-left" => Ok(Corner::TopLeft),
+            "top-right" => Ok(Corner::TopRight),
+            "bottom-left" => Ok(Corner::BottomLeft),
+            "bottom-right" => Ok(Corner::BottomRight),
+            _ => Err(format!("Unknown corner: {}", s)),
+        }
+    }
+}
+
+/// Palette and sizes of the slides, the theme of the front matter is applied on top of it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeckTheme {
+    Default,
+    /// Black on white text, or white on black in the dark appearance, with a larger font
+    HighContrast,
+}
 
-        writeln!(f, "</head>")?;
+impl DeckTheme {
+    pub fn variants() -> [&'static str; 2] {
+        ["default", "high-contrast"]
+    }
 
-        writeln!(f, "</html>")
+    fn css(self) -> Option<&'static str> {
+        match self {
+            DeckTheme::Default => None,
+            DeckTheme::HighContrast => Some(HIGH_CONTRAST_CSS),
+        }
     }
 }
 
+impl FromStr for DeckTheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DeckTheme, String> {
+        match s {
+            "default" => Ok(DeckTheme::Default),
+            "high-contrast" => Ok(DeckTheme::HighContrast),
+            _ => Err(format!("Unknown deck theme: {}", s)),
+        }
+    }
+}
+
+/// Pages the speaker notes are printed on, to save a rehearsal document from the browser
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrintNotes {
+    None,
+    /// All the notes after the slides
+    After,
+    /// The notes of each slide right after it
+    Interleaved,
+}
+
+impl PrintNotes {
+    pub fn variants() -> [&'static str; 3] {
+        ["none", "after", "interleaved"]
+    }
+
+    fn name(self) -> Option<&'static str> {
+        match self {
+            PrintNotes::None => None,
+            PrintNotes::After => Some("after"),
+            PrintNotes::Interleaved => Some("interleaved"),
+        }
+    }
+}
+
+impl FromStr for PrintNotes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PrintNotes, String> {
+        match s {
+            "none" => Ok(PrintNotes::None),
+            "after" => Ok(PrintNotes::After),
+            "interleaved" => Ok(PrintNotes::Interleaved),
+            _ => Err(format!("Unknown placement of the printed notes: {}", s)),
+        }
+    }
+}
+
+/// Stylesheet or script referenced by its URL instead of being inlined, written as the URL
+/// optionally followed by a space and its subresource integrity hash, e.g.
+/// `https://example.com/slides.css sha384-...`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub url: String,
+    pub integrity: Option<String>,
+}
+
+impl Link {
+    fn attributes(&self) -> String {
+        match self.integrity {
+            // Browsers only check the integrity of cross-origin resources fetched with CORS
+            Some(ref integrity) => format!(
+                " integrity=\"{}\" crossorigin=\"anonymous\"",
+                escape_html(integrity)
+            ),
+            None => String::new(),
+        }
+    }
+
+    fn stylesheet_tag(&self) -> String {
+        format!(
+            "<link rel=\"stylesheet\" href=\"{}\"{}>\n",
+            escape_html(&self.url),
+            self.attributes()
+        )
+    }
+
+    fn script_tag(&self) -> String {
+        format!(
+            "<script src=\"{}\"{}></script>\n",
+            escape_html(&self.url),
+            self.attributes()
+        )
+    }
+}
+
+impl FromStr for Link {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Link, String> {
+        let mut parts = s.split_whitespace();
+        let url = parts.next().ok_or_else(|| "Missing URL".to_owned())?;
+        let integrity = parts.next();
+        if parts.next().is_some() {
+            return Err(format!(
+                "Expected a URL and its integrity hash, got {:?}",
+                s
+            ));
+        }
+        if let Some(integrity) = integrity {
+            let is_valid = match integrity.split_once('-') {
+                Some((algorithm, digest)) => {
+                    ["sha256", "sha384", "sha512"].contains(&algorithm) && !digest.is_empty()
+                }
+                None => false,
+            };
+            if !is_valid {
+                return Err(format!(
+                    "Invalid integrity hash {:?}, expected e.g. sha384-<base64 digest>",
+                    integrity
+                ));
+            }
+        }
+        Ok(Link {
+            url: url.to_owned(),
+            integrity: integrity.map(str::to_owned),
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct Options {
     pub title: Option<String>,
     pub theme: Option<String>,
     pub theme_dirs: Vec<PathBuf>,
+    /// Palette of the slides, as opposed to the `theme` highlighting the code
+    pub deck_theme: DeckTheme,
+    /// URL under which the presentation is served, it must start and end with a `/`
+    pub base_url: String,
+    /// Replacement URLs for the local images referenced by the slides. This map and the next
+    /// ones are sorted so that the output never depends on the order they were filled in.
+    pub assets: BTreeMap<String, String>,
+    /// Markup of the local SVG images inlined in the slides instead of being referenced, by URL
+    pub svgs: BTreeMap<String, String>,
+    /// `srcset` attribute of the local images having resized variants, by URL
+    pub srcsets: BTreeMap<String, String>,
+    /// Defer the loading of the media of the slides after the second one until they are about to
+    /// be shown
+    pub lazy_media: bool,
+    /// Normalize line endings so that a deck checked out on different platforms is rendered to
+    /// the exact same bytes
+    pub reproducible: bool,
+    /// Style the slides made of a single `#` heading as section dividers
+    pub section_dividers: bool,
+    /// Keep the last two words of the headings together and balance the length of their lines
+    pub balance_headings: bool,
+    /// Animate the transitions between slides and steps, unless the viewer prefers reduced
+    /// motion
+    pub animations: bool,
+    /// Embed the speaker notes so that they can be toggled with the `n` key
+    pub show_notes: bool,
+    /// Print the speaker notes on pages of their own, they are embedded without being shown
+    pub print_notes: PrintNotes,
+    /// Limit the height of code blocks to the given number of lines, the rest being scrollable.
+    /// It can be overridden with the `max_lines` attribute of a fence.
+    pub max_code_lines: Option<usize>,
+    /// Language used to highlight inline code without a `{.lang}` attribute
+    pub inline_lang: Option<String>,
+    /// Keep the views of a served presentation on the same slide through the websocket
+    pub sync: bool,
+    /// Render the presenter view showing the next slide, the notes and a timer
+    pub presenter: bool,
+    /// Duration of the talk in seconds
+    pub duration: Option<u64>,
+    /// Toggle fullscreen with the `f` key
+    pub fullscreen: bool,
+    /// Keep the screen from sleeping while the presentation is shown
+    pub wake_lock: bool,
+    /// Corner of every slide reserved for a webcam overlay
+    pub webcam_corner: Option<Corner>,
+    /// Text stamped diagonally across every slide, e.g. `CONFIDENTIAL`
+    pub watermark: Option<String>,
+    /// Go through the slides on their own, each one being shown for its planned time or the
+    /// given number of seconds, start over after the last one and hide the cursor
+    pub kiosk: Option<u64>,
+    /// Report the slide each viewer has active to the server, for its analytics
+    pub analytics: bool,
+    /// Render only the first slides of the deck, to preview it
+    pub max_slides: Option<usize>,
+    /// Render only the selected slides, keeping their numbers
+    pub slides: Option<slides::Selection>,
+    /// Add the lines of the markdown each slide is written on, to open them from the browser
+    pub source_lines: bool,
+    /// Directory the local images are relative to, those missing from it are reported
+    pub base_dir: Option<PathBuf>,
+    /// Directories mounted under a prefix of the URLs, where the local images starting with it
+    /// are looked for
+    pub asset_dirs: Vec<assets::AssetDir>,
+    /// List the warnings on the page, along with the slides found to overflow by the script
+    pub show_warnings: bool,
+    /// Stylesheets linked after the inline style, in order
+    pub link_css: Vec<Link>,
+    /// Scripts loaded after the inline script, in order
+    pub link_js: Vec<Link>,
 }
 
 impl Default for Options {
@@ -58,75 +560,769 @@ impl Default for Options {
             title: None,
             theme: None,
             theme_dirs: Vec::new(),
+            deck_theme: DeckTheme::Default,
+            base_url: String::from("/"),
+            assets: BTreeMap::new(),
+            svgs: BTreeMap::new(),
+            srcsets: BTreeMap::new(),
+            lazy_media: true,
+            reproducible: false,
+            section_dividers: true,
+            balance_headings: false,
+            animations: true,
+            show_notes: false,
+            print_notes: PrintNotes::None,
+            max_code_lines: None,
+            inline_lang: None,
+            sync: false,
+            presenter: false,
+            duration: None,
+            fullscreen: true,
+            wake_lock: true,
+            webcam_corner: None,
+            watermark: None,
+            kiosk: None,
+            analytics: false,
+            max_slides: None,
+            slides: None,
+            source_lines: false,
+            base_dir: None,
+            asset_dirs: Vec::new(),
+            show_warnings: false,
+            link_css: Vec::new(),
+            link_js: Vec::new(),
         }
     }
 }
 
+/// Configuration made available to the injected script
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScriptConfig<'a> {
+    base_url: &'a str,
+    /// Titles of the slides, searched from the navigation palette
+    titles: Vec<Option<String>>,
+    /// Keys bound to each action
+    keys: BTreeMap<String, Vec<String>>,
+    show_notes: bool,
+    /// Placement of the printed notes, `null` when they aren't printed
+    print_notes: Option<&'static str>,
+    sync: bool,
+    presenter: bool,
+    duration: Option<u64>,
+    fullscreen: bool,
+    wake_lock: bool,
+    show_warnings: bool,
+    /// Seconds each slide without a planned time is shown for, `null` unless running a kiosk
+    kiosk: Option<u64>,
+    analytics: bool,
+}
+
+/// Syntaxes and themes, loading them is the expensive part of creating a renderer so they are
+/// loaded once and shared by the renderers created from this handle, which is cheap to clone
 #[derive(Debug, Clone)]
-pub struct Renderer {
-    syntax_set: SyntaxSet,
-    theme: Theme,
-    title: Option<String>,
+pub struct Highlighting {
+    syntax_set: Arc<SyntaxSet>,
+    themes: Arc<BTreeMap<String, Arc<Theme>>>,
 }
 
-impl Renderer {
-    pub fn try_new(options: Options) -> Result<Renderer, Error> {
-        // Load syntax and theme
+impl Highlighting {
+    /// Load the default syntaxes and themes, the bundled colorblind-safe theme and the themes
+    /// found in the given directories, which take precedence
+    pub fn load(theme_dirs: &[PathBuf]) -> Result<Highlighting, Error> {
         let syntax_set = SyntaxSet::load_defaults_newlines();
         let mut theme_set = ThemeSet::load_defaults();
-        for theme_dir in &options.theme_dirs {
+        let (name, theme) = COLORBLIND_THEME;
+        theme_set.themes.insert(
+            name.to_owned(),
+            ThemeSet::load_from_reader(&mut io::Cursor::new(theme))?,
+        );
+        for theme_dir in theme_dirs {
             theme_set.add_from_folder(theme_dir)?;
         }
-        let theme_name = options.theme.unwrap_or_else(|| DEFAULT_THEME.to_owned());
-        let theme = theme_set
+        let themes = theme_set
             .themes
-            .remove(&theme_name)
-            .ok_or_else(|| Error::ThemeNotFound)?;
+            .into_iter()
+            .map(|(name, theme)| (name, Arc::new(theme)))
+            .collect();
+        Ok(Highlighting {
+            syntax_set: Arc::new(syntax_set),
+            themes: Arc::new(themes),
+        })
+    }
+
+    pub fn has_theme(&self, name: &str) -> bool {
+        self.themes.contains_key(name)
+    }
+
+    /// Names of the themes loaded, in alphabetical order
+    pub fn themes(&self) -> impl Iterator<Item = &str> {
+        self.themes.keys().map(String::as_str)
+    }
+
+    /// Create a renderer sharing these syntaxes and themes, the `theme_dirs` of the options are
+    /// ignored: their themes must have been loaded with the highlighting
+    pub fn renderer(&self, options: Options) -> Result<Renderer, Error> {
+        let theme_name = options.theme.as_deref().unwrap_or(DEFAULT_THEME);
+        let theme = self
+            .themes
+            .get(theme_name)
+            .cloned()
+            .ok_or(Error::ThemeNotFound)?;
         Ok(Renderer {
-            syntax_set,
+            syntax_set: self.syntax_set.clone(),
             theme,
             title: options.title,
+            deck_theme: options.deck_theme,
+            base_url: options.base_url,
+            assets: options.assets,
+            svgs: options.svgs,
+            srcsets: options.srcsets,
+            lazy_media: options.lazy_media,
+            reproducible: options.reproducible,
+            section_dividers: options.section_dividers,
+            balance_headings: options.balance_headings,
+            animations: options.animations,
+            show_notes: options.show_notes,
+            print_notes: options.print_notes,
+            max_code_lines: options.max_code_lines,
+            inline_lang: options.inline_lang,
+            sync: options.sync,
+            presenter: options.presenter,
+            duration: options.duration,
+            fullscreen: options.fullscreen,
+            wake_lock: options.wake_lock,
+            webcam_corner: options.webcam_corner,
+            watermark: options.watermark,
+            kiosk: options.kiosk,
+            analytics: options.analytics,
+            max_slides: options.max_slides,
+            slides: options.slides,
+            source_lines: options.source_lines,
+            base_dir: options.base_dir,
+            asset_dirs: options.asset_dirs,
+            show_warnings: options.show_warnings,
+            link_css: options.link_css,
+            link_js: options.link_js,
         })
     }
+}
+
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    syntax_set: Arc<SyntaxSet>,
+    theme: Arc<Theme>,
+    title: Option<String>,
+    deck_theme: DeckTheme,
+    base_url: String,
+    assets: BTreeMap<String, String>,
+    svgs: BTreeMap<String, String>,
+    srcsets: BTreeMap<String, String>,
+    lazy_media: bool,
+    reproducible: bool,
+    section_dividers: bool,
+    balance_headings: bool,
+    animations: bool,
+    show_notes: bool,
+    print_notes: PrintNotes,
+    max_code_lines: Option<usize>,
+    inline_lang: Option<String>,
+    sync: bool,
+    presenter: bool,
+    duration: Option<u64>,
+    fullscreen: bool,
+    wake_lock: bool,
+    webcam_corner: Option<Corner>,
+    watermark: Option<String>,
+    kiosk: Option<u64>,
+    analytics: bool,
+    max_slides: Option<usize>,
+    slides: Option<slides::Selection>,
+    source_lines: bool,
+    base_dir: Option<PathBuf>,
+    asset_dirs: Vec<assets::AssetDir>,
+    show_warnings: bool,
+    link_css: Vec<Link>,
+    link_js: Vec<Link>,
+}
 
+impl Renderer {
+    /// Load the syntaxes and themes and create a renderer using them, see `Highlighting` to
+    /// create several renderers
+    pub fn try_new(options: Options) -> Result<Renderer, Error> {
+        Highlighting::load(&options.theme_dirs)?.renderer(options)
+    }
+
+    /// Replace the images whose alternative text is `video` or `audio` by the matching element
+    fn embed_media<'a>(&self, events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+        let mut media = HashMap::new();
+        for (i, window) in events.windows(3).enumerate() {
+            if let [Event::Start(Tag::Image(..)), Event::Text(alt), Event::End(Tag::Image(..))] =
+                window
+            {
+                if let Some(kind) = media::Kind::from_alt(alt) {
+                    media.insert(i, kind);
+                }
+            }
+        }
+
+        let mut output = Vec::with_capacity(events.len());
+        let mut events = events.into_iter().enumerate().peekable();
+        while let Some((i, event)) = events.next() {
+            let (kind, url) = match (media.get(&i), event) {
+                (Some(&kind), Event::Start(Tag::Image(_, url, _))) => (kind, url),
+                (_, event) => {
+                    output.push(event);
+                    continue;
+                }
+            };
+            // Skip the alternative text and the end of the image
+            events.next();
+            events.next();
+            let mut flags = Vec::new();
+            if let Some((_, Event::Text(text))) = events.peek_mut() {
+                let parsed = media::parse_flags(text).map(|(flags, rest)| {
+                    let flags: Vec<String> = flags.into_iter().map(String::from).collect();
+                    (flags, rest.to_owned())
+                });
+                if let Some((parsed, rest)) = parsed {
+                    flags = parsed;
+                    *text = rest.into();
+                }
+            }
+            let flags: Vec<&str> = flags.iter().map(String::as_str).collect();
+            let url = self.assets.get(&*url).map_or(&*url, String::as_str);
+            output.push(Event::Html(kind.to_html(url, &flags).into()));
+        }
+        output
+    }
+
+    /// Replace the images whose SVG was loaded by the SVG itself so that it can be styled, and
+    /// the images having resized variants by an `<img>` listing them
+    fn render_images<'a>(&self, events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+        let mut output = Vec::with_capacity(events.len());
+        let mut events = events.into_iter();
+        while let Some(event) = events.next() {
+            let (url, title) = match event {
+                Event::Start(Tag::Image(_, ref url, ref title))
+                    if self.svgs.contains_key(&**url) || self.srcsets.contains_key(&**url) =>
+                {
+                    (url.to_string(), title.to_string())
+                }
+                event => {
+                    output.push(event);
+                    continue;
+                }
+            };
+            let mut alt = String::new();
+            for event in &mut events {
+                match event {
+                    Event::End(Tag::Image(..)) => break,
+                    Event::Text(text) | Event::Code(text) => alt.push_str(&text),
+                    _ => {}
+                }
+            }
+            let html = match (self.svgs.get(&url), self.srcsets.get(&url)) {
+                // The alternative text is kept as the label of the SVG
+                (Some(svg), _) => format!(
+                    "<span class=\"svg\" role=\"img\" aria-label=\"{}\">{}</span>",
+                    escape_html(&alt),
+                    svg
+                ),
+                (None, Some(srcset)) => {
+                    let src = self.assets.get(&url).unwrap_or(&url);
+                    let mut html = format!(
+                        "<img src=\"{}\" srcset=\"{}\" sizes=\"100vw\" alt=\"{}\"",
+                        escape_html(src),
+                        escape_html(srcset),
+                        escape_html(&alt)
+                    );
+                    if !title.is_empty() {
+                        html.push_str(&format!(" title=\"{}\"", escape_html(&title)));
+                    }
+                    html.push_str(" />");
+                    html
+                }
+                (None, None) => continue,
+            };
+            output.push(Event::Html(html.into()));
+        }
+        output
+    }
+
+    /// Replace the links written as `@[iframe](url)` by an iframe embedding the page
+    fn embed_iframes<'a>(&self, events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+        let mut output: Vec<Event<'a>> = Vec::with_capacity(events.len());
+        let mut events = events.into_iter().peekable();
+        while let Some(event) = events.next() {
+            let is_iframe = match (output.last(), events.peek()) {
+                (Some(Event::Text(before)), Some(Event::Text(text))) => {
+                    before.ends_with('@') && &**text == "iframe"
+                }
+                _ => false,
+            };
+            let url = match event {
+                Event::Start(Tag::Link(_, url, _)) if is_iframe && iframe::is_allowed(&url) => url,
+                event => {
+                    output.push(event);
+                    continue;
+                }
+            };
+            // Skip the text and the end of the link and remove the leading `@`
+            events.next();
+            events.next();
+            if let Some(Event::Text(before)) = output.last_mut() {
+                *before = before.trim_end_matches('@').to_owned().into();
+            }
+            let mut attributes = Vec::new();
+            if let Some(Event::Text(text)) = events.peek_mut() {
+                let parsed = media::parse_flags(text).map(|(attributes, rest)| {
+                    let attributes: Vec<String> =
+                        attributes.into_iter().map(String::from).collect();
+                    (attributes, rest.to_owned())
+                });
+                if let Some((parsed, rest)) = parsed {
+                    attributes = parsed;
+                    *text = rest.into();
+                }
+            }
+            let attributes: Vec<&str> = attributes.iter().map(String::as_str).collect();
+            let url = self.assets.get(&*url).map_or(&*url, String::as_str);
+            output.push(Event::Html(iframe::to_html(url, &attributes).into()));
+        }
+        output
+    }
+
+    /// Highlight inline code written in the language set by a `{.lang}` attribute right after it
+    /// or, when missing, in the default inline language
+    fn highlight_inline_code<'a>(&self, events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+        let mut output = Vec::with_capacity(events.len());
+        let mut events = events.into_iter().peekable();
+        while let Some(event) = events.next() {
+            let code = match event {
+                Event::Code(code) => code,
+                event => {
+                    output.push(event);
+                    continue;
+                }
+            };
+            let mut lang = self.inline_lang.clone();
+            if let Some(Event::Text(text)) = events.peek_mut() {
+                let attribute =
+                    parse_inline_lang(text).map(|(l, rest)| (l.to_owned(), rest.to_owned()));
+                if let Some((attribute_lang, rest)) = attribute {
+                    lang = Some(attribute_lang);
+                    *text = rest.into();
+                }
+            }
+            let syntax = lang
+                .as_deref()
+                .and_then(|lang| self.syntax_set.find_syntax_by_token(lang));
+            match syntax {
+                Some(syntax) => {
+                    let mut highlighter = HighlightLines::new(syntax, &self.theme);
+                    let regions = highlighter.highlight(&code, &self.syntax_set);
+                    let html = styled_line_to_highlighted_html(&regions, IncludeBackground::No);
+                    let (_, background) = start_highlighted_html_snippet(&self.theme);
+                    output.push(Event::Html(
+                        format!(
+                            "<code class=\"highlighted\" \
+                             style=\"background-color:#{:02x}{:02x}{:02x};\">{}</code>",
+                            background.r, background.g, background.b, html
+                        )
+                        .into(),
+                    ));
+                }
+                None => output.push(Event::Code(code)),
+            }
+        }
+        output
+    }
+
+    /// Render the page to a string, see `render_to` to stream it instead
     pub fn render(
         &self,
         input: String,
         css: Option<String>,
         js: Option<String>,
     ) -> Result<Output, Error> {
-        // Create parser
-        let mut opts = MarkdownOptions::empty();
-        opts.insert(MarkdownOptions::ENABLE_TABLES);
-        let parser = Parser::new_ext(&input, opts);
+        let mut output = None;
+        let mut body = String::new();
+        let (timings, warnings) = self.render_parts(input, css, js, |part| {
+            match part {
+                Part::Head(metadata, style, script, links) => {
+                    output = Some((metadata, style, script, links))
+                }
+                Part::Slide(index, html) => {
+                    if index > 0 {
+                        body.push('\n');
+                    }
+                    body.push_str(&html);
+                }
+                Part::Warnings(html) => body.push_str(&html),
+            }
+            Ok(())
+        })?;
+        let (metadata, style, script, links) =
+            output.expect("the head is rendered before the slides");
+        Ok(Output {
+            metadata,
+            style,
+            script,
+            links,
+            body,
+            timings,
+            warnings,
+        })
+    }
+
+    /// Render the page to the given writer slide by slide, so that the markup of the whole
+    /// presentation is never held in memory at once. The bytes written are the same as the
+    /// ones of `render`.
+    pub fn render_to<W: io::Write>(
+        &self,
+        input: String,
+        css: Option<String>,
+        js: Option<String>,
+        mut writer: W,
+    ) -> Result<(Timings, Vec<Warning>), Error> {
+        let rendered = self.render_parts(input, css, js, |part| {
+            match part {
+                Part::Head(metadata, style, script, links) => {
+                    writer.write_all(head(&metadata, &style, &script, &links).as_bytes())?
+                }
+                Part::Slide(index, html) => {
+                    if index > 0 {
+                        writer.write_all(b"\n")?;
+                    }
+                    writer.write_all(html.as_bytes())?;
+                }
+                Part::Warnings(html) => writer.write_all(html.as_bytes())?,
+            }
+            Ok(())
+        })?;
+        writer.write_all(FOOTER.as_bytes())?;
+        writer.flush()?;
+        Ok(rendered)
+    }
+
+    /// Render the page and hand over its parts as soon as they are ready: the head first, then
+    /// each slide in order and finally the warnings, if they are shown
+    fn render_parts<F>(
+        &self,
+        input: String,
+        css: Option<String>,
+        js: Option<String>,
+        mut emit: F,
+    ) -> Result<(Timings, Vec<Warning>), Error>
+    where
+        F: FnMut(Part) -> Result<(), Error>,
+    {
+        let (input, css, js) = if self.reproducible {
+            let normalize = |s: String| s.replace("\r\n", "\n");
+            (normalize(input), css.map(normalize), js.map(normalize))
+        } else {
+            (input, css, js)
+        };
+
+        // Closing the span logs how long the rendering took when debugging
+        let span = debug_span!("render", slides = field::Empty, highlight = field::Empty);
+        let _enter = span.enter();
+        let mut timings = Timings::default();
+
+        let parse_start = Instant::now();
+        let (mut input, abbreviations, removed) = markup::abbreviations(&input);
+        let (front_matter, _) = front_matter::parse(&input)?;
+        if front_matter.glossary && !abbreviations.is_empty() {
+            input.push_str(&markup::glossary(&abbreviations));
+        }
+        let mut slides = slides::parse(&input);
+        if let Some(max_slides) = self.max_slides {
+            slides.truncate(max_slides);
+        }
+        if let Some(ref selection) = self.slides {
+            slides.retain(|slide| selection.contains(slide.index));
+        }
+        // Point to the lines of the deck as written, abbreviation definitions included
+        for slide in &mut slides {
+            let (first, last) = slide.lines;
+            slide.lines = (
+                markup::original_line(first, &removed),
+                markup::original_line(last, &removed),
+            );
+        }
+        timings.record("parse", parse_start.elapsed());
+        span.record("slides", &(slides.len() as u64));
+        let title = self
+            .title
+            .clone()
+            .or_else(|| deck_title(front_matter.title, &slides));
+        let titles = slides.iter().map(|slide| slide.title.clone()).collect();
+        let keys = keys::keymap(front_matter.keys, front_matter.swap_navigation);
+
+        // Build inline css
+        let mut style = include_str!("style.css").to_owned();
+        if let Some(theme_css) = self.deck_theme.css() {
+            style.push_str(theme_css);
+        }
+        if let Some(variables) = front_matter.theme.to_css() {
+            style.push_str(&variables);
+        }
+        if self.balance_headings {
+            style.push_str(BALANCED_HEADINGS_CSS);
+        }
+        if !self.animations {
+            style.push_str(NO_ANIMATIONS_CSS);
+        }
+        if let Some(ref custom_css) = css {
+            style.push_str(custom_css);
+        }
+        let style = timings
+            .time("minify css", || minifier::css::minify(&style))
+            .map_err(|s| Error::Minification(s))?;
+
+        // Build inline js
+        let config = ScriptConfig {
+            base_url: &self.base_url,
+            titles,
+            keys,
+            show_notes: self.show_notes,
+            print_notes: self.print_notes.name(),
+            sync: self.sync,
+            presenter: self.presenter,
+            duration: self.duration,
+            fullscreen: self.fullscreen,
+            wake_lock: self.wake_lock,
+            show_warnings: self.show_warnings,
+            kiosk: self.kiosk,
+            analytics: self.analytics,
+        };
+        let mut script = format!("const DECK_CONFIG = {};\n", serde_json::to_string(&config)?);
+        script.push_str(include_str!("cast.js"));
+        script.push_str(include_str!("script.js"));
+        if let Some(ref custom_js) = js {
+            script.push_str(custom_js);
+        }
+        let script = timings.time("minify js", || minifier::js::minify(&script));
+        let mut links = String::new();
+        for link in &self.link_css {
+            links.push_str(&link.stylesheet_tag());
+        }
+        for link in &self.link_js {
+            links.push_str(&link.script_tag());
+        }
+        let metadata = Metadata {
+            title,
+            author: front_matter.author,
+        };
+        emit(Part::Head(metadata, style, script, links))?;
+
+        // Time spent highlighting the code blocks
+        let mut highlight_time = Duration::default();
+        let mut render_time = Duration::default();
+        let mut warnings = Vec::new();
+        // Slides are separated by their position in the page, which differs from their index when
+        // only some of them are selected
+        for (position, slide) in slides.into_iter().enumerate() {
+            let slide_start = Instant::now();
+            let (index, title) = (slide.index, slide.title.clone());
+            let html =
+                self.render_slide(slide, &abbreviations, &mut highlight_time, &mut warnings)?;
+            let elapsed = slide_start.elapsed();
+            render_time += elapsed;
+            timings.record_slide(index, title, elapsed);
+            emit(Part::Slide(position, html))?;
+        }
+        if self.show_warnings && !warnings.is_empty() {
+            emit(Part::Warnings(warnings_html(&warnings)))?;
+        }
+
+        span.record("highlight", &field::debug(highlight_time));
+        timings.record("render", render_time - highlight_time);
+        timings.record("highlight", highlight_time);
+        Ok((timings, warnings))
+    }
+
+    /// Report the local images of the slide missing from the base directory, the ones replaced
+    /// by another URL or inlined having been found when preparing the assets
+    fn check_images(&self, slide: &slides::Slide, warnings: &mut Vec<Warning>) {
+        let base_dir = match self.base_dir {
+            Some(ref base_dir) => base_dir,
+            None => return,
+        };
+        for event in &slide.events {
+            let url = match event {
+                Event::Start(Tag::Image(_, url, _)) => url,
+                _ => continue,
+            };
+            if !assets::is_local(url)
+                || self.assets.contains_key(&**url)
+                || self.svgs.contains_key(&**url)
+            {
+                continue;
+            }
+            let path = assets::url_path(url);
+            let file = self
+                .asset_dirs
+                .iter()
+                .find_map(|asset_dir| asset_dir.resolve(&path.to_string_lossy()))
+                .unwrap_or_else(|| base_dir.join(path));
+            if !file.is_file() {
+                warnings.push(Warning {
+                    slide: slide.index,
+                    message: format!("image not found: {}", url),
+                });
+            }
+        }
+    }
+
+    fn render_slide(
+        &self,
+        slide: slides::Slide,
+        abbreviations: &[(String, String)],
+        highlight_time: &mut Duration,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<String, Error> {
+        self.check_images(&slide, warnings);
+        let mut html = String::new();
+        let mut class = String::from("slide");
+        if let Some(ref layout) = slide.layout {
+            class.push_str(" layout-");
+            class.push_str(layout);
+        } else if self.section_dividers && slide.is_section_divider() {
+            class.push_str(" slide--section");
+        } else if slide.is_quote() {
+            class.push_str(" layout-quote");
+        }
+        if slide.countdown.is_some() {
+            class.push_str(" slide--break");
+        }
+        for name in &slide.classes {
+            class.push(' ');
+            class.push_str(name);
+        }
+        let time = match slide.time {
+            Some(time) => format!(" data-time=\"{}\"", time),
+            None => String::new(),
+        };
+        let lines = if self.source_lines {
+            format!(" data-lines=\"{}-{}\"", slide.lines.0, slide.lines.1)
+        } else {
+            String::new()
+        };
+        html.push_str(&format!(
+            "<div class=\"{}\" id=\"{}\"{}{}>\n<div class=\"content\">\n",
+            class, slide.id, time, lines
+        ));
         let mut in_code_block = false;
         let mut highlighter = None;
-        let parser = parser.map(|event| match event {
-            Event::Rule => {
-                Event::Html("</div>\n</div>\n<div class=\"slide\">\n<div class=\"content\">".into())
-            }
+        let mut max_lines = None;
+        let mut code_lines = 0;
+        let mut has_steps = false;
+        let events = render_charts(slide.events, slide.index + 1)?;
+        let events = markup::quote_attributions(markup::task_lists(events));
+        let events = markup::definition_lists(markup::tables(events));
+        let events = markup::inline_marks(markup::autolinks(events));
+        let events = markup::expand_abbreviations(events, abbreviations);
+        let events = if self.balance_headings {
+            markup::balance_headings(events)
+        } else {
+            events
+        };
+        let events = self.embed_media(self.highlight_inline_code(self.render_images(events)));
+        let events = self.embed_iframes(events);
+        let index = slide.index;
+        let events = events.into_iter().map(|event| match event {
             Event::Start(Tag::CodeBlock(ref kind)) => {
                 in_code_block = true;
-                let snippet = start_highlighted_html_snippet(&self.theme);
-                let lang = match kind {
-                    CodeBlockKind::Indented => "",
-                    CodeBlockKind::Fenced(lang) => lang,
+                let info = match kind {
+                    CodeBlockKind::Indented => Info::default(),
+                    CodeBlockKind::Fenced(info) => Info::parse(info),
                 };
-                if let Some(syntax) = self.syntax_set.find_syntax_by_token(lang) {
-                    highlighter = Some(HighlightLines::new(syntax, &self.theme));
+                let (snippet, _) = start_highlighted_html_snippet(&self.theme);
+                // Append the style of the block to the background set by syntect
+                let style = block_style(&info);
+                let mut snippet = snippet.replacen("\">", &format!("{}\">", style), 1);
+                let steps = info.get("steps").and_then(parse_steps);
+                has_steps = steps.is_some();
+                if let Some(steps) = steps {
+                    snippet =
+                        snippet.replacen("<pre", &format!("<pre data-steps=\"{}\"", steps), 1);
+                }
+                match self.syntax_set.find_syntax_by_token(&info.lang) {
+                    Some(syntax) => highlighter = Some(HighlightLines::new(syntax, &self.theme)),
+                    None if !info.lang.is_empty() && !PLAIN_LANGS.contains(&info.lang.as_str()) => {
+                        warnings.push(Warning {
+                            slide: index,
+                            message: format!("unknown language {:?}, not highlighted", info.lang),
+                        })
+                    }
+                    None => {}
+                }
+                max_lines = info
+                    .get("max_lines")
+                    .and_then(|lines| lines.parse::<usize>().ok())
+                    .or(self.max_code_lines);
+                code_lines = 0;
+                match max_lines {
+                    Some(lines) => Event::Html(
+                        format!(
+                            "<div class=\"code-clamp\" style=\"--max-lines: {}\">{}",
+                            lines, snippet
+                        )
+                        .into(),
+                    ),
+                    None => Event::Html(snippet.into()),
                 }
-                Event::Html(snippet.0.into())
             }
             Event::End(Tag::CodeBlock(_)) => {
+                in_code_block = false;
                 highlighter = None;
-                Event::Html("</pre>".into())
+                match max_lines.take() {
+                    Some(lines) if code_lines > lines => Event::Html(
+                        format!(
+                            "</pre>\n<div class=\"code-more\">{} more lines</div>\n</div>",
+                            code_lines - lines
+                        )
+                        .into(),
+                    ),
+                    Some(_) => Event::Html("</pre>\n</div>".into()),
+                    None => Event::Html("</pre>".into()),
+                }
+            }
+            Event::Start(Tag::Image(link_type, url, title)) => {
+                let url = match self.assets.get(&*url) {
+                    Some(new_url) => new_url.clone().into(),
+                    None => url,
+                };
+                Event::Start(Tag::Image(link_type, url, title))
             }
             Event::Text(text) => {
                 if in_code_block {
-                    if let Some(ref mut highlighter) = highlighter {
-                        let highlighted = highlighter.highlight(&text, &self.syntax_set);
-                        let html =
-                            styled_line_to_highlighted_html(&highlighted, IncludeBackground::No);
+                    code_lines += text.matches('\n').count();
+                    if highlighter.is_some() || has_steps {
+                        let started = Instant::now();
+                        let mut html = String::with_capacity(text.len());
+                        for line in text.split_inclusive('\n') {
+                            let line_html = match highlighter {
+                                Some(ref mut highlighter) => styled_line_to_highlighted_html(
+                                    &highlighter.highlight(line, &self.syntax_set),
+                                    IncludeBackground::No,
+                                ),
+                                None => escape_html(line),
+                            };
+                            // Lines are wrapped so that the script can highlight them step
+                            // by step
+                            if has_steps {
+                                html.push_str(&format!(
+                                    "<span class=\"line\">{}</span>",
+                                    line_html
+                                ));
+                            } else {
+                                html.push_str(&line_html);
+                            }
+                        }
+                        *highlight_time += started.elapsed();
                         return Event::Html(html.into());
                     }
                 }
@@ -134,31 +1330,44 @@ impl Renderer {
             }
             e => e,
         });
-
-        let mut html = String::with_capacity(input.len());
-        html::push_html(&mut html, parser);
-        html.insert_str(0, "<div class=\"slide\">\n<div class=\"content\">\n");
-        html.push_str("</div>\n</div>");
-
-        // Build inline css
-        let mut style = include_str!("style.css").to_owned();
-        if let Some(ref custom_css) = css {
-            style.push_str(custom_css);
+        if self.lazy_media && slide.index > 1 {
+            let mut content = String::new();
+            html::push_html(&mut content, events);
+            html.push_str(&defer_media(&content));
+        } else {
+            html::push_html(&mut html, events);
         }
-        let style = minifier::css::minify(&style).map_err(|s| Error::Minification(s))?;
-
-        // Build inline js
-        let mut script = include_str!("script.js").to_owned();
-        if let Some(ref custom_js) = js {
-            script.push_str(custom_js);
+        if let Some(countdown) = slide.countdown {
+            html.push_str(&format!(
+                "<div class=\"countdown\" data-duration=\"{}\"{}>{}:{:02}</div>\n",
+                countdown.duration,
+                if countdown.chime { " data-chime" } else { "" },
+                countdown.duration / 60,
+                countdown.duration % 60
+            ));
         }
-        let script = minifier::js::minify(&script);
-        Ok(Output {
-            title: self.title.clone(),
-            style,
-            script,
-            body: html,
-        })
+        html.push_str("</div>\n");
+        if let Some(corner) = self.webcam_corner {
+            html.push_str(&format!(
+                "<div class=\"webcam webcam--{}\"></div>\n",
+                corner.name()
+            ));
+        }
+        if let Some(ref watermark) = self.watermark {
+            html.push_str(&format!(
+                "<div class=\"watermark\" aria-hidden=\"true\"><span>{}</span></div>\n",
+                escape_html(watermark)
+            ));
+        }
+        let embed_notes = self.show_notes || self.print_notes != PrintNotes::None;
+        if let (true, Some(notes)) = (embed_notes, &slide.notes) {
+            html.push_str(&format!(
+                "<aside class=\"notes\">{}</aside>\n",
+                escape_html(notes)
+            ));
+        }
+        html.push_str("</div>");
+        Ok(html)
     }
 }
 
@@ -183,13 +1392,13 @@ And it should work"#;
             .render(input.into(), None, None)
             .expect("Failed to render");
         assert_eq!(
-            r#"<div class="slide">
+            r#"<div class="slide" id="slide-1">
 <div class="content">
 <h1>Slide 1</h1>
 <p>This is a <strong>test</strong></p>
 </div>
 </div>
-<div class="slide">
+<div class="slide" id="slide-2">
 <div class="content">
 <h1>Slide 2</h1>
 <p>And it should work</p>
@@ -198,4 +1407,438 @@ And it should work"#;
             output.body
         );
     }
+
+    #[test]
+    fn test_render_selection() {
+        let options = Options {
+            slides: Some("2-3".parse().unwrap()),
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let output = renderer
+            .render("# A\n\n---\n\n# B\n\n---\n\n# C\n".into(), None, None)
+            .expect("Failed to render");
+        assert!(output.body.starts_with("<div class=\"slide"));
+        assert!(!output.body.contains("<h1>A</h1>"));
+        assert!(output.body.contains("<h1>B</h1>") && output.body.contains("<h1>C</h1>"));
+    }
+
+    #[test]
+    fn test_render_watermark() {
+        let options = Options {
+            watermark: Some("CONFIDENTIAL – <jane@example.com>".to_owned()),
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let output = renderer
+            .render("# A\n\n---\n\n# B\n".into(), None, None)
+            .expect("Failed to render");
+        let watermark = "<div class=\"watermark\" aria-hidden=\"true\">\
+                         <span>CONFIDENTIAL – &lt;jane@example.com&gt;</span></div>";
+        assert_eq!(2, output.body.matches(watermark).count());
+    }
+
+    #[test]
+    fn test_render_title() {
+        let renderer = Renderer::try_new(Options::default()).expect("Failed to create renderer");
+        let render = |input: &str| {
+            renderer
+                .render(input.into(), None, None)
+                .expect("Failed to render")
+        };
+        let output = render("## Agenda\n\n---\n\n# Generics `<T>`\n\n# Traits\n");
+        assert_eq!(Some("Generics <T>"), output.metadata.title.as_deref());
+        assert!(output
+            .to_string()
+            .contains("<title>Generics &lt;T&gt;</title>"));
+        assert_eq!(None, render("## Agenda\n").metadata.title);
+        let output = render("---\ntitle: Deck\nauthor: Ada & Grace\n---\n# Traits\n");
+        assert_eq!(Some("Deck"), output.metadata.title.as_deref());
+        assert!(output
+            .to_string()
+            .contains("<meta name=\"author\" content=\"Ada &amp; Grace\">"));
+    }
+
+    #[test]
+    fn test_render_source_lines() {
+        let options = Options {
+            source_lines: true,
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let input = "*[CLI]: Command Line Interface\n# A CLI\n\n---\n\nSome text\n";
+        let output = renderer
+            .render(input.into(), None, None)
+            .expect("Failed to render");
+        assert!(output.body.contains("id=\"a-cli\" data-lines=\"2-2\">"));
+        assert!(output.body.contains("id=\"slide-2\" data-lines=\"6-6\">"));
+    }
+
+    #[test]
+    fn test_render_warnings() {
+        let options = Options {
+            base_dir: Some(PathBuf::from(env!("CARGO_MANIFEST_DIR"))),
+            show_warnings: true,
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let input = "![Readme](README.md)\n\n---\n\n![Logo](missing.png)\n\n```text\nplain\n```\n\n```klingon\nQapla'\n```\n";
+        let output = renderer
+            .render(input.into(), None, None)
+            .expect("Failed to render");
+        let warnings: Vec<String> = output.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            vec![
+                "Slide 2: image not found: missing.png",
+                "Slide 2: unknown language \"klingon\", not highlighted",
+            ],
+            warnings
+        );
+        assert!(output.body.contains(
+            "<li class=\"toast\" data-slide=\"1\">Slide 2: image not found: missing.png<button"
+        ));
+    }
+
+    #[test]
+    fn test_render_escaped_images() {
+        let dir = std::env::temp_dir().join(format!("deck-images-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("my photo.png"), "").unwrap();
+        let options = Options {
+            base_dir: Some(dir.clone()),
+            show_warnings: true,
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let input = "![Photo](my%20photo.png)\n\n![Other](my%20other%20photo.png)\n";
+        let output = renderer
+            .render(input.into(), None, None)
+            .expect("Failed to render");
+        std::fs::remove_dir_all(&dir).unwrap();
+        let warnings: Vec<String> = output.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            vec!["Slide 1: image not found: my%20other%20photo.png"],
+            warnings
+        );
+    }
+
+    #[test]
+    fn test_render_links() {
+        let link: Link = "https://example.com/deck.css sha384-abc"
+            .parse()
+            .expect("Failed to parse link");
+        assert_eq!(Some("sha384-abc"), link.integrity.as_deref());
+        assert!("https://example.com/deck.css md5-abc"
+            .parse::<Link>()
+            .is_err());
+        assert!("https://example.com/deck.css sha384-"
+            .parse::<Link>()
+            .is_err());
+        let options = Options {
+            link_css: vec![link],
+            link_js: vec!["https://example.com/deck.js".parse().unwrap()],
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let output = renderer
+            .render("# Slide 1\n".into(), None, None)
+            .expect("Failed to render");
+        assert!(output.to_string().contains(
+            "</script>\n<link rel=\"stylesheet\" href=\"https://example.com/deck.css\" \
+             integrity=\"sha384-abc\" crossorigin=\"anonymous\">\n\
+             <script src=\"https://example.com/deck.js\"></script>\n<body>"
+        ));
+    }
+
+    #[test]
+    fn test_render_accessible_themes() {
+        let options = Options {
+            theme: Some("colorblind-safe".to_owned()),
+            deck_theme: DeckTheme::HighContrast,
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let output = renderer
+            .render("```rust\nlet s = \"deck\";\n```\n".into(), None, None)
+            .expect("Failed to render")
+            .to_string();
+        assert!(output.contains("#ffd700"));
+        // Strings are orange in the Okabe-Ito palette
+        assert!(output.contains("color:#e69f00;"));
+    }
+
+    #[test]
+    fn test_render_notes() {
+        let input = "# Slide 1\n\n<!-- notes: Mind the <gap> -->\n";
+        let options = Options {
+            show_notes: true,
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let output = renderer
+            .render(input.into(), None, None)
+            .expect("Failed to render");
+        assert!(output
+            .body
+            .contains("<aside class=\"notes\">Mind the &lt;gap&gt;</aside>"));
+
+        let renderer = Renderer::try_new(Options::default()).expect("Failed to create renderer");
+        let output = renderer
+            .render(input.into(), None, None)
+            .expect("Failed to render");
+        assert!(!output.body.contains("notes"));
+
+        let options = Options {
+            print_notes: PrintNotes::Interleaved,
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let output = renderer
+            .render(input.into(), None, None)
+            .expect("Failed to render");
+        assert!(output.body.contains("<aside class=\"notes\">"));
+        assert!(output.script.contains("\"printNotes\":\"interleaved\""));
+    }
+
+    #[test]
+    fn test_render_lazy_media() {
+        let input = "![a](a.png)\n\n---\n![b](b.png)\n\n---\n![c](c.png) `<img src=\"d.png\">`\n";
+        let renderer = Renderer::try_new(Options::default()).expect("Failed to create renderer");
+        let output = renderer
+            .render(input.into(), None, None)
+            .expect("Failed to render");
+        assert!(output.body.contains("<img src=\"b.png\""));
+        assert!(output.body.contains("<img data-src=\"c.png\""));
+        assert!(output.body.contains("&lt;img src=&quot;d.png&quot;&gt;"));
+
+        let options = Options {
+            lazy_media: false,
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let output = renderer
+            .render(input.into(), None, None)
+            .expect("Failed to render");
+        assert!(!output.body.contains("data-src"));
+    }
+
+    #[test]
+    fn test_render_max_lines() {
+        let renderer = Renderer::try_new(Options::default()).expect("Failed to create renderer");
+        let output = renderer
+            .render(
+                "```text {max_lines=2}\n1\n2\n3\n4\n```\n".into(),
+                None,
+                None,
+            )
+            .expect("Failed to render");
+        assert!(output
+            .body
+            .contains("<div class=\"code-clamp\" style=\"--max-lines: 2\">"));
+        assert!(output
+            .body
+            .contains("<div class=\"code-more\">2 more lines</div>"));
+    }
+
+    #[test]
+    fn test_block_style() {
+        assert_eq!("", block_style(&Info::parse("rust")));
+        assert_eq!(
+            "font-size:0.8em;white-space:pre-wrap;overflow-wrap:anywhere;",
+            block_style(&Info::parse("rust {fontsize=0.8 wrap}"))
+        );
+        assert_eq!("", block_style(&Info::parse("rust {fontsize=big}")));
+    }
+
+    #[test]
+    fn test_render_inline_code() {
+        let renderer = Renderer::try_new(Options::default()).expect("Failed to create renderer");
+        let output = renderer
+            .render("`let x = 1;`{.rust} and `plain`\n".into(), None, None)
+            .expect("Failed to render");
+        assert!(output.body.contains("<code class=\"highlighted\""));
+        assert!(output.body.contains("</code> and <code>plain</code>"));
+        assert!(!output.body.contains("{.rust}"));
+    }
+
+    #[test]
+    fn test_render_media() {
+        let renderer = Renderer::try_new(Options::default()).expect("Failed to create renderer");
+        let output = renderer
+            .render(
+                "![video](demo.mp4){autoplay muted} ![audio](talk.mp3) ![logo](logo.png)\n".into(),
+                None,
+                None,
+            )
+            .expect("Failed to render");
+        assert_eq!(
+            "<p><video src=\"demo.mp4\" controls data-autoplay muted></video> \
+             <audio src=\"talk.mp3\" controls></audio> \
+             <img src=\"logo.png\" alt=\"logo\" /></p>",
+            output.body.lines().nth(2).unwrap_or_default()
+        );
+    }
+
+    #[test]
+    fn test_render_iframe() {
+        let renderer = Renderer::try_new(Options::default()).expect("Failed to create renderer");
+        let output = renderer
+            .render(
+                "Demo: @[iframe](https://example.com){height=400} and [link](https://example.com)\n"
+                    .into(),
+                None,
+                None,
+            )
+            .expect("Failed to render");
+        assert_eq!(
+            "<p>Demo: <iframe class=\"embed\" src=\"https://example.com\" \
+             style=\"height:400px;\" sandbox=\"allow-scripts allow-same-origin allow-forms \
+             allow-popups\" loading=\"lazy\" allowfullscreen></iframe> and \
+             <a href=\"https://example.com\">link</a></p>",
+            output.body.lines().nth(2).unwrap_or_default()
+        );
+    }
+
+    #[test]
+    fn test_render_chart() {
+        let renderer = Renderer::try_new(Options::default()).expect("Failed to create renderer");
+        let output = renderer
+            .render(
+                "```chart {type=line}\nmonth,visits\njan,3\nfeb,5\n```\n".into(),
+                None,
+                None,
+            )
+            .expect("Failed to render");
+        assert!(output.body.contains("<svg class=\"chart\""));
+        assert!(output.body.contains("<polyline"));
+        assert!(!output.body.contains("<pre"));
+        assert!(renderer
+            .render("```chart\nmonth,visits\njan\n```\n".into(), None, None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let mut svgs = BTreeMap::new();
+        svgs.insert("a.svg".to_owned(), "<svg><rect/></svg>".to_owned());
+        let options = Options {
+            svgs,
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let output = renderer
+            .render("![A diagram](a.svg) ![Other](b.svg)\n".into(), None, None)
+            .expect("Failed to render");
+        assert_eq!(
+            "<p><span class=\"svg\" role=\"img\" aria-label=\"A diagram\"><svg><rect/></svg>\
+             </span> <img src=\"b.svg\" alt=\"Other\" /></p>",
+            output.body.lines().nth(2).unwrap_or_default()
+        );
+    }
+
+    #[test]
+    fn test_render_srcset() {
+        let mut srcsets = BTreeMap::new();
+        srcsets.insert(
+            "photo.jpg".to_owned(),
+            "photo-640w.jpg 640w, photo.jpg 1000w".to_owned(),
+        );
+        let options = Options {
+            srcsets,
+            ..Options::default()
+        };
+        let renderer = Renderer::try_new(options).expect("Failed to create renderer");
+        let output = renderer
+            .render("![A *photo*](photo.jpg \"Title\")\n".into(), None, None)
+            .expect("Failed to render");
+        assert_eq!(
+            "<p><img src=\"photo.jpg\" srcset=\"photo-640w.jpg 640w, photo.jpg 1000w\" \
+             sizes=\"100vw\" alt=\"A photo\" title=\"Title\" /></p>",
+            output.body.lines().nth(2).unwrap_or_default()
+        );
+    }
+
+    #[test]
+    fn test_parse_steps() {
+        assert_eq!(
+            Some("1-3|4-8,12|10".to_owned()),
+            parse_steps("[1-3][4-8,12][10]")
+        );
+        assert_eq!(None, parse_steps("1-3"));
+        assert_eq!(None, parse_steps("[1-][2]"));
+        assert_eq!(None, parse_steps("[a]"));
+    }
+
+    #[test]
+    fn test_render_reproducible() {
+        let render = |input: &str, css: &str, assets: &[(&str, &str)]| {
+            let options = Options {
+                reproducible: true,
+                assets: assets
+                    .iter()
+                    .map(|(url, new_url)| (url.to_string(), new_url.to_string()))
+                    .collect(),
+                ..Options::default()
+            };
+            Renderer::try_new(options)
+                .expect("Failed to create renderer")
+                .render(
+                    input.into(),
+                    Some(css.into()),
+                    Some("let a = 1;\r\n".into()),
+                )
+                .expect("Failed to render")
+                .to_string()
+        };
+        let input = "# Slide 1\n\n![](a.png) ![](b.png)\n\n---\n\n```rust\nfn main() {}\n```\n";
+        let assets = [("a.png", "assets/0-a.png"), ("b.png", "assets/1-b.png")];
+        let output = render(input, "body {\n}", &assets);
+        assert!(output.contains("assets/1-b.png"));
+        assert_eq!(output, render(input, "body {\n}", &assets));
+        assert_eq!(
+            output,
+            render(
+                &input.replace('\n', "\r\n"),
+                "body {\r\n}",
+                &[assets[1], assets[0]]
+            )
+        );
+    }
+
+    #[test]
+    fn test_highlighting() {
+        let highlighting = Highlighting::load(&[]).expect("Failed to load the highlighting");
+        let renderer = highlighting
+            .renderer(Options::default())
+            .expect("Failed to create renderer");
+        let light = highlighting
+            .renderer(Options {
+                theme: Some("InspiredGitHub".to_owned()),
+                ..Options::default()
+            })
+            .expect("Failed to create renderer");
+        assert!(Arc::ptr_eq(&renderer.syntax_set, &light.syntax_set));
+        assert!(!Arc::ptr_eq(&renderer.theme, &light.theme));
+        assert!(highlighting
+            .renderer(Options {
+                theme: Some("Unknown".to_owned()),
+                ..Options::default()
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_render_to() {
+        let renderer = Renderer::try_new(Options::default()).expect("Failed to create renderer");
+        let input = "---\ntitle: Deck\n---\n# Slide 1\n\n---\n\n```rust\nfn main() {}\n```\n";
+        let mut streamed = Vec::new();
+        let (timings, _) = renderer
+            .render_to(input.into(), None, None, &mut streamed)
+            .expect("Failed to render");
+        let output = renderer
+            .render(input.into(), None, None)
+            .expect("Failed to render");
+        assert_eq!(output.to_string().as_bytes(), &streamed[..]);
+        assert!(timings.to_string().contains("highlight"));
+    }
 }
@@ -0,0 +1,91 @@
+use crate::{assets, html::escape_html};
+
+/// Permissions granted to embedded pages, enough for most demos and code playgrounds while
+/// preventing them from navigating the deck away
+const SANDBOX: &str = "allow-scripts allow-same-origin allow-forms allow-popups";
+/// Permissions granted to local pages, served from the origin of the deck. Keeping their origin
+/// along with their scripts would let them lift their sandbox and script the deck.
+const LOCAL_SANDBOX: &str = "allow-scripts allow-forms allow-popups";
+
+/// Whether the URL can be embedded, only web pages and local files are allowed
+pub fn is_allowed(url: &str) -> bool {
+    let has_scheme = url.split('/').next().map_or(false, |s| s.contains(':'));
+    url.starts_with("https://")
+        || url.starts_with("http://")
+        || (assets::is_local(url) && !has_scheme)
+}
+
+/// Length given to the `width` and `height` attributes, plain numbers are in pixels
+fn parse_length(value: &str) -> Option<String> {
+    if value.is_empty() {
+        return None;
+    }
+    if value.chars().all(|c| c.is_ascii_digit()) {
+        return Some(format!("{}px", value));
+    }
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(unit_start);
+    if number.parse::<f32>().is_err() {
+        return None;
+    }
+    match unit {
+        "px" | "em" | "rem" | "%" | "vw" | "vh" => Some(value.to_owned()),
+        _ => None,
+    }
+}
+
+/// Sandboxed iframe embedding the page, sized by the `width=...` and `height=...` attributes
+/// written as in `@[iframe](https://example.com){height=400}`
+pub fn to_html(url: &str, attributes: &[&str]) -> String {
+    let mut style = String::new();
+    for attribute in attributes {
+        let (key, value) = match attribute.find('=') {
+            Some(i) => (&attribute[..i], &attribute[i + 1..]),
+            None => continue,
+        };
+        if let ("width", Some(length)) | ("height", Some(length)) = (key, parse_length(value)) {
+            style.push_str(&format!("{}:{};", key, length));
+        }
+    }
+    let mut html = format!("<iframe class=\"embed\" src=\"{}\"", escape_html(url));
+    if !style.is_empty() {
+        html.push_str(&format!(" style=\"{}\"", style));
+    }
+    let sandbox = if assets::is_local(url) {
+        LOCAL_SANDBOX
+    } else {
+        SANDBOX
+    };
+    html.push_str(&format!(
+        " sandbox=\"{}\" loading=\"lazy\" allowfullscreen></iframe>",
+        sandbox
+    ));
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_html() {
+        assert_eq!(
+            "<iframe class=\"embed\" src=\"https://example.com/?a=1&amp;b=2\" \
+             style=\"width:80%;height:400px;\" sandbox=\"allow-scripts allow-same-origin \
+             allow-forms allow-popups\" loading=\"lazy\" allowfullscreen></iframe>",
+            to_html(
+                "https://example.com/?a=1&b=2",
+                &["width=80%", "height=400", "height=1;x", "border=0"]
+            )
+        );
+        assert_eq!(
+            "<iframe class=\"embed\" src=\"demo/index.html\" sandbox=\"allow-scripts \
+             allow-forms allow-popups\" loading=\"lazy\" allowfullscreen></iframe>",
+            to_html("demo/index.html", &[])
+        );
+        assert!(is_allowed("demo/index.html"));
+        assert!(!is_allowed("javascript:alert(1)"));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{assets, error::Error, slides::Slide};
+use image::{imageops::FilterType, GenericImageView};
+use pulldown_cmark::{Event, Tag};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+/// Widths of the variants generated for the images wider than them
+pub const WIDTHS: [u32; 3] = [640, 1280, 1920];
+
+/// Whether the image can be decoded and resized, animated GIFs are left untouched
+fn is_raster(url: &str) -> bool {
+    let url = url.to_ascii_lowercase();
+    url.ends_with(".png") || url.ends_with(".jpg") || url.ends_with(".jpeg")
+}
+
+/// URL of the variant of an image with the given width, e.g. `photo-640w.jpg`
+fn variant_url(url: &str, width: u32) -> String {
+    match url.rfind('.').filter(|&i| !url[i..].contains('/')) {
+        Some(i) => format!("{}-{}w{}", &url[..i], width, &url[i..]),
+        None => format!("{}-{}w", url, width),
+    }
+}
+
+/// Resize the raster images copied to the output directory. Images wider than the maximum width
+/// are replaced by a downscaled copy in the assets and, when responsive, smaller variants are
+/// generated for each of the standard widths. Returns the `srcset` attribute of the images having
+/// variants.
+pub fn resize(
+    slides: &[Slide],
+    output_dir: &Path,
+    assets: &mut HashMap<String, String>,
+    max_width: Option<u32>,
+    responsive: bool,
+) -> Result<HashMap<String, String>, Error> {
+    let mut srcsets = HashMap::new();
+    let mut resized = HashSet::new();
+    for slide in slides {
+        for event in &slide.events {
+            let url = match event {
+                Event::Start(Tag::Image(_, url, _)) => url,
+                _ => continue,
+            };
+            if !assets::is_local(url) || !is_raster(url) || !resized.insert(url.to_string()) {
+                continue;
+            }
+            // Images are only resized once they have been copied, the originals are never
+            // overwritten
+            let copied_url = match assets.get(&**url) {
+                Some(copied_url) if !copied_url.contains(|c| c == '?' || c == '#') => {
+                    copied_url.clone()
+                }
+                _ => continue,
+            };
+            let image = image::open(output_dir.join(assets::url_path(&copied_url)))?;
+            let mut width = image.width();
+            let mut main_url = copied_url.clone();
+            if let Some(max_width) = max_width.filter(|&max_width| width > max_width) {
+                main_url = variant_url(&copied_url, max_width);
+                image
+                    .resize(max_width, u32::MAX, FilterType::Lanczos3)
+                    .save(output_dir.join(assets::url_path(&main_url)))?;
+                width = max_width;
+                assets.insert(url.to_string(), main_url.clone());
+            }
+            if !responsive {
+                continue;
+            }
+            let mut srcset = Vec::new();
+            for &variant_width in WIDTHS.iter().filter(|&&w| w < width) {
+                let variant_url = variant_url(&copied_url, variant_width);
+                image
+                    .resize(variant_width, u32::MAX, FilterType::Lanczos3)
+                    .save(output_dir.join(assets::url_path(&variant_url)))?;
+                srcset.push(format!("{} {}w", variant_url, variant_width));
+            }
+            if !srcset.is_empty() {
+                srcset.push(format!("{} {}w", main_url, width));
+                srcsets.insert(url.to_string(), srcset.join(", "));
+            }
+        }
+    }
+    Ok(srcsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_url() {
+        assert_eq!("img/photo-640w.jpg", variant_url("img/photo.jpg", 640));
+        assert_eq!("v1.0/photo-1280w", variant_url("v1.0/photo", 1280));
+        assert!(is_raster("photo.JPG"));
+        assert!(!is_raster("animation.gif"));
+    }
+}
@@ -0,0 +1,97 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use tracing::warn;
+
+/// Keys bound to each action of the presentation by default, named after the `key` property of
+/// keyboard events. Presenter remotes usually send `PageDown` and `PageUp` to navigate, `b` or
+/// `.` to black out the screen and alternate between `F5` and `Escape` to start and stop the
+/// presentation.
+const DEFAULT_KEYS: [(&str, &[&str]); 11] = [
+    ("next", &["ArrowRight", "PageDown"]),
+    ("previous", &["ArrowLeft", "PageUp"]),
+    ("black", &["b", "."]),
+    ("present", &["F5"]),
+    ("exit", &["Escape"]),
+    ("pointer", &["l"]),
+    ("draw", &["d"]),
+    ("notes", &["n"]),
+    ("fullscreen", &["f"]),
+    ("search", &["/"]),
+    ("appearance", &["D"]),
+];
+
+/// Keys bound to an action in the front matter, either a single one or a list
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Keys {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Keys::One(key) => vec![key],
+            Keys::Many(keys) => keys,
+        }
+    }
+}
+
+/// Merge the keys bound in the front matter with the default ones, the keys of an action
+/// replacing its default keys. The keys of the next and previous actions are swapped for the
+/// remotes whose buttons are reversed.
+pub fn keymap(
+    overrides: BTreeMap<String, Keys>,
+    swap_navigation: bool,
+) -> BTreeMap<String, Vec<String>> {
+    let mut keymap: BTreeMap<String, Vec<String>> = DEFAULT_KEYS
+        .iter()
+        .map(|(action, keys)| {
+            let keys = keys.iter().map(|key| key.to_string()).collect();
+            (action.to_string(), keys)
+        })
+        .collect();
+    for (action, keys) in overrides {
+        match keymap.get_mut(&action) {
+            Some(bound) => *bound = keys.into_vec(),
+            None => {
+                let actions: Vec<&str> = DEFAULT_KEYS.iter().map(|(action, _)| *action).collect();
+                warn!(
+                    "Unknown action {:?} in the keys of the front matter, expected one of {}",
+                    action,
+                    actions.join(", ")
+                );
+            }
+        }
+    }
+    if swap_navigation {
+        let next = keymap.remove("next").unwrap_or_default();
+        let previous = keymap.insert("previous".to_owned(), next);
+        keymap.insert("next".to_owned(), previous.unwrap_or_default());
+    }
+    keymap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keymap() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert(
+            "next".to_owned(),
+            Keys::Many(vec!["ArrowDown".to_owned(), "PageDown".to_owned()]),
+        );
+        overrides.insert("black".to_owned(), Keys::One("z".to_owned()));
+        overrides.insert("overview".to_owned(), Keys::One("o".to_owned()));
+        let bound = keymap(overrides.clone(), false);
+        assert_eq!(vec!["ArrowDown", "PageDown"], bound["next"]);
+        assert_eq!(vec!["z"], bound["black"]);
+        assert_eq!(vec!["ArrowLeft", "PageUp"], bound["previous"]);
+        assert!(!bound.contains_key("overview"));
+        let bound = keymap(overrides, true);
+        assert_eq!(vec!["ArrowLeft", "PageUp"], bound["next"]);
+        assert_eq!(vec!["ArrowDown", "PageDown"], bound["previous"]);
+    }
+}
@@ -0,0 +1,38 @@
+//! Rendering and serving of the presentations, shared by the `deck` command and its benchmarks.
+//! Only the renderer is built without the `cli` feature, e.g. for `wasm32-unknown-unknown`.
+
+#[cfg(feature = "cli")]
+pub mod analytics;
+pub mod assets;
+pub mod budget;
+pub mod chart;
+#[cfg(feature = "cli")]
+pub mod check;
+pub mod code;
+pub mod compat;
+pub mod diff;
+pub mod error;
+pub mod excerpt;
+pub mod front_matter;
+pub mod html;
+pub mod iframe;
+#[cfg(feature = "cli")]
+pub mod images;
+pub mod keys;
+pub mod markup;
+#[cfg(feature = "cli")]
+pub mod mdns;
+pub mod media;
+pub mod merge;
+pub mod notes;
+pub mod poll;
+#[cfg(feature = "cli")]
+pub mod record;
+#[cfg(feature = "cli")]
+pub mod server;
+pub mod slides;
+pub mod svg;
+pub mod timings;
+pub mod variables;
+#[cfg(feature = "wasm")]
+pub mod wasm;
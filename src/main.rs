@@ -1,23 +1,79 @@
+use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Read};
-use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+use std::{
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
 
-use structopt::StructOpt;
+use structopt::{clap::Shell, StructOpt};
+use syntect::parsing::SyntaxSet;
+use tracing_subscriber::fmt::format::FmtSpan;
 
-use crate::error::Error;
-
-mod error;
-mod html;
-mod server;
+use deck::{
+    assets, budget, check, code, compat, diff, error::Error, front_matter, html, images, media,
+    merge, notes, server, slides, svg, timings,
+};
 
 #[derive(Debug, StructOpt)]
 struct Cli {
-    #[structopt(long = "verbose", short = "v")]
-    verbose: bool,
+    /// Log more details, `-v` logs the information of every dependency, `-vv` debugging details
+    /// and `-vvv` traces
+    #[structopt(long = "verbose", short = "v", parse(from_occurrences))]
+    verbose: u8,
+    /// Only log errors, e.g. in CI
+    #[structopt(long = "quiet", short = "q", conflicts_with = "verbose")]
+    quiet: bool,
+    /// Format of the logs, `json` writes a JSON object per line for log collectors
+    #[structopt(
+        long = "log-format",
+        default_value = "text",
+        possible_values = &LogFormat::variants()
+    )]
+    log_format: LogFormat,
     #[structopt(subcommand)]
     cmd: Command,
 }
 
+/// Directives of the log filter, the dependencies only report warnings unless verbose. Requests
+/// are traced by warp, each one within a span whose closing logs how long it took.
+fn log_filter(verbose: u8, quiet: bool) -> &'static str {
+    match (quiet, verbose) {
+        (true, _) => "error",
+        (false, 0) => "warn,deck=info,warp::filters::trace=info",
+        (false, 1) => "info",
+        (false, 2) => "debug",
+        (false, _) => "trace",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    fn variants() -> [&'static str; 2] {
+        ["text", "json"]
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LogFormat, String> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, StructOpt)]
 enum Command {
     /// Convert a markdown file containing the slides markup to a self-contained
@@ -33,12 +89,128 @@ enum Command {
         /// Add a directory to the paths searched for syntect themes (.tmTheme files)
         #[structopt(long = "theme-dir")]
         theme_dirs: Vec<PathBuf>,
-        /// Add custom css from the given file
-        #[structopt(long = "css")]
-        css: Option<PathBuf>,
-        /// Add custom javascript from the given file
-        #[structopt(long = "js")]
-        js: Option<PathBuf>,
+        /// Set the palette of the slides, `high-contrast` being suited to projectors and low
+        /// vision audiences
+        #[structopt(
+            long = "deck-theme",
+            possible_values = &html::DeckTheme::variants(),
+            default_value = "default"
+        )]
+        deck_theme: html::DeckTheme,
+        /// Add custom css from the given file, repeat it to add several files in order
+        #[structopt(long = "css", number_of_values = 1)]
+        css: Vec<PathBuf>,
+        /// Add custom javascript from the given file, repeat it to add several files in order
+        #[structopt(long = "js", number_of_values = 1)]
+        js: Vec<PathBuf>,
+        /// Link the stylesheet at the given URL instead of inlining it, the URL can be followed
+        /// by a space and its integrity hash, e.g. "https://example.com/deck.css sha384-...".
+        /// Repeat it to link several stylesheets in order
+        #[structopt(long = "link-css", number_of_values = 1)]
+        link_css: Vec<html::Link>,
+        /// Load the script at the given URL instead of inlining it, the URL can be followed by a
+        /// space and its integrity hash. Repeat it to load several scripts in order
+        #[structopt(long = "link-js", number_of_values = 1)]
+        link_js: Vec<html::Link>,
+        /// Download the linked stylesheets and scripts to pin them with their integrity hash,
+        /// unless one is already given
+        #[structopt(long = "integrity")]
+        integrity: bool,
+        /// Write the HTML to the given file instead of stdout and copy the local assets it
+        /// references to the same directory
+        #[structopt(long = "output", short = "o", parse(from_os_str))]
+        output: Option<PathBuf>,
+        /// Guarantee byte-identical output for identical decks regardless of the platform they
+        /// were checked out on
+        #[structopt(long = "reproducible")]
+        reproducible: bool,
+        /// Don't style the slides made of a single `#` heading as section dividers
+        #[structopt(long = "no-section-dividers")]
+        no_section_dividers: bool,
+        /// Keep the last two words of the headings together and balance the length of their lines
+        #[structopt(long = "balance-headings")]
+        balance_headings: bool,
+        /// Show the slides and their steps without any transition, e.g. for screen recordings
+        #[structopt(long = "no-animations")]
+        no_animations: bool,
+        /// Render only the given slides, e.g. `1,5-10`, numbered from 1
+        #[structopt(long = "slides")]
+        selection: Option<slides::Selection>,
+        /// Stamp the given text diagonally across every slide, `{recipient}` being replaced by
+        /// the value of `--recipient`
+        #[structopt(long = "watermark")]
+        watermark: Option<String>,
+        /// Name or email address of the person the presentation is sent to, for the watermark
+        #[structopt(long = "recipient", requires = "watermark")]
+        recipient: Option<String>,
+        /// Embed the speaker notes in the presentation, they can then be toggled with the `n` key
+        #[structopt(long = "show-notes")]
+        show_notes: bool,
+        /// Print the speaker notes on pages of their own, after all the slides or right after
+        /// their slide, when saving the presentation as a PDF from the browser
+        #[structopt(
+            long = "print-notes",
+            possible_values = &html::PrintNotes::variants(),
+            default_value = "none"
+        )]
+        print_notes: html::PrintNotes,
+        /// Limit the height of code blocks to the given number of lines and make them scrollable
+        #[structopt(long = "max-code-lines")]
+        max_code_lines: Option<usize>,
+        /// Highlight inline code without a `{.lang}` attribute using the given language
+        #[structopt(long = "inline-lang")]
+        inline_lang: Option<String>,
+        /// Embed the local video and audio files no larger than the given size, in KB, as data
+        /// URLs
+        #[structopt(long = "inline-media")]
+        inline_media: Option<u64>,
+        /// Generate smaller variants of the copied PNG and JPEG images and let browsers pick the
+        /// most appropriate one
+        #[structopt(long = "responsive-images")]
+        responsive_images: bool,
+        /// Downscale the copied PNG and JPEG images wider than the given number of pixels
+        #[structopt(long = "max-image-width")]
+        max_image_width: Option<u32>,
+        /// Convert the slides written for another presentation tool
+        #[structopt(long = "compat", possible_values = &compat::Compat::variants())]
+        compat: Option<compat::Compat>,
+        /// Heading level starting a new slide in pandoc decks, defaults to the highest level
+        /// directly followed by content
+        #[structopt(long = "slide-level")]
+        slide_level: Option<usize>,
+        /// Load the images, videos and iframes of all the slides when opening the presentation
+        /// instead of a few slides ahead
+        #[structopt(long = "no-lazy-media")]
+        no_lazy_media: bool,
+        /// Inline the local SVG images so that they can be styled with css
+        #[structopt(long = "inline-svg")]
+        inline_svg: bool,
+        /// Remove the scripts and event handlers of the inlined SVG images, implies --inline-svg
+        #[structopt(long = "sanitize-svg")]
+        sanitize_svg: bool,
+        /// Don't toggle fullscreen with the `f` key
+        #[structopt(long = "no-fullscreen")]
+        no_fullscreen: bool,
+        /// Let the screen sleep while the presentation is shown
+        #[structopt(long = "no-wake-lock")]
+        no_wake_lock: bool,
+        /// Reserve a corner of every slide for the webcam overlay of a recorded talk
+        #[structopt(long = "webcam-corner", possible_values = &html::Corner::variants())]
+        webcam_corner: Option<html::Corner>,
+        /// Fail instead of writing the presentation if it requests any resource from the
+        /// network, such as remote images, fonts or iframes
+        #[structopt(long = "offline")]
+        offline: bool,
+        /// Warn when the generated HTML is larger than the given size, e.g. 5MB, and list the
+        /// largest assets embedded in it
+        #[structopt(long = "max-size")]
+        max_size: Option<budget::Size>,
+        /// Fail instead of writing the presentation when it is larger than --max-size
+        #[structopt(long = "max-size-error", requires = "max-size")]
+        max_size_error: bool,
+        /// Report how long each phase of the build took, and the slowest slides with --verbose
+        #[structopt(long = "timings")]
+        timings: bool,
     },
     /// Serve a local markdown files containing the slides markup
     #[structopt(name = "serve")]
@@ -46,25 +218,262 @@ enum Command {
         /// Change the port used by the server
         #[structopt(long = "port", short = "p", default_value = "8000")]
         port: u16,
-        /// Markdown file containing the slides markup
+        /// Change the address the server listens on, repeat it to listen on several ones. `::`
+        /// listens on every IPv6 and IPv4 address on most systems.
+        #[structopt(long = "host", default_value = "127.0.0.1", number_of_values = 1)]
+        hosts: Vec<IpAddr>,
+        /// Advertise the presentation on the local network using mDNS
+        #[structopt(long = "mdns")]
+        mdns: bool,
+        /// Serve the presentation under the given path, e.g. when running behind a reverse proxy
+        #[structopt(long = "base-url", default_value = "/")]
+        base_url: String,
+        /// Listen on the given unix domain socket instead of a tcp port
+        #[structopt(long = "uds", parse(from_os_str))]
+        uds: Option<PathBuf>,
+        /// Save the current slide, the recorded timings, the polls and the questions to the given
+        /// file, and resume them from it when restarting the server
+        #[structopt(long = "state", parse(from_os_str))]
+        state: Option<PathBuf>,
+        /// Serve a directory of shared assets under a prefix of the URLs, e.g.
+        /// `common=~/slides/common-assets` for the slides to reference `common/logo.png`
+        #[structopt(long = "asset-dir", number_of_values = 1)]
+        asset_dirs: Vec<assets::AssetDir>,
+        /// Report how many viewers had each slide active and for how long, without identifying
+        /// them, to the given file and at /api/analytics
+        #[structopt(long = "analytics", parse(from_os_str))]
+        analytics: Option<PathBuf>,
+        /// Maximum number of viewers connected at once, 500 by default when listening on a
+        /// public address
+        #[structopt(long = "max-connections")]
+        max_connections: Option<usize>,
+        /// Maximum number of requests per minute from each address, 1200 by default when
+        /// listening on a public address
+        #[structopt(long = "rate-limit")]
+        rate_limit: Option<usize>,
+        /// Open the presentation in the default browser once the server listens
+        #[structopt(long = "open")]
+        open: bool,
+        /// Open the presentation with the given command instead of the default browser, e.g.
+        /// `chromium --kiosk --user-data-dir=/tmp/deck`, the URL is appended to its arguments
+        #[structopt(long = "browser")]
+        browser: Option<String>,
+        /// Markdown file containing the slides markup, use `-` to read it from stdin
         #[structopt(parse(from_os_str))]
         input: PathBuf,
         /// Whether the input file, the custom css file or the custom js file should be watched for
         /// change
         #[structopt(long = "watch", short = "w")]
         watch: bool,
+        /// Check the watched files for changes every given number of milliseconds instead of
+        /// relying on inotify, which doesn't work on network filesystems such as NFS or SSHFS
+        #[structopt(long = "poll", requires = "watch")]
+        poll: Option<u64>,
         /// Set the theme used to highlight text within the code blocks
         #[structopt(long = "theme")]
         theme: Option<String>,
         /// Add a directory to the paths searched for syntect themes (.tmTheme files)
         #[structopt(long = "theme-dir")]
         theme_dirs: Vec<PathBuf>,
-        /// Add custom css from the given file
-        #[structopt(long = "css")]
-        css: Option<PathBuf>,
-        /// Add custom js from the given file
-        #[structopt(long = "js")]
-        js: Option<PathBuf>,
+        /// Set the palette of the slides, `high-contrast` being suited to projectors and low
+        /// vision audiences
+        #[structopt(
+            long = "deck-theme",
+            possible_values = &html::DeckTheme::variants(),
+            default_value = "default"
+        )]
+        deck_theme: html::DeckTheme,
+        /// Add custom css from the given file, repeat it to add several files in order
+        #[structopt(long = "css", number_of_values = 1)]
+        css: Vec<PathBuf>,
+        /// Add custom js from the given file, repeat it to add several files in order
+        #[structopt(long = "js", number_of_values = 1)]
+        js: Vec<PathBuf>,
+        /// Don't style the slides made of a single `#` heading as section dividers
+        #[structopt(long = "no-section-dividers")]
+        no_section_dividers: bool,
+        /// Keep the last two words of the headings together and balance the length of their lines
+        #[structopt(long = "balance-headings")]
+        balance_headings: bool,
+        /// Duration of the talk in minutes, the presenter view then shows the remaining time
+        #[structopt(long = "duration")]
+        duration: Option<u64>,
+        /// Limit the height of code blocks to the given number of lines and make them scrollable
+        #[structopt(long = "max-code-lines")]
+        max_code_lines: Option<usize>,
+        /// Highlight inline code without a `{.lang}` attribute using the given language
+        #[structopt(long = "inline-lang")]
+        inline_lang: Option<String>,
+        /// Let viewers ask and upvote questions from the /ask page, they are listed in the
+        /// presenter view
+        #[structopt(long = "ask")]
+        ask: bool,
+        /// Only let the presenter view opened with the token printed at startup change slides,
+        /// draw and point, the other views follow it
+        #[structopt(long = "pair")]
+        pair: bool,
+        /// Convert the slides written for another presentation tool
+        #[structopt(long = "compat", possible_values = &compat::Compat::variants())]
+        compat: Option<compat::Compat>,
+        /// Heading level starting a new slide in pandoc decks, defaults to the highest level
+        /// directly followed by content
+        #[structopt(long = "slide-level")]
+        slide_level: Option<usize>,
+        /// Record the time at which each slide is presented to the given JSON file
+        #[structopt(long = "record", parse(from_os_str))]
+        record: Option<PathBuf>,
+        /// Also record the speaker notes of each presented slide
+        #[structopt(long = "record-notes")]
+        record_notes: bool,
+        /// Don't toggle fullscreen with the `f` key
+        #[structopt(long = "no-fullscreen")]
+        no_fullscreen: bool,
+        /// Let the screen sleep while the presentation is shown
+        #[structopt(long = "no-wake-lock")]
+        no_wake_lock: bool,
+        /// Reserve a corner of every slide for the webcam overlay of a recorded talk, a preview
+        /// of the webcam is shown there when opening the presentation with `?webcam=true`
+        #[structopt(long = "webcam-corner", possible_values = &html::Corner::variants())]
+        webcam_corner: Option<html::Corner>,
+    },
+    /// Edit a markdown file in the browser next to a live preview of the slides, the changes
+    /// being saved back to the file
+    #[structopt(name = "edit")]
+    Edit {
+        /// Change the port used by the server
+        #[structopt(long = "port", short = "p", default_value = "8000")]
+        port: u16,
+        /// Change the address the server listens on, repeat it to listen on several ones
+        #[structopt(long = "host", default_value = "127.0.0.1", number_of_values = 1)]
+        hosts: Vec<IpAddr>,
+        /// Markdown file containing the slides markup
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+        /// Check the files for changes every given number of milliseconds instead of relying on
+        /// inotify, which doesn't work on network filesystems such as NFS or SSHFS
+        #[structopt(long = "poll")]
+        poll: Option<u64>,
+        /// Set the theme used to highlight text within the code blocks
+        #[structopt(long = "theme")]
+        theme: Option<String>,
+        /// Add a directory to the paths searched for syntect themes (.tmTheme files)
+        #[structopt(long = "theme-dir")]
+        theme_dirs: Vec<PathBuf>,
+        /// Add custom css from the given file, repeat it to add several files in order
+        #[structopt(long = "css", number_of_values = 1)]
+        css: Vec<PathBuf>,
+        /// Add custom js from the given file, repeat it to add several files in order
+        #[structopt(long = "js", number_of_values = 1)]
+        js: Vec<PathBuf>,
+    },
+    /// Run the presentation on a kiosk, such as signage or a booth demo: the deck is served on
+    /// localhost and shown by a fullscreen browser going through the slides in a loop, which is
+    /// launched again whenever it crashes
+    #[structopt(name = "kiosk")]
+    Kiosk {
+        /// Change the port used by the server
+        #[structopt(long = "port", short = "p", default_value = "8000")]
+        port: u16,
+        /// Markdown file containing the slides markup, reloaded when it changes
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+        /// Number of seconds each slide is shown for, unless a `time` directive plans another
+        /// duration
+        #[structopt(long = "interval", default_value = "10")]
+        interval: u64,
+        /// Command launching the browser in kiosk mode, the URL is appended to its arguments
+        #[structopt(
+            long = "browser",
+            default_value = "chromium --kiosk --noerrdialogs --disable-infobars --autoplay-policy=no-user-gesture-required"
+        )]
+        browser: String,
+        /// Set the theme used to highlight text within the code blocks
+        #[structopt(long = "theme")]
+        theme: Option<String>,
+        /// Add a directory to the paths searched for syntect themes (.tmTheme files)
+        #[structopt(long = "theme-dir")]
+        theme_dirs: Vec<PathBuf>,
+        /// Add custom css from the given file, repeat it to add several files in order
+        #[structopt(long = "css", number_of_values = 1)]
+        css: Vec<PathBuf>,
+        /// Add custom js from the given file, repeat it to add several files in order
+        #[structopt(long = "js", number_of_values = 1)]
+        js: Vec<PathBuf>,
+    },
+    /// Extract the speaker notes of the markdown slides read from stdin
+    #[structopt(name = "extract-notes")]
+    ExtractNotes {
+        /// Format of the extracted notes
+        #[structopt(
+            long = "format",
+            default_value = "markdown",
+            possible_values = &notes::Format::variants()
+        )]
+        format: notes::Format,
+    },
+    /// Extract the fenced code blocks of the markdown slides read from stdin to separate files
+    #[structopt(name = "extract-code")]
+    ExtractCode {
+        /// Directory where the code blocks and their manifest are written
+        #[structopt(parse(from_os_str))]
+        output_dir: PathBuf,
+    },
+    /// Run the code blocks marked with the `test` attribute in the markdown slides read from
+    /// stdin and fail if any of them exits with an error
+    #[structopt(name = "test")]
+    Test,
+    /// Verify that the links and images of the markdown slides read from stdin resolve and
+    /// report the slide of each broken one
+    #[structopt(name = "check")]
+    Check {
+        /// Also send HTTP requests to verify that external URLs can be reached
+        #[structopt(long = "check-external")]
+        check_external: bool,
+        /// Timeout of each HTTP request, in seconds
+        #[structopt(long = "timeout", default_value = "10")]
+        timeout: u64,
+        /// Maximum number of HTTP requests sent at the same time
+        #[structopt(long = "concurrency", default_value = "8")]
+        concurrency: usize,
+        /// Also verify that the generated presentation doesn't request any resource from the
+        /// network, as required when presenting in air-gapped environments
+        #[structopt(long = "offline")]
+        offline: bool,
+        /// Also verify that every stylesheet and script the given generated page links to has
+        /// an integrity hash matching its current content
+        #[structopt(long = "integrity", parse(from_os_str))]
+        integrity: Option<PathBuf>,
+    },
+    /// Concatenate several markdown decks into a single one written to stdout, each deck being
+    /// introduced by a section slide holding its title
+    #[structopt(name = "merge")]
+    Merge {
+        /// Set the title of the resulting deck
+        #[structopt(long = "title")]
+        title: Option<String>,
+        /// Markdown files containing the decks to merge
+        #[structopt(parse(from_os_str), required = true)]
+        inputs: Vec<PathBuf>,
+    },
+    /// Compare two versions of a deck and report the slides added, removed, changed or moved
+    #[structopt(name = "diff")]
+    Diff {
+        /// Markdown file containing the previous version of the deck
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+        /// Markdown file containing the new version of the deck
+        #[structopt(parse(from_os_str))]
+        new: PathBuf,
+        /// Also write a page showing both versions of each slide that differs side by side
+        #[structopt(long = "html", parse(from_os_str))]
+        html: Option<PathBuf>,
+    },
+    /// Generate a completion script for the given shell
+    #[structopt(name = "completions")]
+    Completions {
+        /// Shell the completion script is generated for
+        #[structopt(possible_values = &Shell::variants(), case_insensitive = true)]
+        shell: Shell,
     },
 }
 
@@ -72,74 +481,509 @@ enum Command {
 async fn main() -> Result<(), Error> {
     let cli = Cli::from_args();
 
-    pretty_env_logger::formatted_builder()
-        .filter_module(
-            "deck",
-            if cli.verbose {
-                log::LevelFilter::Debug
-            } else {
-                log::LevelFilter::Info
-            },
-        )
-        .init();
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(log_filter(cli.verbose, cli.quiet))
+        .with_span_events(FmtSpan::CLOSE);
+    match cli.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
 
     match cli.cmd {
         Command::Build {
             theme,
             title,
-            css,
-            js,
+            css: css_paths,
+            js: js_paths,
+            mut link_css,
+            mut link_js,
+            integrity,
             theme_dirs,
+            deck_theme,
+            output,
+            reproducible,
+            no_section_dividers,
+            balance_headings,
+            no_animations,
+            selection,
+            watermark,
+            recipient,
+            show_notes,
+            print_notes,
+            max_code_lines,
+            inline_lang,
+            inline_media,
+            responsive_images,
+            max_image_width,
+            no_lazy_media,
+            compat,
+            slide_level,
+            inline_svg,
+            sanitize_svg,
+            no_fullscreen,
+            no_wake_lock,
+            webcam_corner,
+            offline,
+            max_size,
+            max_size_error,
+            timings: report_timings,
         } => {
+            let mut timings = timings::Timings::default();
+            timings.per_slide = cli.verbose > 0;
+
+            let watermark = match (watermark, recipient) {
+                (Some(watermark), Some(recipient)) => {
+                    Some(watermark.replace("{recipient}", &recipient))
+                }
+                (Some(watermark), None) if watermark.contains("{recipient}") => {
+                    tracing::error!("The watermark names a recipient but --recipient is missing");
+                    process::exit(1);
+                }
+                (watermark, _) => watermark,
+            };
+
             // Read input from stdin
             let mut input = String::new();
             io::stdin().read_to_string(&mut input)?;
+            if let Some(compat) = compat {
+                input = compat::convert(&input, compat, slide_level);
+            }
 
-            let css = if let Some(path) = css {
-                let s = fs::read_to_string(path)?;
-                Some(s)
-            } else {
-                None
-            };
+            // The files listed in the front matter follow the ones given on the command line,
+            // they are relative to the current directory as the markdown is read from stdin
+            let (front_matter, _) = front_matter::parse(&input)?;
+            let css_paths: Vec<PathBuf> = css_paths.into_iter().chain(front_matter.css).collect();
+            let js_paths: Vec<PathBuf> = js_paths.into_iter().chain(front_matter.js).collect();
+            let mut styles = css_paths
+                .iter()
+                .map(fs::read_to_string)
+                .collect::<Result<Vec<String>, io::Error>>()?;
+            let scripts = js_paths
+                .iter()
+                .map(fs::read_to_string)
+                .collect::<Result<Vec<String>, io::Error>>()?;
 
-            let js = if let Some(path) = js {
-                let s = fs::read_to_string(path)?;
-                Some(s)
+            // Copy the assets next to the output, paths are relative to the current directory
+            // as the markdown is read from stdin
+            // Only the media of the selected slides are embedded and copied
+            let mut slides = slides::parse(&input);
+            if let Some(ref selection) = selection {
+                slides.retain(|slide| selection.contains(slide.index));
+            }
+            let mut assets = timings.time("embed media", || {
+                media::inline(&slides, Path::new(""), inline_media.map(|size| size * 1024))
+            })?;
+            let mut srcsets = HashMap::new();
+            if let Some(ref output) = output {
+                let output_dir = output.parent().unwrap_or_else(|| Path::new(""));
+                let mut copier = assets::Copier::new(output_dir);
+                let copied = timings.time("copy images", || {
+                    assets::copy_images(&slides, Path::new(""), &mut copier)
+                })?;
+                for (url, new_url) in copied {
+                    // Media embedded as data URLs take precedence over their copy
+                    assets.entry(url).or_insert(new_url);
+                }
+                if responsive_images || max_image_width.is_some() {
+                    srcsets = timings.time("resize images", || {
+                        images::resize(
+                            &slides,
+                            output_dir,
+                            &mut assets,
+                            max_image_width,
+                            responsive_images,
+                        )
+                    })?;
+                }
+                for (style, path) in styles.iter_mut().zip(&css_paths) {
+                    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+                    *style = assets::copy_stylesheet_urls(style, base_dir, &mut copier)?;
+                }
+            }
+
+            let svgs = if inline_svg || sanitize_svg {
+                timings.time("inline svg", || {
+                    svg::load(&slides, Path::new(""), sanitize_svg)
+                })?
             } else {
-                None
+                HashMap::new()
             };
 
-            // Render html to stdout
+            let css = html::join_custom(styles, "\n");
+            let js = html::join_custom(scripts, html::JS_SEPARATOR);
+            if integrity {
+                let client = reqwest::Client::new();
+                for link in link_css.iter_mut().chain(link_js.iter_mut()) {
+                    if link.integrity.is_none() {
+                        let content = check::fetch(&client, &link.url).await?;
+                        link.integrity = Some(check::integrity(&content));
+                    }
+                }
+            }
+
+            let embedded = budget::embedded(&assets, &svgs, css.as_deref());
+
+            // Render html
             let options = html::Options {
                 title,
                 theme,
                 theme_dirs,
+                deck_theme,
+                assets: assets.into_iter().collect(),
+                svgs: svgs.into_iter().collect(),
+                srcsets: srcsets.into_iter().collect(),
+                lazy_media: !no_lazy_media,
+                reproducible,
+                section_dividers: !no_section_dividers,
+                balance_headings,
+                animations: !no_animations,
+                slides: selection,
+                watermark,
+                show_notes,
+                print_notes,
+                max_code_lines,
+                inline_lang,
+                fullscreen: !no_fullscreen,
+                wake_lock: !no_wake_lock,
+                webcam_corner,
+                base_dir: Some(PathBuf::new()),
+                link_css,
+                link_js,
+                ..html::Options::default()
             };
 
-            let renderer = html::Renderer::try_new(options)?;
-            let html = renderer.render(input, css, js)?;
-            print!("{}", html);
+            let renderer = timings.time("load syntaxes", || html::Renderer::try_new(options))?;
+            // The page is streamed to the output unless it has to be checked before being written
+            if !offline && max_size.is_none() {
+                let (render_timings, warnings) = match output {
+                    Some(path) => {
+                        let file = io::BufWriter::new(fs::File::create(path)?);
+                        renderer.render_to(input, css, js, file)?
+                    }
+                    None => renderer.render_to(input, css, js, io::stdout().lock())?,
+                };
+                for warning in warnings {
+                    tracing::warn!("{}", warning);
+                }
+                timings.extend(render_timings);
+                if report_timings {
+                    eprint!("{}", timings);
+                }
+                return Ok(());
+            }
+            let mut output_html = renderer.render(input, css, js)?;
+            for warning in &output_html.warnings {
+                tracing::warn!("{}", warning);
+            }
+            timings.extend(std::mem::take(&mut output_html.timings));
+            let html = output_html.to_string();
+            if report_timings {
+                eprint!("{}", timings);
+            }
+            if offline {
+                let requests = check::external_requests(&html);
+                for request in &requests {
+                    eprintln!("{}", request);
+                }
+                if !requests.is_empty() {
+                    process::exit(1);
+                }
+            }
+            if let Some(max_size) = max_size {
+                let size = budget::Size(html.len() as u64);
+                if let Some(message) = budget::exceeded(size, max_size, &embedded) {
+                    if max_size_error {
+                        tracing::error!("{}", message);
+                        process::exit(1);
+                    }
+                    tracing::warn!("{}", message);
+                }
+            }
+            match output {
+                Some(path) => fs::write(path, html)?,
+                None => print!("{}", html),
+            }
         }
         Command::Serve {
             port,
+            hosts,
+            mdns,
+            base_url,
+            uds,
+            state,
+            analytics,
+            asset_dirs,
+            max_connections,
+            rate_limit,
+            open,
+            browser,
             input,
             watch,
+            poll,
             theme,
             theme_dirs,
+            deck_theme,
             css,
             js,
+            no_section_dividers,
+            balance_headings,
+            duration,
+            max_code_lines,
+            inline_lang,
+            ask,
+            pair,
+            compat,
+            slide_level,
+            record,
+            record_notes,
+            no_fullscreen,
+            no_wake_lock,
+            webcam_corner,
         } => {
             let config = server::Config {
                 port,
+                hosts,
+                mdns,
+                base_url,
+                uds,
                 watch,
+                poll: poll.map(Duration::from_millis),
+                input,
+                theme,
+                theme_dirs,
+                deck_theme,
+                css,
+                js,
+                section_dividers: !no_section_dividers,
+                balance_headings,
+                duration,
+                max_code_lines,
+                inline_lang,
+                ask,
+                compat,
+                slide_level,
+                record,
+                record_notes,
+                fullscreen: !no_fullscreen,
+                wake_lock: !no_wake_lock,
+                webcam_corner,
+                pair,
+                edit: false,
+                open,
+                browser,
+                kiosk: None,
+                state,
+                analytics,
+                asset_dirs,
+                max_connections,
+                rate_limit,
+            };
+            server::start(config).await?;
+        }
+        Command::Edit {
+            port,
+            hosts,
+            input,
+            poll,
+            theme,
+            theme_dirs,
+            css,
+            js,
+        } => {
+            if input == Path::new("-") {
+                tracing::error!("The slides read from stdin can't be edited");
+                process::exit(1);
+            }
+            // The preview is reloaded by the watcher once the editor saved the file
+            let config = server::Config {
+                port,
+                hosts,
+                mdns: false,
+                base_url: String::from("/"),
+                uds: None,
+                watch: true,
+                poll: poll.map(Duration::from_millis),
+                input,
+                theme,
+                theme_dirs,
+                deck_theme: html::DeckTheme::Default,
+                css,
+                js,
+                section_dividers: true,
+                balance_headings: false,
+                duration: None,
+                max_code_lines: None,
+                inline_lang: None,
+                ask: false,
+                compat: None,
+                slide_level: None,
+                record: None,
+                record_notes: false,
+                fullscreen: true,
+                wake_lock: true,
+                webcam_corner: None,
+                pair: false,
+                edit: true,
+                open: false,
+                browser: None,
+                kiosk: None,
+                state: None,
+                analytics: None,
+                asset_dirs: Vec::new(),
+                max_connections: None,
+                rate_limit: None,
+            };
+            server::start(config).await?;
+        }
+        Command::Kiosk {
+            port,
+            input,
+            interval,
+            browser,
+            theme,
+            theme_dirs,
+            css,
+            js,
+        } => {
+            let config = server::Config {
+                port,
+                hosts: vec![IpAddr::from([127, 0, 0, 1])],
+                mdns: false,
+                base_url: String::from("/"),
+                uds: None,
+                watch: input != Path::new("-"),
+                poll: None,
                 input,
                 theme,
                 theme_dirs,
+                deck_theme: html::DeckTheme::Default,
                 css,
                 js,
+                section_dividers: true,
+                balance_headings: false,
+                duration: None,
+                max_code_lines: None,
+                inline_lang: None,
+                ask: false,
+                compat: None,
+                slide_level: None,
+                record: None,
+                record_notes: false,
+                fullscreen: false,
+                wake_lock: true,
+                webcam_corner: None,
+                pair: false,
+                edit: false,
+                open: true,
+                browser: Some(browser),
+                kiosk: Some(interval),
+                state: None,
+                analytics: None,
+                asset_dirs: Vec::new(),
+                max_connections: None,
+                rate_limit: None,
             };
             server::start(config).await?;
         }
+        Command::ExtractNotes { format } => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            let slides = slides::parse(&input);
+            print!("{}", notes::extract(&slides, format));
+        }
+        Command::ExtractCode { output_dir } => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            let slides = slides::parse(&input);
+            let blocks = code::collect(&slides);
+            let syntax_set = SyntaxSet::load_defaults_newlines();
+            code::extract(&blocks, &syntax_set, &output_dir)?;
+        }
+        Command::Test => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            let slides = slides::parse(&input);
+            let blocks = code::collect(&slides);
+            if code::test(&blocks)? > 0 {
+                process::exit(1);
+            }
+        }
+        Command::Check {
+            check_external,
+            timeout,
+            concurrency,
+            offline,
+            integrity,
+        } => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            let slides = slides::parse(&input);
+            let external = if check_external {
+                Some(check::ExternalOptions {
+                    timeout: Duration::from_secs(timeout),
+                    concurrency,
+                })
+            } else {
+                None
+            };
+            // Paths are relative to the current directory as the markdown is read from stdin
+            let issues = check::check(&slides, Path::new(""), external).await?;
+            for issue in &issues {
+                eprintln!("{}", issue);
+            }
+            let requests = if offline {
+                let renderer = html::Renderer::try_new(html::Options::default())?;
+                let html = renderer.render(input.clone(), None, None)?;
+                check::external_requests(&html.to_string())
+            } else {
+                Vec::new()
+            };
+            for request in &requests {
+                eprintln!("{}", request);
+            }
+            let (front_matter, _) = front_matter::parse(&input)?;
+            let contrast_issues = front_matter.theme.contrast_issues();
+            for issue in &contrast_issues {
+                eprintln!("{}", issue);
+            }
+            let integrity_issues = match integrity {
+                Some(path) => {
+                    let page = fs::read_to_string(path)?;
+                    let assets = check::linked_assets(&page);
+                    check::check_integrity(assets, Duration::from_secs(timeout)).await?
+                }
+                None => Vec::new(),
+            };
+            for issue in &integrity_issues {
+                eprintln!("{}", issue);
+            }
+            if !issues.is_empty()
+                || !requests.is_empty()
+                || !contrast_issues.is_empty()
+                || !integrity_issues.is_empty()
+            {
+                process::exit(1);
+            }
+        }
+        Command::Merge { title, inputs } => {
+            let mut decks = Vec::with_capacity(inputs.len());
+            for path in inputs {
+                let input = fs::read_to_string(&path)?;
+                decks.push((path, input));
+            }
+            print!("{}", merge::merge(&decks, title)?);
+        }
+        Command::Diff { old, new, html } => {
+            let changes = diff::diff(&fs::read_to_string(&old)?, &fs::read_to_string(&new)?);
+            print!("{}", changes);
+            if let Some(path) = html {
+                let page = changes.to_html(&old.to_string_lossy(), &new.to_string_lossy());
+                fs::write(path, page)?;
+            }
+        }
+        Command::Completions { shell } => {
+            Cli::clap().gen_completions_to("deck", shell, &mut io::stdout());
+        }
     }
     Ok(())
 }
@@ -0,0 +1,897 @@
+use crate::{html::escape_html, slides};
+use pulldown_cmark::{Alignment, CowStr, Event, LinkType, Tag};
+use std::{cmp::Reverse, ops::Range};
+
+/// Markers of the inline elements that must not contain spaces, e.g. `H~2~O` or `2^10^`
+const TIGHT_MARKS: [(char, &str); 2] = [('~', "sub"), ('^', "sup")];
+
+/// Schemes of the bare URLs turned into links
+const URL_SCHEMES: [&str; 2] = ["https://", "http://"];
+
+/// Whether the line of a paragraph defines the terms preceding it, as in `: Definition`
+fn is_definition(line: &[Event]) -> bool {
+    match line.first() {
+        Some(Event::Text(text)) => text.starts_with(": ") || text.starts_with(":\t"),
+        _ => false,
+    }
+}
+
+/// Lines of a paragraph, separated by soft or hard breaks
+fn lines(paragraph: &[Event]) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, event) in paragraph.iter().enumerate() {
+        if let Event::SoftBreak | Event::HardBreak = event {
+            lines.push(start..i);
+            start = i + 1;
+        }
+    }
+    lines.push(start..paragraph.len());
+    lines
+}
+
+/// Turn the paragraphs made of terms followed by lines starting with `: ` into definition lists,
+/// consecutive lists being merged
+///
+/// ```text
+/// Term
+/// : Definition
+/// ```
+pub fn definition_lists(events: Vec<Event>) -> Vec<Event> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut events = events.into_iter();
+    while let Some(event) = events.next() {
+        if event != Event::Start(Tag::Paragraph) {
+            output.push(event);
+            continue;
+        }
+        let mut paragraph = Vec::new();
+        for event in &mut events {
+            if event == Event::End(Tag::Paragraph) {
+                break;
+            }
+            paragraph.push(event);
+        }
+        let lines = lines(&paragraph);
+        let is_list = lines.len() > 1
+            && !is_definition(&paragraph[lines[0].clone()])
+            && lines
+                .iter()
+                .any(|line| is_definition(&paragraph[line.clone()]));
+        if !is_list {
+            output.push(Event::Start(Tag::Paragraph));
+            output.extend(paragraph);
+            output.push(Event::End(Tag::Paragraph));
+            continue;
+        }
+
+        if output.last() == Some(&Event::Html("</dl>".into())) {
+            output.pop();
+        } else {
+            output.push(Event::Html("<dl>".into()));
+        }
+        let mut paragraph = paragraph.into_iter();
+        for line in lines {
+            let mut line: Vec<Event> = paragraph.by_ref().take(line.len()).collect();
+            // Skip the line break
+            paragraph.next();
+            let tag = if is_definition(&line) {
+                if let Some(Event::Text(text)) = line.first_mut() {
+                    *text = text[2..].to_owned().into();
+                }
+                "dd"
+            } else {
+                "dt"
+            };
+            output.push(Event::Html(format!("<{}>", tag).into()));
+            output.append(&mut line);
+            output.push(Event::Html(format!("</{}>", tag).into()));
+        }
+        output.push(Event::Html("</dl>".into()));
+    }
+    output
+}
+
+/// Join the last two words of the headings with a non-breaking space so that a title never wraps
+/// with a single dangling word
+pub fn balance_headings(mut events: Vec<Event>) -> Vec<Event> {
+    // Position of the last space of each heading, as the index of its text and the byte offset
+    let mut spaces = Vec::new();
+    let mut last_space = None;
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::Heading(_)) => last_space = None,
+            // The last word may be emphasized, the space is then found in a previous text
+            Event::Text(text) => {
+                if let Some(offset) = text.rfind(' ') {
+                    last_space = Some((i, offset));
+                }
+            }
+            Event::End(Tag::Heading(_)) => spaces.extend(last_space.take()),
+            _ => {}
+        }
+    }
+    for (i, offset) in spaces {
+        if let Event::Text(ref mut text) = events[i] {
+            let mut joined = text.to_string();
+            joined.replace_range(offset..offset + 1, "\u{a0}");
+            *text = joined.into();
+        }
+    }
+    events
+}
+
+/// Strip the `Table:` prefix and the trailing classes of a caption such as
+/// `Table: Benchmarks {.compact}`
+fn parse_caption(mut caption: Vec<Event>) -> (Vec<Event>, Vec<String>) {
+    if let Some(Event::Text(text)) = caption.first_mut() {
+        *text = text["Table:".len()..].trim_start().to_owned().into();
+    }
+    let mut classes = Vec::new();
+    if let Some(Event::Text(text)) = caption.last_mut() {
+        if let Some((stripped, attributes)) = slides::parse_attributes(text) {
+            classes = attributes;
+            *text = stripped.to_owned().into();
+        }
+    }
+    caption.retain(|event| !matches!(event, Event::Text(text) if text.is_empty()));
+    (caption, classes)
+}
+
+fn table_start(classes: &[String]) -> Event<'static> {
+    if classes.is_empty() {
+        return Event::Html("<table>".into());
+    }
+    Event::Html(format!("<table class=\"{}\">", classes.join(" ")).into())
+}
+
+/// Add the captions written as paragraphs starting with `Table:` right before or after a table,
+/// along with their classes, and align the cells with inline styles taking precedence over the
+/// default table style
+pub fn tables(events: Vec<Event>) -> Vec<Event> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut events = events.into_iter().peekable();
+    // Caption waiting for the table following it
+    let mut caption = None;
+    // Position of the last table in the output and the length of the output once it ended, a
+    // caption found at that length belongs to the table
+    let mut last_table = None;
+    let mut alignments = Vec::new();
+    let mut in_head = false;
+    let mut column = 0;
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::Paragraph) => {
+                let is_caption = matches!(
+                    events.peek(),
+                    Some(Event::Text(text)) if text.starts_with("Table:")
+                );
+                if !is_caption {
+                    output.push(event);
+                    continue;
+                }
+                let mut paragraph = Vec::new();
+                for event in &mut events {
+                    if event == Event::End(Tag::Paragraph) {
+                        break;
+                    }
+                    paragraph.push(event);
+                }
+                if matches!(events.peek(), Some(Event::Start(Tag::Table(_)))) {
+                    caption = Some(paragraph);
+                    continue;
+                }
+                match last_table {
+                    Some((start, end)) if end == output.len() => {
+                        let (content, classes) = parse_caption(paragraph);
+                        output[start] = table_start(&classes);
+                        if !content.is_empty() {
+                            let mut inserted = vec![Event::Html("<caption>".into())];
+                            inserted.extend(content);
+                            inserted.push(Event::Html("</caption>".into()));
+                            output.splice(start + 1..start + 1, inserted);
+                        }
+                        last_table = None;
+                    }
+                    _ => {
+                        output.push(Event::Start(Tag::Paragraph));
+                        output.extend(paragraph);
+                        output.push(Event::End(Tag::Paragraph));
+                    }
+                }
+            }
+            Event::Start(Tag::Table(table_alignments)) => {
+                let start = output.len();
+                // A table captioned before it can't also be captioned after it
+                last_table = if caption.is_none() {
+                    Some((start, 0))
+                } else {
+                    None
+                };
+                let (content, classes) = caption.take().map(parse_caption).unwrap_or_default();
+                output.push(table_start(&classes));
+                if !content.is_empty() {
+                    output.push(Event::Html("<caption>".into()));
+                    output.extend(content);
+                    output.push(Event::Html("</caption>".into()));
+                }
+                alignments = table_alignments;
+            }
+            Event::Start(Tag::TableHead) => {
+                in_head = true;
+                column = 0;
+                output.push(event);
+            }
+            Event::End(Tag::TableHead) => {
+                in_head = false;
+                output.push(event);
+            }
+            Event::Start(Tag::TableRow) => {
+                column = 0;
+                output.push(event);
+            }
+            Event::Start(Tag::TableCell) => {
+                let name = if in_head { "th" } else { "td" };
+                let align = match alignments.get(column) {
+                    Some(Alignment::Left) => " style=\"text-align: left\"",
+                    Some(Alignment::Center) => " style=\"text-align: center\"",
+                    Some(Alignment::Right) => " style=\"text-align: right\"",
+                    _ => "",
+                };
+                output.push(Event::Html(format!("<{}{}>", name, align).into()));
+            }
+            Event::End(Tag::TableCell) => {
+                output.push(Event::Html(if in_head { "</th>" } else { "</td>" }.into()));
+                column += 1;
+            }
+            Event::End(Tag::Table(_)) => {
+                output.push(event);
+                if let Some((start, _)) = last_table {
+                    last_table = Some((start, output.len()));
+                }
+            }
+            event => output.push(event),
+        }
+    }
+    output
+}
+
+/// Markers introducing the author at the end of a quote, e.g. `> -- Ada Lovelace`
+const ATTRIBUTION_MARKERS: [&str; 2] = ["-- ", "\u{2014} "];
+
+/// Turn the last line of a quote starting with `-- ` into its attribution
+pub fn quote_attributions(mut events: Vec<Event>) -> Vec<Event> {
+    let ends: Vec<usize> = events
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| **event == Event::End(Tag::BlockQuote))
+        .map(|(i, _)| i)
+        .collect();
+    for end in ends {
+        if end < 2 || events[end - 1] != Event::End(Tag::Paragraph) {
+            continue;
+        }
+        let paragraph = match events[..end - 1]
+            .iter()
+            .rposition(|event| *event == Event::Start(Tag::Paragraph))
+        {
+            Some(paragraph) => paragraph,
+            None => continue,
+        };
+        let line = events[paragraph + 1..end - 1]
+            .iter()
+            .rposition(|event| matches!(event, Event::SoftBreak | Event::HardBreak))
+            .map_or(paragraph + 1, |i| paragraph + i + 2);
+        let marker = match events.get(line) {
+            Some(Event::Text(text)) => ATTRIBUTION_MARKERS
+                .iter()
+                .find(|marker| text.starts_with(*marker)),
+            _ => None,
+        };
+        let is_alone = line == paragraph + 1
+            && paragraph > 0
+            && events[paragraph - 1] == Event::Start(Tag::BlockQuote);
+        let marker = match marker {
+            Some(marker) if !is_alone => marker,
+            _ => continue,
+        };
+        if let Event::Text(ref mut text) = events[line] {
+            *text = text[marker.len()..].to_owned().into();
+        }
+        let start = "<p class=\"attribution\">\u{2014}\u{a0}";
+        if line == paragraph + 1 {
+            events[paragraph] = Event::Html(start.into());
+        } else {
+            events[line - 1] = Event::Html(format!("</p>\n{}", start).into());
+        }
+    }
+    events
+}
+
+/// Render the items of task lists with styled markers instead of disabled checkboxes. Classes
+/// can be set on an item by ending it with attributes, `{.fragment}` revealing it step by step.
+pub fn task_lists(mut events: Vec<Event>) -> Vec<Event> {
+    // Position of the start of each task item and of the last text of its own line
+    let mut items = Vec::new();
+    for (i, event) in events.iter().enumerate() {
+        if *event != Event::Start(Tag::Item) {
+            continue;
+        }
+        let is_task = match events.get(i + 1) {
+            Some(Event::TaskListMarker(_)) => true,
+            // Items of loose lists are made of paragraphs
+            Some(Event::Start(Tag::Paragraph)) => {
+                matches!(events.get(i + 2), Some(Event::TaskListMarker(_)))
+            }
+            _ => false,
+        };
+        if !is_task {
+            continue;
+        }
+        let end = events[i..].iter().position(|event| {
+            matches!(
+                event,
+                Event::End(Tag::Item) | Event::End(Tag::Paragraph) | Event::Start(Tag::List(_))
+            )
+        });
+        let last_text = end
+            .map(|end| i + end - 1)
+            .filter(|&j| matches!(events[j], Event::Text(_)));
+        items.push((i, last_text));
+    }
+
+    for (start, last_text) in items {
+        let mut classes = vec![String::from("task-item")];
+        if let Some(Event::Text(text)) = last_text.map(|j| &mut events[j]) {
+            if let Some((stripped, attributes)) = slides::parse_attributes(text) {
+                classes.extend(attributes);
+                *text = stripped.to_owned().into();
+            }
+        }
+        events[start] = Event::Html(format!("<li class=\"{}\">", classes.join(" ")).into());
+    }
+    for event in events.iter_mut() {
+        if let Event::TaskListMarker(checked) = *event {
+            *event = Event::Html(
+                format!(
+                    "<span class=\"task{}\" role=\"checkbox\" aria-checked=\"{}\"></span>",
+                    if checked { " checked" } else { "" },
+                    checked
+                )
+                .into(),
+            );
+        }
+    }
+    events
+}
+
+/// Parse an abbreviation definition such as `*[HTML]: HyperText Markup Language`
+fn parse_abbreviation(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_start().strip_prefix("*[")?;
+    let end = rest.find("]:")?;
+    let term = rest[..end].trim();
+    let title = rest[end + 2..].trim();
+    if term.is_empty() || title.is_empty() {
+        return None;
+    }
+    Some((term, title))
+}
+
+/// Remove the abbreviation definitions, found outside of code blocks, from the deck. They are
+/// returned along with the rest of the deck, longest first so that the longest term matches,
+/// and the lines they were defined on.
+pub fn abbreviations(input: &str) -> (String, Vec<(String, String)>, Vec<usize>) {
+    let mut output = String::with_capacity(input.len());
+    let mut abbreviations: Vec<(String, String)> = Vec::new();
+    let mut removed = Vec::new();
+    let mut fence = None;
+    for (number, line) in input.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"]
+            .iter()
+            .find(|marker| trimmed.starts_with(*marker))
+            .copied();
+        match (fence, marker) {
+            (None, Some(marker)) => fence = Some(marker),
+            (Some(open), Some(marker)) if open == marker => fence = None,
+            (None, None) => {
+                if let Some((term, title)) = parse_abbreviation(line) {
+                    // The first definition of a term wins
+                    if !abbreviations.iter().any(|(defined, _)| defined == term) {
+                        abbreviations.push((term.to_owned(), title.to_owned()));
+                    }
+                    removed.push(number + 1);
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        output.push_str(line);
+    }
+    abbreviations.sort_by_key(|(term, _)| Reverse(term.len()));
+    (output, abbreviations, removed)
+}
+
+/// Line of the deck before the lines at the given positions, in ascending order, were removed
+/// from it
+pub fn original_line(mut line: usize, removed: &[usize]) -> usize {
+    for &removed in removed {
+        if removed > line {
+            break;
+        }
+        line += 1;
+    }
+    line
+}
+
+/// Markdown of a slide defining the abbreviations in alphabetical order
+pub fn glossary(abbreviations: &[(String, String)]) -> String {
+    let mut abbreviations: Vec<&(String, String)> = abbreviations.iter().collect();
+    abbreviations.sort_by_key(|(term, _)| term.to_lowercase());
+    let mut markdown = String::from("\n\n---\n\n## Glossary\n\n");
+    for (term, title) in abbreviations {
+        markdown.push_str(&format!("{}\n: {}\n\n", term, title));
+    }
+    markdown
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Wrap the whole-word occurrences of the abbreviations within a text in `<abbr>` elements
+fn expand_text<'a>(text: &str, abbreviations: &[(String, String)], output: &mut Vec<Event<'a>>) {
+    let mut start = 0;
+    let mut i = 0;
+    let mut previous = None;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+        let found = abbreviations.iter().find(|(term, _)| {
+            rest.starts_with(term.as_str())
+                && !rest[term.len()..]
+                    .chars()
+                    .next()
+                    .map_or(false, is_word_char)
+        });
+        match found {
+            Some((term, title)) if !previous.map_or(false, is_word_char) => {
+                if start < i {
+                    output.push(Event::Text(text[start..i].to_owned().into()));
+                }
+                output.push(Event::Html(
+                    format!(
+                        "<abbr title=\"{}\">{}</abbr>",
+                        escape_html(title),
+                        escape_html(term)
+                    )
+                    .into(),
+                ));
+                i += term.len();
+                start = i;
+                previous = term.chars().next_back();
+            }
+            _ => {
+                i += c.len_utf8();
+                previous = Some(c);
+            }
+        }
+    }
+    if start < text.len() {
+        output.push(Event::Text(text[start..].to_owned().into()));
+    }
+}
+
+/// Expand the abbreviations defined in the deck outside of code and of the alternative text of
+/// images
+pub fn expand_abbreviations<'a>(
+    events: Vec<Event<'a>>,
+    abbreviations: &[(String, String)],
+) -> Vec<Event<'a>> {
+    if abbreviations.is_empty() {
+        return events;
+    }
+    let mut output = Vec::with_capacity(events.len());
+    let mut in_code_block = false;
+    let mut in_image = 0;
+    for event in events {
+        match event {
+            Event::Text(ref text) if !in_code_block && in_image == 0 => {
+                expand_text(text, abbreviations, &mut output);
+                continue;
+            }
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+            Event::Start(Tag::Image(..)) => in_image += 1,
+            Event::End(Tag::Image(..)) => in_image -= 1,
+            _ => {}
+        }
+        output.push(event);
+    }
+    output
+}
+
+/// Convert `~sub~` and `^sup^` within a text, the markers must enclose a word without spaces
+fn tight_marks(text: &str) -> Vec<Event<'static>> {
+    let mut events = Vec::new();
+    let mut rest = text;
+    // Length of the beginning of the rest already known to be plain text
+    let mut plain = 0;
+    while let Some(i) = rest[plain..].find(|c: char| TIGHT_MARKS.iter().any(|(m, _)| *m == c)) {
+        let start = plain + i;
+        let (marker, tag) = TIGHT_MARKS
+            .iter()
+            .find(|(m, _)| rest[start..].starts_with(*m))
+            .copied()
+            .unwrap_or_default();
+        let after = &rest[start + 1..];
+        let len = match after.find(marker) {
+            Some(len) if len > 0 && !after[..len].contains(char::is_whitespace) => len,
+            // Doubled markers, such as `~~`, are kept as is
+            Some(0) => {
+                plain = start + 2;
+                continue;
+            }
+            _ => {
+                plain = start + 1;
+                continue;
+            }
+        };
+        if start > 0 {
+            events.push(Event::Text(rest[..start].to_owned().into()));
+        }
+        events.push(Event::Html(format!("<{}>", tag).into()));
+        events.push(Event::Text(after[..len].to_owned().into()));
+        events.push(Event::Html(format!("</{}>", tag).into()));
+        rest = &after[len + 1..];
+        plain = 0;
+    }
+    if !rest.is_empty() {
+        events.push(Event::Text(rest.to_owned().into()));
+    }
+    events
+}
+
+/// Convert the `==highlighted==` text, which may span several inline elements, and the
+/// `~sub~` and `^sup^` scripts of a run of inline events
+fn convert_marks<'a>(run: Vec<Event<'a>>, output: &mut Vec<Event<'a>>) {
+    // Pair the `==` that can open a highlight, being followed by a non-space, with the next one
+    // that can close it, being preceded by a non-space
+    let mut pairs = Vec::new();
+    let mut open = None;
+    let mut in_image = 0;
+    for (i, event) in run.iter().enumerate() {
+        let text = match event {
+            Event::Start(Tag::Image(..)) => {
+                in_image += 1;
+                continue;
+            }
+            Event::End(Tag::Image(..)) => {
+                in_image -= 1;
+                continue;
+            }
+            Event::Text(text) if in_image == 0 => text,
+            _ => continue,
+        };
+        for (position, _) in text.match_indices("==") {
+            let before = text[..position].chars().next_back();
+            let after = text[position + 2..].chars().next();
+            match open {
+                None if after.map_or(true, |c| !c.is_whitespace() && c != '=') => {
+                    open = Some((i, position))
+                }
+                Some(start) if before.map_or(true, |c| !c.is_whitespace()) => {
+                    pairs.push(start);
+                    pairs.push((i, position));
+                    open = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut in_image = 0;
+    let mut is_open = false;
+    for (i, event) in run.into_iter().enumerate() {
+        let text = match event {
+            Event::Text(text) if in_image == 0 => text,
+            event => {
+                match event {
+                    Event::Start(Tag::Image(..)) => in_image += 1,
+                    Event::End(Tag::Image(..)) => in_image -= 1,
+                    _ => {}
+                }
+                output.push(event);
+                continue;
+            }
+        };
+        let mut start = 0;
+        for &(_, position) in pairs.iter().filter(|(index, _)| *index == i) {
+            output.extend(tight_marks(&text[start..position]));
+            output.push(Event::Html(
+                if is_open { "</mark>" } else { "<mark>" }.into(),
+            ));
+            is_open = !is_open;
+            start = position + 2;
+        }
+        output.extend(tight_marks(&text[start..]));
+    }
+}
+
+/// Convert the highlights, subscripts and superscripts outside of code
+pub fn inline_marks(events: Vec<Event>) -> Vec<Event> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut run = Vec::new();
+    let mut in_code_block = false;
+    for event in events {
+        let is_inline = match event {
+            Event::Text(_) | Event::Code(_) | Event::SoftBreak | Event::HardBreak => true,
+            Event::Start(ref tag) | Event::End(ref tag) => matches!(
+                tag,
+                Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..)
+            ),
+            _ => false,
+        };
+        if is_inline && !in_code_block {
+            run.push(event);
+            continue;
+        }
+        convert_marks(std::mem::take(&mut run), &mut output);
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+            _ => {}
+        }
+        output.push(event);
+    }
+    convert_marks(run, &mut output);
+    output
+}
+
+/// Length of the URL the text starts with, without the punctuation ending the sentence
+fn url_len(text: &str) -> usize {
+    let end = text
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"'))
+        .unwrap_or_else(|| text.len());
+    let mut url = &text[..end];
+    loop {
+        let trimmed =
+            url.trim_end_matches(|c| matches!(c, '.' | ',' | ';' | ':' | '!' | '?' | '\''));
+        // A closing parenthesis is only part of the URL when it opened one, e.g. `Rust_(language)`
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(rest) if rest.matches('(').count() <= rest.matches(')').count() => rest,
+            _ => trimmed,
+        };
+        if trimmed.len() == url.len() {
+            return url.len();
+        }
+        url = trimmed;
+    }
+}
+
+/// Split a text on the bare URLs it contains, each one becoming a link
+fn link_urls<'a>(text: &str, output: &mut Vec<Event<'a>>) {
+    let mut rest = text;
+    // Length of the beginning of the rest already known to be plain text
+    let mut plain = 0;
+    while let Some((start, scheme)) = URL_SCHEMES
+        .iter()
+        .filter_map(|scheme| rest[plain..].find(scheme).map(|i| (plain + i, scheme)))
+        .min()
+    {
+        let len = url_len(&rest[start..]);
+        // Skip the schemes without a host and the ones ending a word, e.g. `xhttp://`
+        if len <= scheme.len() || rest[..start].ends_with(char::is_alphanumeric) {
+            plain = start + scheme.len();
+            continue;
+        }
+        if start > 0 {
+            output.push(Event::Text(rest[..start].to_owned().into()));
+        }
+        let url: CowStr = rest[start..start + len].to_owned().into();
+        output.push(Event::Start(Tag::Link(
+            LinkType::Autolink,
+            url.clone(),
+            "".into(),
+        )));
+        output.push(Event::Text(url.clone()));
+        output.push(Event::End(Tag::Link(LinkType::Autolink, url, "".into())));
+        rest = &rest[start + len..];
+        plain = 0;
+    }
+    if !rest.is_empty() {
+        output.push(Event::Text(rest.to_owned().into()));
+    }
+}
+
+/// Turn the bare URLs written in the text, outside of code and links, into links so that they
+/// can be followed from the page and stay clickable in the PDFs printed from it
+pub fn autolinks(events: Vec<Event>) -> Vec<Event> {
+    let mut output = Vec::with_capacity(events.len());
+    // The parser may split a text, around underscores for instance, so consecutive ones are
+    // joined before looking for URLs
+    let mut text = String::new();
+    let mut in_code_block = false;
+    let mut in_link = 0;
+    for event in events {
+        match event {
+            Event::Text(ref t) if !in_code_block && in_link == 0 => {
+                text.push_str(t);
+                continue;
+            }
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+            Event::Start(Tag::Link(..)) | Event::Start(Tag::Image(..)) => in_link += 1,
+            Event::End(Tag::Link(..)) | Event::End(Tag::Image(..)) => in_link -= 1,
+            // Links written as HTML
+            Event::Html(ref html) if html.starts_with("<a ") => in_link += 1,
+            Event::Html(ref html) if html.starts_with("</a>") && in_link > 0 => in_link -= 1,
+            _ => {}
+        }
+        link_urls(&std::mem::take(&mut text), &mut output);
+        output.push(event);
+    }
+    link_urls(&text, &mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulldown_cmark::{html, Parser};
+
+    fn render(input: &str) -> String {
+        let events = inline_marks(definition_lists(Parser::new(input).collect()));
+        let mut output = String::new();
+        html::push_html(&mut output, events.into_iter());
+        output
+    }
+
+    #[test]
+    fn test_autolinks() {
+        let render = |input| {
+            let mut output = String::new();
+            html::push_html(&mut output, autolinks(Parser::new(input).collect()));
+            output
+        };
+        assert_eq!(
+            "<p>Slides at <a href=\"https://example.com/a_b_c\">https://example.com/a_b_c</a>. \
+             See <a href=\"https://en.wikipedia.org/wiki/Rust_(language)\">\
+             https://en.wikipedia.org/wiki/Rust_(language)</a>)</p>\n",
+            render("Slides at https://example.com/a_b_c. See https://en.wikipedia.org/wiki/Rust_(language))")
+        );
+        assert_eq!(
+            "<p><a href=\"https://deck.rs\">site</a> <code>https://x.org</code> xhttps://y.org http://</p>\n",
+            render("[site](https://deck.rs) `https://x.org` xhttps://y.org http://")
+        );
+    }
+
+    #[test]
+    fn test_definition_lists() {
+        assert_eq!(
+            "<dl><dt>Rust</dt><dd>A language</dd><dd>A <em>game</em></dd>\
+             <dt>Go</dt><dd>Another</dd></dl>",
+            render("Rust\n: A language\n: A *game*\n\nGo\n: Another\n")
+        );
+        assert_eq!("<p>Ratio\n1: 2</p>\n", render("Ratio\n1: 2\n"));
+    }
+
+    #[test]
+    fn test_balance_headings() {
+        let render = |input| {
+            let mut output = String::new();
+            html::push_html(&mut output, balance_headings(Parser::new(input).collect()));
+            output
+        };
+        assert_eq!(
+            "<h1>Writing a\u{a0}parser</h1>\n<p>Some text</p>\n",
+            render("# Writing a parser\nSome text")
+        );
+        assert_eq!(
+            "<h2>Why\u{a0}<em>Rust</em></h2>\n<h2>Deck</h2>\n",
+            render("## Why *Rust*\n## Deck")
+        );
+    }
+
+    #[test]
+    fn test_tables() {
+        let render = |input| {
+            let mut opts = pulldown_cmark::Options::empty();
+            opts.insert(pulldown_cmark::Options::ENABLE_TABLES);
+            let mut output = String::new();
+            html::push_html(&mut output, tables(Parser::new_ext(input, opts).collect()));
+            output
+        };
+        assert_eq!(
+            "<table class=\"compact\"><caption>Results of <em>v2</em></caption>\
+             <thead><tr><th>Name</th><th style=\"text-align: right\">Time</th></tr></thead>\
+             <tbody>\n<tr><td>a</td><td style=\"text-align: right\">1</td></tr>\n</tbody></table>\n",
+            render("| Name | Time |\n|---|--:|\n| a | 1 |\n\nTable: Results of *v2* {.compact}\n")
+        );
+        assert_eq!(
+            "<table><caption>Before</caption><thead><tr><th>A</th></tr></thead><tbody>\n\
+             </tbody></table>\n<p>Table: none</p>\n",
+            render("Table: Before\n\n| A |\n|---|\n\nTable: none\n")
+        );
+    }
+
+    #[test]
+    fn test_quote_attributions() {
+        let render = |input| {
+            let mut output = String::new();
+            html::push_html(
+                &mut output,
+                quote_attributions(Parser::new(input).collect()),
+            );
+            output
+        };
+        assert_eq!(
+            "<blockquote>\n<p>Simplicity is prerequisite for reliability.</p>\n\
+             <p class=\"attribution\">\u{2014}\u{a0}Edsger W. Dijkstra</p>\n</blockquote>\n",
+            render("> Simplicity is prerequisite for reliability.\n> -- Edsger W. Dijkstra\n")
+        );
+        assert_eq!(
+            "<blockquote>\n<p>Quote</p>\n<p class=\"attribution\">\u{2014}\u{a0}Author</p>\n</blockquote>\n",
+            render("> Quote\n>\n> \u{2014} Author\n")
+        );
+        assert_eq!(
+            "<blockquote>\n<p>-- Not an author</p>\n</blockquote>\n",
+            render("> -- Not an author\n")
+        );
+    }
+
+    #[test]
+    fn test_task_lists() {
+        let mut opts = pulldown_cmark::Options::empty();
+        opts.insert(pulldown_cmark::Options::ENABLE_TASKLISTS);
+        let input = "- [x] Write the slides\n- [ ] Rehearse {.fragment}\n  - Twice\n- Relax\n";
+        let mut output = String::new();
+        html::push_html(
+            &mut output,
+            task_lists(Parser::new_ext(input, opts).collect()).into_iter(),
+        );
+        assert_eq!(
+            "<ul>\n<li class=\"task-item\">\
+             <span class=\"task checked\" role=\"checkbox\" aria-checked=\"true\"></span>\
+             Write the slides</li>\n\
+             <li class=\"task-item fragment\">\
+             <span class=\"task\" role=\"checkbox\" aria-checked=\"false\"></span>Rehearse\n\
+             <ul>\n<li>Twice</li>\n</ul>\n</li>\n<li>Relax</li>\n</ul>\n",
+            output
+        );
+    }
+
+    #[test]
+    fn test_abbreviations() {
+        let input = "*[HTML]: HyperText Markup Language\n*[HTML5]: The fifth \"HTML\"\n\
+                     HTML5 is not XHTML, nor HTML\n![HTML](logo.png)\n\
+                     ```\n*[CSS]: Cascading Style Sheets\n```\n";
+        let (input, abbreviations, removed) = abbreviations(input);
+        assert_eq!(2, abbreviations.len());
+        assert_eq!(vec![1, 2], removed);
+        assert_eq!(4, original_line(2, &removed));
+        assert!(input.starts_with("HTML5 is"));
+        let events = expand_abbreviations(Parser::new(&input).collect(), &abbreviations);
+        let mut output = String::new();
+        html::push_html(&mut output, events.into_iter());
+        assert_eq!(
+            "<p><abbr title=\"The fifth &quot;HTML&quot;\">HTML5</abbr> is not XHTML, nor \
+             <abbr title=\"HyperText Markup Language\">HTML</abbr>\n\
+             <img src=\"logo.png\" alt=\"HTML\" /></p>\n\
+             <pre><code>*[CSS]: Cascading Style Sheets\n</code></pre>\n",
+            output
+        );
+    }
+
+    #[test]
+    fn test_inline_marks() {
+        assert_eq!(
+            "<p>H<sub>2</sub>O, 2<sup>10</sup>, <mark>very <strong>bold</strong></mark> \
+             and a == b ~~ c</p>\n",
+            render("H~2~O, 2^10^, ==very **bold**== and a == b ~~ c\n")
+        );
+        assert_eq!(
+            "<pre><code>H~2~O\n</code></pre>\n",
+            render("```\nH~2~O\n```\n")
+        );
+    }
+}
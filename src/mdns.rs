@@ -0,0 +1,31 @@
+use crate::error::Error;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use std::net::IpAddr;
+
+const SERVICE_TYPE: &str = "_deck._tcp.local.";
+
+/// Advertise the presentation served at the given addresses on the local network. The
+/// advertisement lasts as long as the returned daemon is alive.
+pub fn advertise(title: &str, hosts: &[IpAddr], port: u16) -> Result<ServiceDaemon, Error> {
+    let daemon = ServiceDaemon::new()?;
+    let host_name = format!("{}.local.", hostname::get()?.to_string_lossy());
+    let properties = [("title", title), ("path", "/slides")];
+    let service = if hosts.iter().any(|host| host.is_unspecified()) {
+        ServiceInfo::new(SERVICE_TYPE, title, &host_name, (), port, &properties[..])?
+            .enable_addr_auto()
+    } else {
+        // Several addresses are given as a comma separated list
+        let hosts: Vec<String> = hosts.iter().map(|host| host.to_string()).collect();
+        let hosts = hosts.join(",");
+        ServiceInfo::new(
+            SERVICE_TYPE,
+            title,
+            &host_name,
+            &*hosts,
+            port,
+            &properties[..],
+        )?
+    };
+    daemon.register(service)?;
+    Ok(daemon)
+}
@@ -0,0 +1,149 @@
+use crate::{assets, error::Error, html::escape_html, slides::Slide};
+use pulldown_cmark::{Event, Tag};
+use std::{collections::HashMap, fs, path::Path};
+
+/// Flags of a media that are copied to the generated element, e.g. `{autoplay muted loop}`
+const FLAGS: [&str; 5] = ["autoplay", "muted", "loop", "controls", "playsinline"];
+
+/// Kind of media embedded with the image syntax, the alternative text being either `video`,
+/// `audio` or `cast` as in `![video](demo.mp4)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Video,
+    Audio,
+    /// Terminal session recorded with asciinema, replayed by the bundled player
+    Cast,
+}
+
+impl Kind {
+    pub fn from_alt(alt: &str) -> Option<Kind> {
+        match alt.trim() {
+            "video" => Some(Kind::Video),
+            "audio" => Some(Kind::Audio),
+            "cast" => Some(Kind::Cast),
+            _ => None,
+        }
+    }
+
+    /// HTML element playing the media, the flags that aren't supported are ignored. Autoplay is
+    /// left to the script so that the media only plays while its slide is shown.
+    pub fn to_html(self, url: &str, flags: &[&str]) -> String {
+        let tag = match self {
+            Kind::Video => "video",
+            Kind::Audio => "audio",
+            Kind::Cast => {
+                // Recordings are loaded by the player from the data-src attribute
+                let mut html = format!("<span class=\"cast\" data-src=\"{}\"", escape_html(url));
+                for flag in flags {
+                    if *flag == "autoplay" || *flag == "loop" {
+                        html.push_str(&format!(" data-{}", flag));
+                    }
+                }
+                html.push_str("><code class=\"cast-screen\"></code></span>");
+                return html;
+            }
+        };
+        let mut html = format!("<{} src=\"{}\" controls", tag, escape_html(url));
+        for flag in flags {
+            if *flag == "autoplay" {
+                html.push_str(" data-autoplay");
+            } else if FLAGS.contains(flag) && *flag != "controls" {
+                html.push(' ');
+                html.push_str(flag);
+            }
+        }
+        html.push_str(&format!("></{}>", tag));
+        html
+    }
+}
+
+/// Splits the text following a media into its flags, written as `{autoplay muted}`, and the rest
+/// of the text
+pub fn parse_flags(text: &str) -> Option<(Vec<&str>, &str)> {
+    let rest = text.strip_prefix('{')?;
+    let end = rest.find('}')?;
+    Some((rest[..end].split_whitespace().collect(), &rest[end + 1..]))
+}
+
+fn mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let mime_type = match extension.as_str() {
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "mov" => "video/quicktime",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "ogg" | "oga" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "cast" => "application/x-asciicast",
+        _ => return None,
+    };
+    Some(mime_type)
+}
+
+/// Embed the local media files no larger than the given size, in bytes, as data URLs and return
+/// the URLs that must be rewritten. Recordings are always embedded so that the player can load
+/// them from a file opened without a server.
+pub fn inline(
+    slides: &[Slide],
+    base_dir: &Path,
+    max_size: Option<u64>,
+) -> Result<HashMap<String, String>, Error> {
+    let mut urls = HashMap::new();
+    for slide in slides {
+        let mut events = slide.events.iter().peekable();
+        while let Some(event) = events.next() {
+            let url = match event {
+                Event::Start(Tag::Image(_, url, _)) => url,
+                _ => continue,
+            };
+            let kind = match events.peek() {
+                Some(Event::Text(alt)) => Kind::from_alt(alt),
+                _ => None,
+            };
+            let max_size = match (kind, max_size) {
+                (Some(Kind::Cast), _) => u64::MAX,
+                (Some(_), Some(max_size)) => max_size,
+                _ => continue,
+            };
+            if !assets::is_local(url) || urls.contains_key(&**url) {
+                continue;
+            }
+            let path = base_dir.join(&**url);
+            let mime_type = match mime_type(&path) {
+                Some(mime_type) => mime_type,
+                None => continue,
+            };
+            if fs::metadata(&path)?.len() > max_size {
+                continue;
+            }
+            let data = base64::encode(fs::read(&path)?);
+            urls.insert(
+                url.to_string(),
+                format!("data:{};base64,{}", mime_type, data),
+            );
+        }
+    }
+    Ok(urls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_html() {
+        assert_eq!(
+            "<video src=\"demo.mp4\" controls data-autoplay muted></video>",
+            Kind::Video.to_html("demo.mp4", &["autoplay", "muted", "onload=x"])
+        );
+        assert_eq!(
+            "<span class=\"cast\" data-src=\"demo.cast\" data-loop>\
+             <code class=\"cast-screen\"></code></span>",
+            Kind::Cast.to_html("demo.cast", &["loop", "muted"])
+        );
+        assert_eq!(Some((vec!["loop"], " rest")), parse_flags("{loop} rest"));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{error::Error, front_matter, slides};
+use serde_yaml::{Mapping, Value};
+use std::path::PathBuf;
+
+/// Concatenate several decks into a single one. Each deck is introduced by a section slide
+/// holding its title and the front matters are merged, the first deck defining a key wins.
+pub fn merge(decks: &[(PathBuf, String)], title: Option<String>) -> Result<String, Error> {
+    let mut merged = Mapping::new();
+    if let Some(title) = title {
+        merged.insert(Value::from("title"), Value::from(title));
+    }
+
+    let mut parts = Vec::with_capacity(decks.len() * 2);
+    for (path, input) in decks {
+        let (front_matter, body) = front_matter::split(input);
+        let mut deck_title = None;
+        if let Some(mapping) = front_matter {
+            deck_title = mapping
+                .get(&Value::from("title"))
+                .and_then(Value::as_str)
+                .map(String::from);
+            for (key, value) in mapping {
+                if !merged.contains_key(&key) {
+                    merged.insert(key, value);
+                }
+            }
+        }
+        let deck_title = deck_title
+            .or_else(|| slides::parse(body).into_iter().find_map(|s| s.title))
+            .unwrap_or_else(|| {
+                path.file_stem()
+                    .unwrap_or_else(|| path.as_os_str())
+                    .to_string_lossy()
+                    .into_owned()
+            });
+        parts.push(format!("# {}", deck_title));
+        parts.push(body.trim().to_owned());
+    }
+
+    let mut output = String::new();
+    if !merged.is_empty() {
+        let yaml = serde_yaml::to_string(&merged)?;
+        let yaml = yaml.trim_start_matches("---\n").trim_end();
+        output.push_str(&format!("---\n{}\n---\n", yaml));
+    }
+    // Blank lines around separators ensure they can't be mistaken for setext headings
+    output.push_str(&parts.join("\n\n---\n\n"));
+    output.push('\n');
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let decks = vec![
+            (
+                PathBuf::from("intro.md"),
+                "---\ntitle: Intro\nauthor: Me\n---\n# Hello\n".to_owned(),
+            ),
+            (
+                PathBuf::from("ownership.md"),
+                "---\nauthor: Someone else\n---\nBorrowing\n".to_owned(),
+            ),
+        ];
+        let output = merge(&decks, Some("Workshop".to_owned())).expect("Failed to merge");
+        assert_eq!(
+            "---\ntitle: Workshop\nauthor: Me\n---\n# Intro\n\n---\n\n# Hello\n\n---\n\n# ownership\n\n---\n\nBorrowing\n",
+            output
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use crate::slides::Slide;
+use std::{fmt::Write, str::FromStr};
+
+/// Format in which the speaker notes are extracted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Markdown,
+    Text,
+}
+
+impl Format {
+    pub fn variants() -> [&'static str; 2] {
+        ["markdown", "text"]
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "markdown" => Ok(Format::Markdown),
+            "text" => Ok(Format::Text),
+            _ => Err(format!("Unknown notes format: {}", s)),
+        }
+    }
+}
+
+/// Write the speaker notes of every slide having some, grouped under the title of the slide
+pub fn extract(slides: &[Slide], format: Format) -> String {
+    let mut output = String::new();
+    for slide in slides {
+        let notes = match slide.notes {
+            Some(ref notes) => notes,
+            None => continue,
+        };
+        let heading = match slide.title {
+            Some(ref title) => format!("{}. {}", slide.index + 1, title),
+            None => format!("{}. Slide {}", slide.index + 1, slide.index + 1),
+        };
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        // Writing to a string can't fail
+        match format {
+            Format::Markdown => {
+                writeln!(output, "## {}\n\n{}", heading, notes).unwrap();
+            }
+            Format::Text => {
+                let underline = "-".repeat(heading.chars().count());
+                writeln!(output, "{}\n{}\n{}", heading, underline, notes).unwrap();
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slides;
+
+    #[test]
+    fn test_extract() {
+        let input = r#"
+# Intro
+
+<!-- notes: Say hello -->
+
+---
+
+No notes here
+
+---
+
+<!-- notes: Wrap up -->
+"#;
+        let slides = slides::parse(input);
+        assert_eq!(
+            "## 1. Intro\n\nSay hello\n\n## 3. Slide 3\n\nWrap up\n",
+            extract(&slides, Format::Markdown)
+        );
+        assert_eq!(
+            "1. Intro\n--------\nSay hello\n\n3. Slide 3\n----------\nWrap up\n",
+            extract(&slides, Format::Text)
+        );
+    }
+}
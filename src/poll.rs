@@ -0,0 +1,114 @@
+use crate::html::escape_html;
+use serde::Serialize;
+use std::ops::Range;
+
+/// Poll defined by a block whose list items are the options
+///
+/// ```text
+/// :::poll Which editor do you use?
+/// - Vim
+/// - Emacs
+/// :::
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Poll {
+    /// Identifier used to collect the votes, unique within a deck
+    pub id: String,
+    pub question: Option<String>,
+    pub options: Vec<String>,
+}
+
+impl Poll {
+    pub fn to_html(&self) -> String {
+        let mut html = format!("<div class=\"poll\" data-poll=\"{}\">\n", self.id);
+        if let Some(ref question) = self.question {
+            html.push_str(&format!(
+                "<p class=\"poll-question\">{}</p>\n",
+                escape_html(question)
+            ));
+        }
+        html.push_str("<ul class=\"poll-options\">\n");
+        for (i, option) in self.options.iter().enumerate() {
+            html.push_str(&format!(
+                "<li data-option=\"{}\"><span class=\"poll-label\">{}</span>\
+                 <span class=\"poll-bar\"><span></span></span>\
+                 <span class=\"poll-count\">0</span></li>\n",
+                i,
+                escape_html(option)
+            ));
+        }
+        html.push_str("</ul>\n</div>\n");
+        html
+    }
+}
+
+/// Find the poll blocks of the markdown input along with the byte range they span, code blocks
+/// are skipped and unterminated polls are left as regular markdown. The identifiers of the polls
+/// are left empty.
+pub fn find(input: &str) -> Vec<(Range<usize>, Poll)> {
+    let mut polls = Vec::new();
+    let mut current: Option<(usize, Poll)> = None;
+    let mut fence: Option<&str> = None;
+    let mut offset = 0;
+    for line in input.split('\n') {
+        let start = offset;
+        offset = (offset + line.len() + 1).min(input.len());
+        let trimmed = line.trim();
+        if let Some((poll_start, ref mut poll)) = current {
+            if trimmed == ":::" {
+                polls.push((poll_start..offset, poll.clone()));
+                current = None;
+            } else if let Some(option) = trimmed
+                .strip_prefix("- ")
+                .or_else(|| trimmed.strip_prefix("* "))
+            {
+                poll.options.push(option.trim().to_owned());
+            }
+            continue;
+        }
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") {
+            fence = Some("```");
+        } else if trimmed.starts_with("~~~") {
+            fence = Some("~~~");
+        } else if let Some(rest) = trimmed.strip_prefix(":::poll") {
+            if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+                continue;
+            }
+            let question = rest.trim();
+            let poll = Poll {
+                id: String::new(),
+                question: if question.is_empty() {
+                    None
+                } else {
+                    Some(question.to_owned())
+                },
+                options: Vec::new(),
+            };
+            current = Some((start, poll));
+        }
+    }
+    polls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let input =
+            ":::poll Favorite?\n- Vim\n- Emacs\n:::\n\n```\n:::poll\n:::\n```\n:::pollster\n";
+        let polls = find(input);
+        assert_eq!(1, polls.len());
+        let (range, poll) = &polls[0];
+        assert_eq!(0..36, *range);
+        assert_eq!(Some("Favorite?"), poll.question.as_deref());
+        assert_eq!(vec!["Vim", "Emacs"], poll.options);
+    }
+}
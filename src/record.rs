@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::mpsc;
+
+/// Time spent on a slide, in seconds since the beginning of the session
+#[derive(Clone, Serialize, Deserialize)]
+struct Visit {
+    index: usize,
+    /// Id of the slide, which unlike its index is kept when slides are inserted or reordered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    start: f64,
+    duration: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Session<'a> {
+    /// Unix timestamp of the beginning of the session
+    started_at: u64,
+    visits: &'a [Visit],
+    /// Total time spent on each slide, indexed by slide
+    time_per_slide: BTreeMap<usize, f64>,
+}
+
+/// Visits recorded so far, saved along with the state of the server to carry on recording the
+/// same session once it is restarted
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct History<'a> {
+    /// Unix timestamp of the beginning of the session
+    started_at: u64,
+    visits: Cow<'a, [Visit]>,
+}
+
+/// Records the slide changes of a live session, written to a JSON file by the server
+pub struct Recorder {
+    /// Notified on each visit so that the session gets written
+    changes: mpsc::UnboundedSender<()>,
+    ids: Vec<String>,
+    /// Speaker notes of each slide, only recorded when given
+    notes: Option<Vec<Option<String>>>,
+    started_at: SystemTime,
+    start: Instant,
+    visits: Vec<Visit>,
+}
+
+impl Recorder {
+    pub fn new(
+        ids: Vec<String>,
+        notes: Option<Vec<Option<String>>>,
+        changes: mpsc::UnboundedSender<()>,
+    ) -> Recorder {
+        Recorder {
+            changes,
+            ids,
+            notes,
+            started_at: SystemTime::now(),
+            start: Instant::now(),
+            visits: Vec::new(),
+        }
+    }
+
+    /// Record that the given slide is now presented, going to the slide already presented or to
+    /// one past the end of the deck is ignored
+    pub fn record(&mut self, index: usize) {
+        let current = self.visits.last().map(|visit| visit.index);
+        if current == Some(index) || index >= self.ids.len() {
+            return;
+        }
+        self.visit(index);
+    }
+
+    fn visit(&mut self, index: usize) {
+        let now = self.start.elapsed().as_secs_f64();
+        if let Some(last) = self.visits.last_mut() {
+            last.duration = now - last.start;
+        }
+        let notes = self
+            .notes
+            .as_ref()
+            .and_then(|notes| notes.get(index).cloned().flatten());
+        self.visits.push(Visit {
+            index,
+            id: self.ids.get(index).cloned(),
+            start: now,
+            duration: 0.0,
+            notes,
+        });
+        self.changes.send(()).ok();
+    }
+
+    /// Carry on recording the same session on the first slide of another deck
+    pub fn switch(&mut self, ids: Vec<String>, notes: Vec<Option<String>>) {
+        self.ids = ids;
+        if self.notes.is_some() {
+            self.notes = Some(notes);
+        }
+        self.visit(0);
+    }
+
+    pub fn history(&self) -> History<'_> {
+        History {
+            started_at: self.started_at(),
+            visits: Cow::Borrowed(&self.visits),
+        }
+    }
+
+    /// Carry on recording a session started before the server was restarted, the time the
+    /// server was down is counted on the last slide
+    pub fn resume(&mut self, history: History) {
+        let started_at = UNIX_EPOCH + Duration::from_secs(history.started_at);
+        let elapsed = SystemTime::now()
+            .duration_since(started_at)
+            .unwrap_or_default();
+        if let Some(start) = Instant::now().checked_sub(elapsed) {
+            self.started_at = started_at;
+            self.start = start;
+            self.visits = history.visits.into_owned();
+            self.changes.send(()).ok();
+        }
+    }
+
+    fn started_at(&self) -> u64 {
+        self.started_at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    }
+
+    /// Session recorded so far, with the total time spent on each slide
+    pub fn session(&self) -> serde_json::Result<String> {
+        let mut time_per_slide = BTreeMap::new();
+        for visit in &self.visits {
+            *time_per_slide.entry(visit.index).or_insert(0.0) += visit.duration;
+        }
+        let session = Session {
+            started_at: self.started_at(),
+            visits: &self.visits,
+            time_per_slide,
+        };
+        serde_json::to_string_pretty(&session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let (changes, mut notified) = mpsc::unbounded_channel();
+        let ids = vec!["intro".to_string(), "outro".to_string()];
+        let mut recorder = Recorder::new(ids, None, changes);
+        recorder.record(0);
+        assert!(notified.try_recv().is_ok());
+        recorder.record(0);
+        recorder.record(2);
+        assert!(notified.try_recv().is_err());
+        recorder.record(1);
+        assert!(notified.try_recv().is_ok());
+        let visits: Vec<_> = recorder.visits.iter().map(|visit| visit.index).collect();
+        assert_eq!(visits, vec![0, 1]);
+        assert!(matches!(recorder.history().visits, Cow::Borrowed(_)));
+    }
+}
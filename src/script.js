@@ -1,3 +1,19 @@
+// Projectors are easier to read in light while screen-share viewers prefer dark, each viewer
+// picks the appearance and it is remembered across decks unless set by the URL. It is applied
+// before the page is first shown so that it doesn't flash.
+const DECK_APPEARANCE_KEY = 'deck-appearance';
+{
+  let appearance = new URLSearchParams(window.location.search).get('appearance');
+  if (appearance === null) {
+    try {
+      appearance = localStorage.getItem(DECK_APPEARANCE_KEY);
+    } catch (err) {
+      console.error('Failed to retrieve the appearance from localStorage', err);
+    }
+  }
+  document.documentElement.classList.toggle('dark', appearance === 'dark');
+}
+
 window.addEventListener('load', evt => {
   const query = new URLSearchParams(window.location.search);
   const isWatching = query.get('watch') == 'true';
@@ -9,8 +25,105 @@ window.addEventListener('load', evt => {
       console.error('Failed to retrieve slide index from sessionStorage', err);
     }
   }
-  let index = storedIndex === null ? 0 : parseInt(storedIndex);
+  // Last position in the deck, restored when opening it again, keyed by its location and title
+  const positionKey = `deck-position:${window.location.pathname}:${document.title}`;
+  let savedPosition = null;
+  try {
+    savedPosition = JSON.parse(localStorage.getItem(positionKey));
+  } catch (err) {
+    console.error('Failed to retrieve the last position from localStorage', err);
+  }
   const slides = document.getElementsByClassName('slide');
+  // Slide targeted by the fragment of the URL, either by its id or, for older links, its position
+  function linkedSlide() {
+    const id = decodeURIComponent(window.location.hash.slice(1));
+    for (let i = 0; i < slides.length; i++) {
+      if (slides.item(i).id === id) {
+        return i;
+      }
+    }
+    const position = id.startsWith('slide-') ? Number(id.slice('slide-'.length)) : NaN;
+    return Number.isInteger(position) && position >= 1 && position <= slides.length ? position - 1 : null;
+  }
+  let index = 0;
+  const linkedIndex = linkedSlide();
+  if (storedIndex !== null) {
+    index = parseInt(storedIndex);
+  } else if (linkedIndex !== null) {
+    index = linkedIndex;
+  } else if (savedPosition !== null && savedPosition.index < slides.length) {
+    index = savedPosition.index;
+  }
+  // Number of steps taken on the current slide, such as revealed fragments
+  let steps = 0;
+  // Preview of the webcam moved to the corner reserved on the current slide
+  let webcamPreview = null;
+  const casts = Array.from(document.querySelectorAll('.cast'), element => new CastPlayer(element));
+  let ws = null;
+  // Kiosks go through the slides on their own, each one being shown for its planned time or the
+  // default interval, and start over after the last one
+  let kioskTimer = null;
+  if (DECK_CONFIG.kiosk !== null) {
+    document.body.classList.add('kiosk');
+  }
+
+  function send(event) {
+    if (ws !== null && ws.readyState === WebSocket.OPEN) {
+      ws.send(JSON.stringify(event));
+    }
+  }
+
+  // Problems found while rendering the deck, listed after the slides, and the slides found to
+  // overflow once shown. Clicking a warning shows its slide until it is dismissed.
+  let toasts = document.querySelector('.toasts');
+  const overflowing = new Set();
+  function showToast(message, slide) {
+    if (toasts === null) {
+      toasts = document.createElement('ul');
+      toasts.className = 'toasts';
+      document.body.appendChild(toasts);
+    }
+    const toast = document.createElement('li');
+    toast.className = 'toast';
+    toast.dataset.slide = slide;
+    toast.textContent = message;
+    const close = document.createElement('button');
+    close.className = 'toast-close';
+    close.title = 'Dismiss';
+    close.textContent = '\u00d7';
+    toast.appendChild(close);
+    toasts.appendChild(toast);
+    console.warn(message);
+  }
+  document.addEventListener('click', evt => {
+    const toast = evt.target.closest('.toast');
+    if (toast === null) {
+      return;
+    }
+    if (evt.target.closest('.toast-close') !== null) {
+      toast.remove();
+    } else {
+      goto(Number(toast.dataset.slide));
+    }
+  }, false);
+  function checkOverflow(i) {
+    const content = slides.item(i) !== null ? slides.item(i).querySelector('.content') : null;
+    if (content === null || overflowing.has(i)) {
+      return;
+    }
+    if (content.scrollHeight > content.clientHeight + 1 || content.scrollWidth > content.clientWidth + 1) {
+      overflowing.add(i);
+      showToast('Slide ' + (i + 1) + ': the content overflows the slide', i);
+    }
+  }
+
+  // Slide this viewer has active, reported for the analytics of the served presentation unless
+  // the page is hidden
+  function reportView() {
+    if (DECK_CONFIG.analytics && !DECK_CONFIG.presenter) {
+      send({ type: 'view', index: document.hidden ? null : index });
+    }
+  }
 
   function update() {
     for (let i = 0; i < slides.length; i++) {
@@ -26,6 +139,38 @@ window.addEventListener('load', evt => {
         slide.classList.add('next');
       }
     }
+    for (let i = Math.max(index - 1, 0); i <= Math.min(index + 2, slides.length - 1); i++) {
+      loadMedia(slides.item(i));
+    }
+    // Recordings are paused when leaving their slide and started when showing it if autoplay is set
+    for (const cast of casts) {
+      if (cast.element.closest('.slide') !== slides.item(index)) {
+        cast.pause();
+      } else if (cast.autoplay && !cast.playing()) {
+        cast.play();
+      }
+    }
+    // Videos and audio clips are paused when leaving their slide, those set to autoplay start
+    // over when it is shown again
+    for (let i = 0; i < slides.length; i++) {
+      for (const media of slides.item(i).querySelectorAll('video, audio')) {
+        const autoplay = media.hasAttribute('data-autoplay');
+        if (i !== index) {
+          if (!media.paused) {
+            media.pause();
+          }
+          if (autoplay && media.currentTime > 0) {
+            media.currentTime = 0;
+          }
+        } else if (autoplay && media.paused && media.currentTime === 0) {
+          media.play().catch(err => console.error('Failed to play media', err));
+        }
+      }
+    }
+    // Break countdowns start the first time their slide is shown
+    for (const countdown of slides.item(index).querySelectorAll('.countdown:not(.running)')) {
+      startCountdown(countdown);
+    }
     if (isWatching) {
       try {
         sessionStorage.setItem('index', index);
@@ -33,39 +178,769 @@ window.addEventListener('load', evt => {
         console.error('Failed to save slide index in sessionStorage', err);
       }
     }
+    savePosition();
+    // The URL links to the current slide so that it can be shared or bookmarked
+    if (slides.length > 0 && slides.item(index).id) {
+      history.replaceState(null, '', '#' + encodeURIComponent(slides.item(index).id));
+    }
+    const webcam = slides.item(index).querySelector('.webcam');
+    if (webcamPreview !== null && webcam !== null) {
+      webcam.appendChild(webcamPreview);
+    }
+    redraw();
+    if (DECK_CONFIG.showWarnings) {
+      checkOverflow(index);
+    }
+    if (DECK_CONFIG.kiosk !== null) {
+      clearTimeout(kioskTimer);
+      const seconds = parseInt(slides.item(index).dataset.time || '0') || DECK_CONFIG.kiosk;
+      kioskTimer = setTimeout(() => goto((index + 1) % slides.length), seconds * 1000);
+    }
+    reportView();
   }
 
+  function savePosition() {
+    try {
+      localStorage.setItem(positionKey, JSON.stringify({ index, steps }));
+    } catch (err) {
+      console.error('Failed to save the position in localStorage', err);
+    }
+  }
+
+  // Countdown of a break slide, clicking it starts it over
+  function startCountdown(countdown) {
+    const duration = parseInt(countdown.dataset.duration) * 1000;
+    const end = Date.now() + duration;
+    clearInterval(countdown.timer);
+    countdown.classList.add('running');
+    countdown.classList.remove('finished');
+    const tick = () => {
+      const remaining = Math.max(0, Math.ceil((end - Date.now()) / 1000));
+      const seconds = remaining % 60;
+      countdown.textContent = `${Math.floor(remaining / 60)}:${seconds < 10 ? '0' : ''}${seconds}`;
+      if (remaining === 0) {
+        clearInterval(countdown.timer);
+        countdown.classList.add('finished');
+        if (countdown.hasAttribute('data-chime')) {
+          chime();
+        }
+      }
+    };
+    tick();
+    countdown.timer = setInterval(tick, 250);
+    countdown.onclick = () => startCountdown(countdown);
+  }
+
+  // Three descending notes played with the Web Audio API once a break is over
+  function chime() {
+    try {
+      const audio = new AudioContext();
+      [880, 660, 440].forEach((frequency, i) => {
+        const oscillator = audio.createOscillator();
+        const gain = audio.createGain();
+        const start = audio.currentTime + i * 0.4;
+        oscillator.frequency.value = frequency;
+        gain.gain.setValueAtTime(0.3, start);
+        gain.gain.exponentialRampToValueAtTime(0.001, start + 0.8);
+        oscillator.connect(gain).connect(audio.destination);
+        oscillator.start(start);
+        oscillator.stop(start + 0.8);
+      });
+    } catch (err) {
+      console.error('Failed to play the chime', err);
+    }
+  }
+
+  // Media of the slides are deferred until they are about to be shown
+  function loadMedia(element) {
+    const selector = '[data-src]:not(.cast), [data-srcset]';
+    for (const media of element.querySelectorAll(selector)) {
+      if (media.dataset.srcset) {
+        media.srcset = media.dataset.srcset;
+        media.removeAttribute('data-srcset');
+      }
+      if (media.dataset.src) {
+        media.src = media.dataset.src;
+        media.removeAttribute('data-src');
+      }
+    }
+  }
+  window.addEventListener('beforeprint', () => loadMedia(document.body), false);
+
+  // Speaker notes are printed on pages of their own, either right after their slide or after
+  // all the slides, so that the same deck prints to a rehearsal document
+  if (DECK_CONFIG.printNotes !== null) {
+    window.addEventListener('beforeprint', () => {
+      const pages = [];
+      for (let i = 0; i < slides.length; i++) {
+        const notes = slides.item(i).querySelector('.notes');
+        if (!notes) {
+          continue;
+        }
+        const page = document.createElement('section');
+        page.className = 'printed-notes';
+        const heading = document.createElement('h2');
+        const title = DECK_CONFIG.titles[i];
+        heading.textContent = 'Notes, slide ' + (i + 1) + (title ? ': ' + title : '');
+        const text = document.createElement('p');
+        text.textContent = notes.textContent;
+        page.append(heading, text);
+        pages.push([slides.item(i), page]);
+      }
+      for (const [slide, page] of pages) {
+        if (DECK_CONFIG.printNotes === 'interleaved') {
+          slide.after(page);
+        } else {
+          document.body.append(page);
+        }
+      }
+    }, false);
+    window.addEventListener('afterprint', () => {
+      for (const page of Array.from(document.querySelectorAll('.printed-notes'))) {
+        page.remove();
+      }
+    }, false);
+  }
+
+  function goto(newIndex) {
+    if (newIndex < 0 || newIndex >= slides.length || newIndex === index) {
+      return;
+    }
+    index = newIndex;
+    steps = 0;
+    strokes = [];
+    for (const pre of slides.item(index).querySelectorAll('pre[data-steps]')) {
+      showStep(pre, -1);
+    }
+    for (const item of slides.item(index).querySelectorAll('.task-item.fragment')) {
+      showFragment(item, -1);
+    }
+    update();
+    send({ type: 'goto', index });
+  }
+
+  // Code blocks revealed step by step, each step highlighting a set of lines. Steps are cycled
+  // through before moving to another slide.
+  function showStep(pre, step) {
+    const ranges = step < 0 ? [] : pre.dataset.steps.split('|')[step].split(',').map(range => {
+      const bounds = range.split('-').map(bound => parseInt(bound));
+      return [bounds[0], bounds[bounds.length - 1]];
+    });
+    pre.dataset.step = step;
+    pre.classList.toggle('stepping', step >= 0);
+    pre.querySelectorAll('.line').forEach((line, i) => {
+      const highlighted = ranges.some(([start, end]) => i + 1 >= start && i + 1 <= end);
+      line.classList.toggle('highlighted', highlighted);
+    });
+  }
+
+  function stepCode(direction) {
+    const blocks = Array.from(slides.item(index).querySelectorAll('pre[data-steps]'));
+    if (direction < 0) {
+      blocks.reverse();
+    }
+    for (const pre of blocks) {
+      const current = parseInt(pre.dataset.step || '-1');
+      const next = current + direction;
+      if (next >= -1 && next < pre.dataset.steps.split('|').length && next !== current) {
+        if (direction > 0 || current >= 0) {
+          showStep(pre, next);
+          return true;
+        }
+      }
+    }
+    return false;
+  }
+
+  // Task list items marked as fragments are revealed one by one, those checked in the markdown
+  // being checked off on the following step
+  const fragments = document.querySelectorAll('.task-item.fragment');
+  for (const item of fragments) {
+    item.dataset.checked = item.querySelector('.task').classList.contains('checked');
+    showFragment(item, -1);
+  }
+  function showFragment(item, step) {
+    const marker = item.querySelector('.task');
+    const checked = step > 0;
+    item.dataset.step = step;
+    item.classList.toggle('hidden', step < 0);
+    marker.classList.toggle('checked', checked);
+    marker.setAttribute('aria-checked', checked);
+  }
+
+  function stepFragments(direction) {
+    const items = Array.from(slides.item(index).querySelectorAll('.task-item.fragment'));
+    if (direction < 0) {
+      items.reverse();
+    }
+    for (const item of items) {
+      const next = parseInt(item.dataset.step) + direction;
+      const last = item.dataset.checked === 'true' ? 1 : 0;
+      if (next >= -1 && next <= last) {
+        showFragment(item, next);
+        return true;
+      }
+    }
+    return false;
+  }
+
+  // Code steps come before the fragments of a slide
+  function step(direction) {
+    const stepped = direction > 0
+      ? stepCode(direction) || stepFragments(direction)
+      : stepFragments(direction) || stepCode(direction);
+    if (stepped) {
+      steps += direction;
+      savePosition();
+    }
+    return stepped;
+  }
+
+  // Drawing on the current slide, the pen is toggled with `d` and the strokes are cleared when
+  // changing slide
+  const canvas = document.createElement('canvas');
+  canvas.className = 'drawing';
+  document.body.appendChild(canvas);
+  const context = canvas.getContext('2d');
+  const MAX_STROKE_POINTS = 4096;
+  let strokes = [];
+  let stroke = null;
+
+  function drawStroke(points) {
+    context.beginPath();
+    points.forEach(([x, y], i) => {
+      if (i === 0) {
+        context.moveTo(x * canvas.width, y * canvas.height);
+      } else {
+        context.lineTo(x * canvas.width, y * canvas.height);
+      }
+    });
+    context.stroke();
+  }
+
+  function redraw() {
+    // Offsets ignore the transform used to animate the transition between slides
+    const slide = slides.item(index);
+    if (slide === null) {
+      return;
+    }
+    canvas.style.left = slide.offsetLeft + 'px';
+    canvas.style.top = slide.offsetTop + 'px';
+    canvas.style.width = slide.offsetWidth + 'px';
+    canvas.style.height = slide.offsetHeight + 'px';
+    canvas.width = slide.offsetWidth * window.devicePixelRatio;
+    canvas.height = slide.offsetHeight * window.devicePixelRatio;
+    context.lineWidth = 3 * window.devicePixelRatio;
+    context.lineCap = 'round';
+    context.lineJoin = 'round';
+    context.strokeStyle = 'red';
+    strokes.forEach(drawStroke);
+  }
+
+  function position(evt) {
+    const rect = canvas.getBoundingClientRect();
+    return [(evt.clientX - rect.left) / rect.width, (evt.clientY - rect.top) / rect.height];
+  }
+
+  function finishStroke() {
+    if (stroke === null) {
+      return;
+    }
+    strokes.push(stroke);
+    send({ type: 'stroke', index, points: stroke });
+    stroke = null;
+  }
+
+  canvas.addEventListener('pointerdown', evt => {
+    stroke = [position(evt)];
+    canvas.setPointerCapture(evt.pointerId);
+  }, false);
+  canvas.addEventListener('pointermove', evt => {
+    if (stroke === null) {
+      return;
+    }
+    stroke.push(position(evt));
+    drawStroke(stroke.slice(-2));
+    // The server drops the strokes of more than `MAX_STROKE_POINTS` points
+    if (stroke.length >= MAX_STROKE_POINTS) {
+      const last = stroke[stroke.length - 1];
+      finishStroke();
+      stroke = [last];
+    }
+  }, false);
+  canvas.addEventListener('pointerup', finishStroke, false);
+  canvas.addEventListener('pointercancel', finishStroke, false);
+  window.addEventListener('resize', redraw, false);
+
+  // Laser pointer toggled with `l`, its position is broadcast to the other views at most once
+  // every `POINTER_INTERVAL` milliseconds
+  const POINTER_INTERVAL = 50;
+  const laser = document.createElement('div');
+  laser.className = 'laser';
+  document.body.appendChild(laser);
+  let isPointing = false;
+  let pointerPosition = null;
+  let pointerTimeout = null;
+
+  function showLaser(position) {
+    const slide = slides.item(index);
+    if (position === null || slide === null) {
+      laser.style.display = 'none';
+      return;
+    }
+    laser.style.display = 'block';
+    laser.style.left = (slide.offsetLeft + position[0] * slide.offsetWidth) + 'px';
+    laser.style.top = (slide.offsetTop + position[1] * slide.offsetHeight) + 'px';
+  }
+
+  function sendPointer() {
+    pointerTimeout = null;
+    send({ type: 'pointer', index, position: pointerPosition });
+  }
+
+  function togglePointer() {
+    isPointing = !isPointing;
+    document.body.classList.toggle('laser-mode', isPointing);
+    if (!isPointing) {
+      pointerPosition = null;
+      showLaser(null);
+      sendPointer();
+    }
+  }
+
+  window.addEventListener('pointermove', evt => {
+    const slide = slides.item(index);
+    if (!isPointing || slide === null) {
+      return;
+    }
+    pointerPosition = [
+      (evt.pageX - slide.offsetLeft) / slide.offsetWidth,
+      (evt.pageY - slide.offsetTop) / slide.offsetHeight,
+    ];
+    showLaser(pointerPosition);
+    if (pointerTimeout === null) {
+      pointerTimeout = setTimeout(sendPointer, POINTER_INTERVAL);
+    }
+  }, false);
+
+  window.addEventListener('hashchange', () => {
+    const linked = linkedSlide();
+    if (linked !== null) {
+      goto(linked);
+    }
+  }, false);
+
+  // The steps taken on the restored slide are replayed
+  const savedSteps = savedPosition !== null && savedPosition.index === index ? savedPosition.steps : 0;
   update();
+  for (let i = 0; i < savedSteps; i++) {
+    if (!step(1)) {
+      break;
+    }
+  }
 
-  // Handle key events
-  window.addEventListener('keydown', evt => {
-    if (evt.key === 'ArrowLeft') {
-      if (index === 0) {
+  // Images are enlarged to full screen when clicked, clicking again or pressing Escape closes them
+  const lightbox = document.createElement('div');
+  lightbox.className = 'lightbox';
+  document.body.appendChild(lightbox);
+  const closeLightbox = () => {
+    lightbox.classList.remove('open');
+    lightbox.innerHTML = '';
+  };
+  lightbox.addEventListener('click', closeLightbox, false);
+  for (const image of document.querySelectorAll('.slide img')) {
+    image.addEventListener('click', () => {
+      const enlarged = document.createElement('img');
+      enlarged.src = image.src;
+      enlarged.alt = image.alt;
+      lightbox.appendChild(enlarged);
+      lightbox.classList.add('open');
+    }, false);
+  }
+
+  // While writing the slides, Alt+click opens the source of a slide: in the split-pane editor when
+  // shown there, otherwise in the editor of the machine running the server
+  if (isWatching) {
+    document.addEventListener('click', evt => {
+      const slide = evt.altKey ? evt.target.closest('.slide[data-lines]') : null;
+      if (slide === null) {
         return;
       }
-      index--;
-      update();
-    } else if (evt.key === 'ArrowRight') {
-      if (index == slides.length - 1) {
+      evt.preventDefault();
+      evt.stopPropagation();
+      const line = parseInt(slide.dataset.lines);
+      if (window.parent !== window) {
+        window.parent.postMessage({ type: 'open', line }, window.location.origin);
         return;
       }
-      index++;
+      const position = Array.prototype.indexOf.call(slides, slide);
+      fetch(DECK_CONFIG.baseUrl + 'api/open?slide=' + position, { method: 'POST' })
+        .then(response => {
+          if (!response.ok) {
+            throw new Error(response.statusText);
+          }
+        })
+        .catch(err => console.error('Failed to open the slide in the editor', err));
+    }, true);
+  }
+
+  // Palette opened with `/` to search the slides by title, the best match is selected with the
+  // arrow keys and shown with Enter
+  const palette = document.createElement('div');
+  palette.className = 'palette';
+  const search = document.createElement('input');
+  search.type = 'search';
+  search.placeholder = 'Go to slide…';
+  const results = document.createElement('ol');
+  palette.appendChild(search);
+  palette.appendChild(results);
+  document.body.appendChild(palette);
+  let matches = [];
+  let selected = 0;
+
+  // Score of a title containing the characters of the query in order, consecutive and early
+  // characters scoring higher, or null if it doesn't match
+  function fuzzyScore(title, query) {
+    let score = 0;
+    let position = 0;
+    let streak = 0;
+    for (const c of query) {
+      const found = title.indexOf(c, position);
+      if (found < 0) {
+        return null;
+      }
+      streak = found === position ? streak + 1 : 0;
+      score += 10 + streak * 5 - Math.min(found - position, 10);
+      position = found + 1;
+    }
+    return score;
+  }
+
+  function showMatches() {
+    const query = search.value.trim().toLowerCase();
+    matches = DECK_CONFIG.titles
+      .map((title, i) => {
+        title = title || `Slide ${i + 1}`;
+        return { title, index: i, score: fuzzyScore(title.toLowerCase(), query) };
+      })
+      .filter(slide => slide.score !== null);
+    if (query !== '') {
+      matches.sort((a, b) => b.score - a.score || a.index - b.index);
+    }
+    selected = 0;
+    results.innerHTML = '';
+    matches.forEach((slide, i) => {
+      const item = document.createElement('li');
+      item.textContent = `${slide.index + 1}. ${slide.title}`;
+      item.classList.toggle('selected', i === selected);
+      item.addEventListener('click', () => {
+        closePalette();
+        goto(slide.index);
+      }, false);
+      results.appendChild(item);
+    });
+  }
+
+  function openPalette() {
+    palette.classList.add('open');
+    search.value = '';
+    showMatches();
+    search.focus();
+  }
+
+  function closePalette() {
+    palette.classList.remove('open');
+    search.blur();
+  }
+
+  search.addEventListener('input', showMatches, false);
+  search.addEventListener('keydown', evt => {
+    evt.stopPropagation();
+    if (evt.key === 'Escape') {
+      closePalette();
+    } else if (evt.key === 'Enter') {
+      closePalette();
+      if (matches.length > 0) {
+        goto(matches[selected].index);
+      }
+    } else if (evt.key === 'ArrowDown' || evt.key === 'ArrowUp') {
+      evt.preventDefault();
+      const count = matches.length;
+      selected = count === 0 ? 0 : (selected + (evt.key === 'ArrowDown' ? 1 : count - 1)) % count;
+      Array.from(results.children).forEach((item, i) => {
+        item.classList.toggle('selected', i === selected);
+        if (i === selected) {
+          item.scrollIntoView({ block: 'nearest' });
+        }
+      });
+    }
+  }, false);
+
+  function toggleFullscreen() {
+    if (document.fullscreenElement) {
+      document.exitFullscreen().catch(err => console.error('Failed to exit fullscreen', err));
+    } else if (document.fullscreenEnabled) {
+      document.documentElement.requestFullscreen()
+        .catch(err => console.error('Failed to enter fullscreen', err));
+    }
+  }
+
+  // The screen is kept awake while the presentation is visible, the lock being released by the
+  // browser whenever the page is hidden
+  let wakeLock = null;
+  function requestWakeLock() {
+    if (!('wakeLock' in navigator) || wakeLock !== null || document.visibilityState !== 'visible') {
+      return;
+    }
+    navigator.wakeLock.request('screen').then(lock => {
+      wakeLock = lock;
+      lock.addEventListener('release', () => {
+        wakeLock = null;
+      }, false);
+    }).catch(err => console.error('Failed to keep the screen awake', err));
+  }
+  if (DECK_CONFIG.wakeLock && window.isSecureContext) {
+    requestWakeLock();
+    document.addEventListener('visibilitychange', requestWakeLock, false);
+  }
+
+  if (query.get('webcam') === 'true' && navigator.mediaDevices && slides.length > 0) {
+    navigator.mediaDevices.getUserMedia({ video: true, audio: false }).then(stream => {
+      webcamPreview = document.createElement('video');
+      webcamPreview.muted = true;
+      webcamPreview.autoplay = true;
+      webcamPreview.srcObject = stream;
       update();
+    }).catch(err => console.error('Failed to open the webcam', err));
+  }
+
+  function toggleAppearance() {
+    const isDark = document.documentElement.classList.toggle('dark');
+    try {
+      localStorage.setItem(DECK_APPEARANCE_KEY, isDark ? 'dark' : 'light');
+    } catch (err) {
+      console.error('Failed to save the appearance in localStorage', err);
+    }
+  }
+
+  // Action bound to each key, which can be changed in the front matter
+  const actions = {};
+  for (const [action, keys] of Object.entries(DECK_CONFIG.keys)) {
+    for (const key of keys) {
+      actions[key] = action;
+    }
+  }
+
+  // Number typed before pressing Enter or `g` to jump to a slide
+  let typedNumber = '';
+
+  // Handle key events
+  window.addEventListener('keydown', evt => {
+    if (lightbox.classList.contains('open')) {
+      if (evt.key === 'Escape') {
+        closeLightbox();
+      }
+      return;
+    }
+    if (evt.key.length === 1 && evt.key >= '0' && evt.key <= '9') {
+      typedNumber += evt.key;
+      return;
+    }
+    if ((evt.key === 'Enter' || evt.key === 'g') && typedNumber !== '') {
+      goto(parseInt(typedNumber) - 1);
+      typedNumber = '';
+      return;
+    }
+    typedNumber = '';
+    const action = actions[evt.key];
+    if (action === 'search') {
+      evt.preventDefault();
+      openPalette();
+    } else if (action === 'previous') {
+      if (!step(-1)) {
+        goto(index - 1);
+      }
+    } else if (action === 'next') {
+      if (!step(1)) {
+        goto(index + 1);
+      }
+    } else if (action === 'black') {
+      document.body.classList.toggle('blacked-out');
+    } else if (action === 'present') {
+      // Keep F5 from reloading the page when a remote starts the presentation
+      evt.preventDefault();
+      if (DECK_CONFIG.fullscreen) {
+        toggleFullscreen();
+      }
+    } else if (action === 'exit') {
+      document.body.classList.remove('blacked-out');
+      if (document.fullscreenElement) {
+        toggleFullscreen();
+      }
+    } else if (action === 'pointer') {
+      togglePointer();
+    } else if (action === 'draw') {
+      document.body.classList.toggle('drawing-mode');
+    } else if (action === 'notes' && DECK_CONFIG.showNotes) {
+      document.body.classList.toggle('show-notes');
+    } else if (action === 'fullscreen' && DECK_CONFIG.fullscreen) {
+      toggleFullscreen();
+    } else if (action === 'appearance') {
+      toggleAppearance();
     }
   }, false);
 
-  if (isWatching) {
-    // Setup auto-reload using a websocket transport
-    const uri = 'ws://' + window.location.host + '/ws';
-    const ws = new WebSocket(uri);
+  // Polls, viewers vote by clicking on an option when the presentation is served
+  if (DECK_CONFIG.sync) {
+    for (const poll of document.getElementsByClassName('poll')) {
+      poll.classList.add('interactive');
+      for (const option of poll.querySelectorAll('[data-option]')) {
+        option.addEventListener('click', () => {
+          if (poll.classList.contains('voted')) {
+            return;
+          }
+          poll.classList.add('voted');
+          option.classList.add('selected');
+          send({ type: 'vote', poll: poll.dataset.poll, option: parseInt(option.dataset.option) });
+        }, false);
+      }
+    }
+  }
+
+  function showResults(id, votes) {
+    const poll = document.querySelector('[data-poll="' + CSS.escape(id) + '"]');
+    if (poll === null) {
+      return;
+    }
+    const max = Math.max(1, ...votes);
+    for (const option of poll.querySelectorAll('[data-option]')) {
+      const count = votes[parseInt(option.dataset.option)] || 0;
+      option.querySelector('.poll-count').textContent = count;
+      option.querySelector('.poll-bar span').style.width = (100 * count / max) + '%';
+    }
+  }
+
+  // Questions asked by the audience, only listed in the presenter view
+  let questions = null;
+
+  function showQuestions(list) {
+    if (questions === null) {
+      return;
+    }
+    questions.innerHTML = '';
+    list
+      .slice()
+      .sort((a, b) => b.votes - a.votes)
+      .forEach(question => {
+        const item = document.createElement('li');
+        item.textContent = question.text + ' (' + question.votes + ')';
+        questions.appendChild(item);
+      });
+  }
+
+  if (DECK_CONFIG.presenter) {
+    questions = document.createElement('ol');
+    questions.className = 'presenter-questions';
+    document.body.appendChild(questions);
+
+    // Show the clock, the elapsed time and, when the duration of the talk is known, the
+    // remaining time. Pressing `r` restarts the timer. When rehearsing, the time left on the
+    // current slide and the drift from the time planned for the previous ones are shown too.
+    document.body.classList.add('presenter');
+    const clock = document.createElement('div');
+    clock.className = 'presenter-clock';
+    document.body.appendChild(clock);
+    let start = Date.now();
+    const isRehearsing = query.get('rehearsal') === 'true';
+    let slideIndex = index;
+    let slideStart = start;
+    const plannedTime = i => parseInt(slides.item(i).dataset.time || '0');
+    const format = seconds => {
+      const minutes = Math.floor(seconds / 60);
+      return minutes + ':' + String(seconds % 60).padStart(2, '0');
+    };
+    const tick = () => {
+      const elapsed = Math.floor((Date.now() - start) / 1000);
+      const lines = [new Date().toLocaleTimeString(), 'Elapsed ' + format(elapsed)];
+      if (DECK_CONFIG.duration !== null) {
+        const remaining = DECK_CONFIG.duration - elapsed;
+        const label = remaining < 0 ? 'Overtime ' : 'Remaining ';
+        lines.push(label + format(Math.abs(remaining)));
+      }
+      if (isRehearsing && index < slides.length) {
+        if (slideIndex !== index) {
+          slideIndex = index;
+          slideStart = Date.now();
+        }
+        let planned = 0;
+        for (let i = 0; i < index; i++) {
+          planned += plannedTime(i);
+        }
+        const left = plannedTime(index) - Math.floor((Date.now() - slideStart) / 1000);
+        const drift = Math.floor((slideStart - start) / 1000) - planned + Math.max(0, -left);
+        lines.push((left < 0 ? 'Slide overtime ' : 'Slide ') + format(Math.abs(left)));
+        lines.push((drift > 0 ? 'Behind ' : 'Ahead ') + format(Math.abs(drift)));
+      }
+      clock.textContent = lines.join('\n');
+    };
+    tick();
+    setInterval(tick, 1000);
+    window.addEventListener('keydown', evt => {
+      if (evt.key === 'r') {
+        start = Date.now();
+        slideStart = start;
+        tick();
+      }
+    }, false);
+  }
+
+  // Problems reported by the server, such as live reload being interrupted, stay displayed until
+  // the page is reloaded
+  function showWarning(message) {
+    let warning = document.querySelector('.warning');
+    if (warning === null) {
+      warning = document.createElement('div');
+      warning.className = 'warning';
+      document.body.appendChild(warning);
+    }
+    warning.textContent = message;
+    console.warn(message);
+  }
+
+  if (DECK_CONFIG.sync) {
+    // Keep the views on the same slide and auto-reload them using a websocket transport
+    const protocol = window.location.protocol === 'https:' ? 'wss://' : 'ws://';
+    let uri = protocol + window.location.host + DECK_CONFIG.baseUrl + 'ws';
+    if (query.has('token')) {
+      // Authenticates the view paired with the server, the only one driving the others
+      uri += '?token=' + encodeURIComponent(query.get('token'));
+    }
+    ws = new WebSocket(uri);
     ws.onopen = () => {
       console.log('[WS] Connected');
+      reportView();
     };
+    document.addEventListener('visibilitychange', reportView, false);
     ws.onmessage = msg => {
       console.log('[WS] Message', msg);
       const event = JSON.parse(msg.data);
       if (event.type === 'reload') {
         window.location.reload();
+      } else if (event.type === 'goto' && event.index < slides.length) {
+        index = event.index;
+        strokes = [];
+        update();
+      } else if (event.type === 'warning') {
+        showWarning(event.message);
+      } else if (event.type === 'results') {
+        showResults(event.poll, event.votes);
+      } else if (event.type === 'questions') {
+        showQuestions(event.questions);
+      } else if (event.type === 'pointer') {
+        showLaser(event.index === index ? event.position : null);
+      } else if (event.type === 'stroke' && event.index === index) {
+        strokes.push(event.points);
+        drawStroke(event.points);
       }
     };
   }
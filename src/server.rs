@@ -1,102 +1,1601 @@
-use crate::{error::Error, html};
+use crate::{
+    analytics::Analytics,
+    assets::{percent_decode, AssetDir},
+    compat::{self, Compat},
+    error::Error,
+    excerpt, front_matter, html, mdns,
+    record::{self, Recorder},
+    slides,
+};
 use futures::{FutureExt, StreamExt};
-use inotify::{EventMask, Inotify, WatchMask};
-use log::{debug, error, info};
-use serde::Serialize;
+use inotify::{Inotify, WatchDescriptor, WatchMask};
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    net::SocketAddr,
-    path::{Path, PathBuf},
+    collections::{HashMap, HashSet},
+    env,
+    ffi::OsString,
+    io::{self, Read},
+    net::{self as std_net, IpAddr, SocketAddr},
+    os::unix::{
+        fs::FileTypeExt,
+        io::{FromRawFd, IntoRawFd, RawFd},
+        net as std_unix,
+    },
+    path::{Component, Path, PathBuf},
+    process,
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc,
+        Arc, PoisonError, RwLock,
     },
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::{
     fs,
-    sync::{mpsc, Mutex},
+    net::{TcpListener, UnixListener},
+    sync::{mpsc, Mutex, Notify},
+    task, time,
 };
+use tracing::{debug, error, info, warn};
 use warp::{
+    filters::BoxedFilter,
     reject,
     ws::{Message, WebSocket},
     Filter,
 };
 
-#[derive(Serialize)]
+/// Messages exchanged with the clients over the websocket
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
 enum Event {
     Reload,
+    /// Problem the views should display, such as live reload being interrupted
+    Warning {
+        message: String,
+    },
+    /// Sent by a view when navigating to another slide so that the others follow
+    Goto {
+        index: usize,
+    },
+    /// Line drawn on a slide, its points are relative to the size of the slide
+    Stroke {
+        index: usize,
+        points: Vec<(f64, f64)>,
+    },
+    /// Position of the laser pointer relative to the size of the slide, hidden when missing
+    Pointer {
+        index: usize,
+        position: Option<(f64, f64)>,
+    },
+    /// Question submitted by a viewer
+    Ask {
+        text: String,
+    },
+    Upvote {
+        id: usize,
+    },
+    /// Questions asked so far, sent whenever one is asked or upvoted
+    Questions {
+        questions: Vec<Question>,
+    },
+    /// Vote of a viewer for an option of a poll
+    Vote {
+        poll: String,
+        option: usize,
+    },
+    /// Number of votes received by each option of a poll
+    Results {
+        poll: String,
+        votes: Vec<usize>,
+    },
+    /// Slide a viewer has active, `null` while the presentation is hidden, sent for the
+    /// analytics
+    View {
+        index: Option<usize>,
+    },
+}
+
+/// Limits protecting the server from clients drawing endlessly, the views split longer strokes
+const MAX_STROKES: usize = 1024;
+const MAX_STROKE_POINTS: usize = 4096;
+
+/// Limits protecting the server from clients voting for polls or options that don't exist. The
+/// identifiers of the polls, e.g. `slide-12-poll-1`, are much shorter than `MAX_POLL_ID_LENGTH`.
+const MAX_POLLS: usize = 256;
+const MAX_POLL_OPTIONS: usize = 64;
+const MAX_POLL_ID_LENGTH: usize = 64;
+
+#[derive(Default)]
+struct PollVotes {
+    votes: Vec<usize>,
+    /// Users who already voted, each of them can only do so once
+    voters: HashSet<usize>,
 }
 
+/// Maximum number of characters of a question
+const MAX_QUESTION_LENGTH: usize = 500;
+/// Limits protecting the session from viewers flooding it with questions. The questions and
+/// upvotes are counted together for each address, over `RATE_LIMIT_WINDOW`.
+const MAX_QUESTIONS: usize = 512;
+const QUESTION_RATE_LIMIT: usize = 30;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Question {
+    id: usize,
+    text: String,
+    votes: usize,
+    /// Users who upvoted the question, each of them can only do so once
+    #[serde(skip)]
+    voters: HashSet<usize>,
+}
+
+const ASK_PAGE: &str = include_str!("ask.html");
+const OVERLAY_PAGE: &str = include_str!("overlay.html");
+const EDIT_PAGE: &str = include_str!("edit.html");
+/// Largest markdown file accepted from the editor
+const MAX_SOURCE_SIZE: u64 = 16 * 1024 * 1024;
+/// Texts of the current slide that can be shown as an overlay while streaming
+const OVERLAYS: [&str; 2] = ["current-title", "notes"];
+
 static NEXT_USER_ID: AtomicUsize = AtomicUsize::new(1);
-type Users = Arc<Mutex<HashMap<usize, mpsc::UnboundedSender<Result<Message, warp::Error>>>>>;
 
-async fn watch_files<P>(files: Vec<P>, users: Users) -> Result<(), Error>
+/// Limits applied when listening on a public address unless configured otherwise. Attendees
+/// behind the NAT of a venue share the same address, hence the generous rate.
+const DEFAULT_MAX_CONNECTIONS: usize = 500;
+const DEFAULT_RATE_LIMIT: usize = 1200;
+/// Window over which the requests of each address are counted
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Counts the requests of each remote address over fixed windows of time, the machine running
+/// the server is never limited
+struct RateLimiter {
+    max_requests: usize,
+    window: std::sync::Mutex<(Instant, HashMap<IpAddr, usize>)>,
+}
+
+impl RateLimiter {
+    fn new(max_requests: usize) -> RateLimiter {
+        RateLimiter {
+            max_requests,
+            window: std::sync::Mutex::new((Instant::now(), HashMap::new())),
+        }
+    }
+
+    /// Count a request from the address, whether it is within the limit
+    fn allow(&self, ip: IpAddr) -> bool {
+        if ip.is_loopback() {
+            return true;
+        }
+        let mut window = self.window.lock().unwrap_or_else(PoisonError::into_inner);
+        let (ref mut start, ref mut requests) = *window;
+        if start.elapsed() >= RATE_LIMIT_WINDOW {
+            *start = Instant::now();
+            requests.clear();
+        }
+        let count = requests.entry(ip).or_insert(0);
+        *count += 1;
+        *count <= self.max_requests
+    }
+}
+
+/// Websocket connections open, counted from their upgrade request so that concurrent requests
+/// can't exceed the limit
+struct Connections {
+    open: AtomicUsize,
+    max: Option<usize>,
+}
+
+/// Connection counted as open until dropped along with its websocket
+struct ConnectionSlot(Arc<Connections>);
+
+impl Connections {
+    fn new(max: Option<usize>) -> Arc<Connections> {
+        Arc::new(Connections {
+            open: AtomicUsize::new(0),
+            max,
+        })
+    }
+
+    /// Count a new connection, unless the limit is reached
+    fn reserve(connections: &Arc<Connections>) -> Option<ConnectionSlot> {
+        let max = connections.max.unwrap_or(usize::MAX);
+        connections
+            .open
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| {
+                Some(open + 1).filter(|_| open < max)
+            })
+            .ok()?;
+        Some(ConnectionSlot(connections.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.open.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// State shared by the clients following the presentation
+#[derive(Default)]
+struct State {
+    users: HashMap<usize, mpsc::UnboundedSender<Result<Message, warp::Error>>>,
+    /// Index of the slide currently presented
+    index: usize,
+    /// Strokes drawn on the current slide
+    strokes: Vec<Event>,
+    /// Whether viewers can ask questions
+    ask: bool,
+    questions: Vec<Question>,
+    /// Questions and upvotes of each address, not limited when missing
+    question_limiter: Option<RateLimiter>,
+    /// Remote address of each user, missing for the ones connected over a unix socket
+    addresses: HashMap<usize, IpAddr>,
+    polls: HashMap<String, PollVotes>,
+    recorder: Option<Recorder>,
+    analytics: Option<Analytics>,
+    /// Token authenticating the views driving the presentation, any view can do so when missing
+    token: Option<String>,
+    /// Users who connected with the token
+    presenters: HashSet<usize>,
+    /// Failure of the file watcher, reported to the views connecting until it restarts
+    watch_error: Option<String>,
+    /// Sends the state of the session on each change, to be written to a file in the background
+    saves: Option<mpsc::UnboundedSender<String>>,
+    /// Deck switched to while serving, instead of the one given on the command line
+    deck: Option<PathBuf>,
+}
+
+type SharedState = Arc<Mutex<State>>;
+
+/// State of the session saved to the file given with `--state`, so that restarting the server
+/// resumes the presentation where the audience left it
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedState<'a> {
+    index: usize,
+    questions: Vec<Question>,
+    /// Votes of each poll, the voters are identified by their connection and can't be kept
+    polls: HashMap<String, Vec<usize>>,
+    recording: Option<record::History<'a>>,
+    deck: Option<PathBuf>,
+}
+
+impl State {
+    /// Send the event to every client but the one it originates from
+    fn broadcast(&self, event: &Event, from: Option<usize>) -> Result<(), Error> {
+        let text = serde_json::to_string(event)?;
+        for (&id, tx) in self.users.iter() {
+            if Some(id) != from {
+                tx.send(Ok(Message::text(text.clone()))).ok();
+            }
+        }
+        Ok(())
+    }
+
+    /// Events bringing a view that just connected up to date
+    fn snapshot(&self) -> Vec<Event> {
+        let mut events = vec![Event::Goto { index: self.index }];
+        if let Some(ref message) = self.watch_error {
+            events.push(Event::Warning {
+                message: message.clone(),
+            });
+        }
+        events.extend(self.strokes.iter().cloned());
+        if self.ask {
+            events.push(self.questions_event());
+        }
+        for (poll, votes) in &self.polls {
+            events.push(Event::Results {
+                poll: poll.clone(),
+                votes: votes.votes.clone(),
+            });
+        }
+        events
+    }
+
+    /// Write the state of the session to its file, if any, next to it first so that it is never
+    /// left half written
+    fn save(&self) -> Result<(), Error> {
+        let saves = match self.saves {
+            Some(ref saves) => saves,
+            None => return Ok(()),
+        };
+        let saved = SavedState {
+            index: self.index,
+            questions: self.questions.clone(),
+            polls: self
+                .polls
+                .iter()
+                .map(|(poll, votes)| (poll.clone(), votes.votes.clone()))
+                .collect(),
+            recording: self.recorder.as_ref().map(Recorder::history),
+            deck: self.deck.clone(),
+        };
+        saves.send(serde_json::to_string(&saved)?).ok();
+        Ok(())
+    }
+
+    /// Resume the session saved before the server was restarted, on the last slide of the deck
+    /// if it has been shortened in the meantime
+    fn restore(&mut self, saved: SavedState, slides: usize) {
+        self.index = saved.index.min(slides.saturating_sub(1));
+        self.deck = saved.deck;
+        self.questions = saved.questions;
+        self.polls = saved
+            .polls
+            .into_iter()
+            .map(|(poll, votes)| {
+                let votes = PollVotes {
+                    votes,
+                    voters: HashSet::new(),
+                };
+                (poll, votes)
+            })
+            .collect();
+    }
+
+    /// Start over on the first slide of another deck, the questions asked so far are kept
+    fn switch_deck(&mut self, path: PathBuf, markdown: &str) -> Result<(), Error> {
+        self.index = 0;
+        self.strokes.clear();
+        self.polls.clear();
+        self.deck = Some(path);
+        if let Err(err) = self.save() {
+            error!(error = %err, "Failed to save the state of the session");
+        }
+        self.broadcast(&Event::Reload, None)?;
+        if let Some(ref mut recorder) = self.recorder {
+            let slides = slides::parse(markdown);
+            let ids = slides.iter().map(|slide| slide.id.clone()).collect();
+            let notes = slides.into_iter().map(|slide| slide.notes).collect();
+            recorder.switch(ids, notes);
+        }
+        Ok(())
+    }
+
+    fn questions_event(&self) -> Event {
+        Event::Questions {
+            questions: self.questions.clone(),
+        }
+    }
+
+    /// Count a question or upvote of the user, whether it is within the limit of its address
+    fn allow_question(&self, user_id: usize) -> bool {
+        match (&self.question_limiter, self.addresses.get(&user_id)) {
+            (Some(limiter), Some(ip)) => limiter.allow(*ip),
+            _ => true,
+        }
+    }
+
+    /// Whether the user can navigate, draw and point on behalf of the others
+    fn can_drive(&self, user_id: usize) -> bool {
+        self.token.is_none() || self.presenters.contains(&user_id)
+    }
+
+    fn handle(&mut self, user_id: usize, event: Event) -> Result<(), Error> {
+        let changes_state = matches!(
+            event,
+            Event::Goto { .. } | Event::Ask { .. } | Event::Upvote { .. } | Event::Vote { .. }
+        );
+        let handled = self.handle_event(user_id, event);
+        if changes_state {
+            if let Err(err) = self.save() {
+                error!(error = %err, "Failed to save the state of the session");
+            }
+        }
+        handled
+    }
+
+    fn handle_event(&mut self, user_id: usize, event: Event) -> Result<(), Error> {
+        match event {
+            Event::Goto { .. } | Event::Stroke { .. } | Event::Pointer { .. }
+                if !self.can_drive(user_id) =>
+            {
+                Ok(())
+            }
+            Event::Goto { index } => {
+                self.index = index;
+                self.strokes.clear();
+                if let Some(ref mut recorder) = self.recorder {
+                    recorder.record(index);
+                }
+                self.broadcast(&event, Some(user_id))
+            }
+            // Strokes drawn on a slide that has been left in the meantime are dropped
+            Event::Stroke { index, ref points }
+                if index == self.index
+                    && points.len() <= MAX_STROKE_POINTS
+                    && self.strokes.len() < MAX_STROKES =>
+            {
+                self.broadcast(&event, Some(user_id))?;
+                self.strokes.push(event);
+                Ok(())
+            }
+            Event::Pointer { index, .. } if index == self.index => {
+                self.broadcast(&event, Some(user_id))
+            }
+            Event::Ask { text } if self.ask => {
+                let text: String = text.trim().chars().take(MAX_QUESTION_LENGTH).collect();
+                if text.is_empty()
+                    || self.questions.len() >= MAX_QUESTIONS
+                    || !self.allow_question(user_id)
+                {
+                    return Ok(());
+                }
+                self.questions.push(Question {
+                    id: self.questions.len(),
+                    text,
+                    votes: 0,
+                    voters: HashSet::new(),
+                });
+                self.broadcast(&self.questions_event(), None)
+            }
+            Event::Upvote { id } if self.ask => {
+                if !self.allow_question(user_id) {
+                    return Ok(());
+                }
+                if let Some(question) = self.questions.get_mut(id) {
+                    if question.voters.insert(user_id) {
+                        question.votes += 1;
+                    }
+                }
+                self.broadcast(&self.questions_event(), None)
+            }
+            Event::Vote { poll, option }
+                if option < MAX_POLL_OPTIONS && poll.len() <= MAX_POLL_ID_LENGTH =>
+            {
+                if !self.polls.contains_key(&poll) && self.polls.len() >= MAX_POLLS {
+                    return Ok(());
+                }
+                let votes = self.polls.entry(poll.clone()).or_default();
+                if !votes.voters.insert(user_id) {
+                    return Ok(());
+                }
+                if votes.votes.len() <= option {
+                    votes.votes.resize(option + 1, 0);
+                }
+                votes.votes[option] += 1;
+                let event = Event::Results {
+                    poll,
+                    votes: votes.votes.clone(),
+                };
+                self.broadcast(&event, None)
+            }
+            // The presenters aren't part of the audience
+            Event::View { index } if !self.presenters.contains(&user_id) => {
+                if let Some(ref mut analytics) = self.analytics {
+                    analytics.view(user_id, index);
+                }
+                Ok(())
+            }
+            Event::Stroke { .. }
+            | Event::Pointer { .. }
+            | Event::Ask { .. }
+            | Event::Upvote { .. }
+            | Event::Questions { .. }
+            | Event::Vote { .. }
+            | Event::Results { .. }
+            | Event::View { .. }
+            | Event::Warning { .. }
+            | Event::Reload => Ok(()),
+        }
+    }
+}
+
+/// Write the states of the session to the file given with `--state`, one at a time so that an
+/// older state never overwrites a newer one. The states sent during a write are skipped but the
+/// last one.
+async fn write_states(path: PathBuf, mut saves: mpsc::UnboundedReceiver<String>) {
+    let mut temporary = path.clone().into_os_string();
+    temporary.push(".tmp");
+    while let Some(mut saved) = saves.recv().await {
+        while let Ok(newer) = saves.try_recv() {
+            saved = newer;
+        }
+        let written = match fs::write(&temporary, saved).await {
+            Ok(()) => fs::rename(&temporary, &path).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = written {
+            error!(error = %err, "Failed to save the state of the session");
+        }
+    }
+}
+
+/// Delay gathering the changes of a report, e.g. the views reported by the audience following a
+/// slide change, into a single write
+const REPORT_WRITE_DELAY: Duration = Duration::from_secs(2);
+
+/// Write a report of the session, such as the analytics or the recording, to its file after each
+/// change so that it is complete whenever the server is stopped. The changes made while waiting
+/// or writing are written at once.
+async fn write_report(
+    path: PathBuf,
+    state: SharedState,
+    mut changes: mpsc::UnboundedReceiver<()>,
+    report: fn(&State) -> Option<serde_json::Result<String>>,
+) {
+    let mut temporary = path.clone().into_os_string();
+    temporary.push(".tmp");
+    while changes.recv().await.is_some() {
+        time::delay_for(REPORT_WRITE_DELAY).await;
+        while changes.try_recv().is_ok() {}
+        let report = match report(&*state.lock().await) {
+            Some(report) => report,
+            None => continue,
+        };
+        let written = match report {
+            Ok(report) => match fs::write(&temporary, report).await {
+                Ok(()) => fs::rename(&temporary, &path).await,
+                Err(err) => Err(err),
+            },
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = written {
+            warn!(error = %err, "Failed to write {}", path.to_string_lossy());
+        }
+    }
+}
+
+/// Delays between the attempts to restart the file watcher after a failure
+const WATCH_RETRY_DELAY: Duration = Duration::from_secs(1);
+const WATCH_MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Watch the files for as long as the server runs. Failures are reported loudly, both in the logs
+/// and on every view, and the watcher is restarted with an increasing delay. It is also
+/// restarted on the files of another deck when switching to it.
+async fn keep_watching(paths: Arc<Paths>, poll: Option<Duration>, state: SharedState) {
+    let mut delay = WATCH_RETRY_DELAY;
+    let mut restarted = false;
+    loop {
+        let started = Instant::now();
+        let files = paths.watched().await;
+        let watching = async {
+            match poll {
+                Some(interval) => poll_files(files, interval, state.clone(), restarted).await,
+                None => watch_files(files, state.clone(), restarted).await,
+            }
+        };
+        let res = tokio::select! {
+            res = watching => Some(res),
+            _ = paths.switched.notified() => None,
+        };
+        let res = match res {
+            Some(res) => res,
+            None => {
+                // The views were reloaded along with the switch
+                debug!("Watching the files of the new deck");
+                restarted = false;
+                continue;
+            }
+        };
+        let message = match res {
+            Ok(()) => "Stopped watching the files for changes".to_owned(),
+            Err(err) => format!("Failed to watch the files for changes, error: {}", err),
+        };
+        error!(
+            "{}, live reload is interrupted until the watcher restarts in {:?}",
+            message, delay
+        );
+        {
+            let mut state = state.lock().await;
+            state
+                .broadcast(
+                    &Event::Warning {
+                        message: message.clone(),
+                    },
+                    None,
+                )
+                .ok();
+            state.watch_error = Some(message);
+        }
+        // A watcher that ran for a while is restarted quickly
+        if started.elapsed() > WATCH_MAX_RETRY_DELAY {
+            delay = WATCH_RETRY_DELAY;
+        }
+        time::delay_for(delay).await;
+        delay = (delay * 2).min(WATCH_MAX_RETRY_DELAY);
+        restarted = true;
+    }
+}
+
+/// Reload the views once the watcher restarted as changes may have been missed in the meantime
+async fn resume_watching(state: &SharedState) -> Result<(), Error> {
+    info!("Watching the files for changes again");
+    let mut state = state.lock().await;
+    state.watch_error = None;
+    state.broadcast(&Event::Reload, None)
+}
+
+/// Time without any change after which a burst of events, such as the ones of a single save, is
+/// considered over
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+async fn watch_files<P>(files: Vec<P>, state: SharedState, restarted: bool) -> Result<(), Error>
 where
     P: AsRef<Path>,
 {
+    // Editors such as Vim often save by writing a new file and renaming it over the original
+    // one, which drops any watch set on the file itself. The directories holding the files are
+    // watched instead and their events filtered by file name.
     let mut inotify = Inotify::init()?;
+    let mut watched: HashMap<WatchDescriptor, HashSet<OsString>> = HashMap::new();
     for file in files {
-        inotify.add_watch(file, WatchMask::MODIFY)?;
+        let file = file.as_ref();
+        let name = match file.file_name() {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        let dir = match file.parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+        let wd = inotify.add_watch(
+            dir,
+            WatchMask::MODIFY | WatchMask::CREATE | WatchMask::MOVED_TO,
+        )?;
+        watched.entry(wd).or_default().insert(name);
     }
-    let mut buffer = [0; 32];
+    let mut buffer = [0; 4096];
     let mut stream = inotify.event_stream(&mut buffer)?;
+    if restarted {
+        resume_watching(&state).await?;
+    }
     while let Some(res) = stream.next().await {
         let event = res?;
-        if event.mask.contains(EventMask::MODIFY) {
-            let text = serde_json::to_string(&Event::Reload)?;
-            for (&id, tx) in users.lock().await.iter() {
-                debug!("Reloading user, user_id={}", id);
-                tx.send(Ok(Message::text(text.clone()))).ok();
-            }
+        let changed = match (watched.get(&event.wd), event.name) {
+            (Some(names), Some(name)) => names.contains(&name),
+            _ => false,
+        };
+        if !changed {
+            continue;
         }
+        // Reload once the file has been fully written rather than on each event
+        while let Ok(Some(res)) = time::timeout(WATCH_DEBOUNCE, stream.next()).await {
+            res?;
+        }
+        debug!("Reloading users");
+        state.lock().await.broadcast(&Event::Reload, None)?;
     }
     Ok(())
 }
 
+/// Modification time and size of a file, `None` while it can't be read such as in the middle of
+/// a save
+async fn file_stamp(path: &Path) -> Option<(Option<SystemTime>, u64)> {
+    let metadata = fs::metadata(path).await.ok()?;
+    Some((metadata.modified().ok(), metadata.len()))
+}
+
+/// Fallback for the filesystems on which inotify doesn't report changes, such as NFS or SSHFS
+/// mounts: the files are checked for changes at a regular interval
+async fn poll_files(
+    files: Vec<PathBuf>,
+    interval: Duration,
+    state: SharedState,
+    restarted: bool,
+) -> Result<(), Error> {
+    let mut stamps = Vec::with_capacity(files.len());
+    for file in &files {
+        stamps.push(file_stamp(file).await);
+    }
+    if restarted {
+        resume_watching(&state).await?;
+    }
+    loop {
+        time::delay_for(interval).await;
+        let mut changed = false;
+        for (file, stamp) in files.iter().zip(stamps.iter_mut()) {
+            if let Some(current) = file_stamp(file).await {
+                if Some(current) != *stamp {
+                    *stamp = Some(current);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            debug!("Reloading users");
+            state.lock().await.broadcast(&Event::Reload, None)?;
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub port: u16,
+    /// Addresses the server listens on, `::` accepting both IPv6 and IPv4 connections on most
+    /// systems
+    pub hosts: Vec<IpAddr>,
+    pub mdns: bool,
+    pub base_url: String,
+    pub uds: Option<PathBuf>,
     pub watch: bool,
+    /// Interval at which the watched files are polled instead of relying on inotify
+    pub poll: Option<Duration>,
     pub input: PathBuf,
     pub theme: Option<String>,
     pub theme_dirs: Vec<PathBuf>,
-    pub css: Option<PathBuf>,
-    pub js: Option<PathBuf>,
+    pub deck_theme: html::DeckTheme,
+    /// Custom css and js files, followed by the ones listed in the front matter
+    pub css: Vec<PathBuf>,
+    pub js: Vec<PathBuf>,
+    pub section_dividers: bool,
+    pub balance_headings: bool,
+    /// Duration of the talk in minutes, used to display the remaining time in the presenter view
+    pub duration: Option<u64>,
+    pub max_code_lines: Option<usize>,
+    pub inline_lang: Option<String>,
+    /// Let viewers ask questions from the `/ask` page
+    pub ask: bool,
+    /// Tool the slides were written for, they are then converted on each read
+    pub compat: Option<Compat>,
+    pub slide_level: Option<usize>,
+    /// File the slide changes of the session are recorded to
+    pub record: Option<PathBuf>,
+    pub record_notes: bool,
+    pub fullscreen: bool,
+    pub wake_lock: bool,
+    pub webcam_corner: Option<html::Corner>,
+    /// Only let the views opened with the token printed at startup drive the presentation
+    pub pair: bool,
+    /// Serve the `/edit` page, saving the slides edited there back to the input file
+    pub edit: bool,
+    /// Open the presentation in a browser once the server listens
+    pub open: bool,
+    /// Command launching the browser, the URL being appended to its arguments, instead of the
+    /// default browser of the system
+    pub browser: Option<String>,
+    /// Seconds each slide is shown for on a kiosk, whose browser is restarted when it crashes
+    pub kiosk: Option<u64>,
+    /// File the state of the session is saved to, and resumed from when it exists
+    pub state: Option<PathBuf>,
+    /// File the views of each slide by the audience are reported to, also served at
+    /// `/api/analytics`
+    pub analytics: Option<PathBuf>,
+    /// Directories of shared assets served under a prefix, next to the files of the deck
+    pub asset_dirs: Vec<AssetDir>,
+    /// Maximum number of websocket connections, defaulting to `DEFAULT_MAX_CONNECTIONS` on a
+    /// public address
+    pub max_connections: Option<usize>,
+    /// Maximum number of requests per minute from each address, defaulting to
+    /// `DEFAULT_RATE_LIMIT` on a public address
+    pub rate_limit: Option<usize>,
+}
+
+/// Markdown slides, either read from a file on each request or buffered once from stdin
+#[derive(Clone)]
+enum Input {
+    File(PathBuf),
+    Buffered(String),
+}
+
+impl Input {
+    async fn read(&self) -> Result<String, Error> {
+        match self {
+            Input::File(path) => Ok(fs::read_to_string(path).await?),
+            Input::Buffered(markdown) => Ok(markdown.clone()),
+        }
+    }
 }
 
 struct Paths {
-    input: PathBuf,
-    css: Option<PathBuf>,
-    js: Option<PathBuf>,
+    /// Deck being served, which can be switched to another one while serving
+    input: RwLock<Input>,
+    /// Notified when switching to another deck
+    switched: Notify,
+    compat: Option<Compat>,
+    slide_level: Option<usize>,
+    css: Vec<PathBuf>,
+    js: Vec<PathBuf>,
+}
+
+impl Paths {
+    fn input(&self) -> Input {
+        self.input
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Path of the deck, unless it is read from stdin
+    fn file(&self) -> Option<PathBuf> {
+        match self.input() {
+            Input::File(path) => Some(path),
+            Input::Buffered(_) => None,
+        }
+    }
+
+    fn switch(&self, path: PathBuf) {
+        *self.input.write().unwrap_or_else(PoisonError::into_inner) = Input::File(path);
+        self.switched.notify();
+    }
+
+    async fn markdown(&self) -> Result<String, Error> {
+        let markdown = self.input().read().await?;
+        Ok(match self.compat {
+            Some(compat) => compat::convert(&markdown, compat, self.slide_level),
+            None => markdown,
+        })
+    }
+
+    /// Custom css and js files, the ones listed in the front matter of the deck being relative
+    /// to its directory. An invalid front matter is left to the rendering to report.
+    fn custom_paths(&self, markdown: &str) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let front_matter = front_matter::parse(markdown)
+            .map(|(front_matter, _)| front_matter)
+            .unwrap_or_default();
+        let file = self.file();
+        let dir = file
+            .as_ref()
+            .and_then(|path| path.parent())
+            .unwrap_or_else(|| Path::new(""));
+        let relative = |paths: &[PathBuf]| -> Vec<PathBuf> {
+            paths.iter().map(|path| dir.join(path)).collect()
+        };
+        let css = relative(&front_matter.css);
+        let js = relative(&front_matter.js);
+        (
+            self.css.iter().cloned().chain(css).collect(),
+            self.js.iter().cloned().chain(js).collect(),
+        )
+    }
+
+    /// Custom css and js added to the presentation, each file concatenated in order
+    async fn custom(&self, markdown: &str) -> Result<(Option<String>, Option<String>), Error> {
+        let (css_paths, js_paths) = self.custom_paths(markdown);
+        let mut styles = Vec::with_capacity(css_paths.len());
+        for path in css_paths {
+            styles.push(fs::read_to_string(path).await?);
+        }
+        let mut scripts = Vec::with_capacity(js_paths.len());
+        for path in js_paths {
+            scripts.push(fs::read_to_string(path).await?);
+        }
+        Ok((
+            html::join_custom(styles, "\n"),
+            html::join_custom(scripts, html::JS_SEPARATOR),
+        ))
+    }
+
+    /// Deck and custom files watched for changes. The files added to the front matter later on
+    /// are picked up when the watcher restarts, an unreadable deck being left to the rendering
+    /// to report.
+    async fn watched(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self.file().into_iter().collect();
+        if let Ok(markdown) = self.markdown().await {
+            let (css, js) = self.custom_paths(&markdown);
+            files.extend(css);
+            files.extend(js);
+        }
+        files
+    }
+}
+
+/// First file descriptor passed by systemd when using socket activation
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Socket inherited from the service manager
+enum ActivatedSocket {
+    Tcp(std_net::TcpListener),
+    Unix(std_unix::UnixListener),
+}
+
+/// Retrieve the socket passed by systemd, if any, following the protocol described in
+/// sd_listen_fds(3)
+fn activated_socket() -> Option<ActivatedSocket> {
+    let pid: u32 = env::var("LISTEN_PID").ok()?.parse().ok()?;
+    if pid != process::id() {
+        return None;
+    }
+    let fds: usize = env::var("LISTEN_FDS").ok()?.parse().ok()?;
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    if fds == 0 {
+        return None;
+    }
+    if fds > 1 {
+        warn!(
+            "{} sockets were passed by systemd, only the first one is used",
+            fds
+        );
+    }
+    // Retrieving the local address of a unix socket through a tcp listener fails which tells us
+    // which kind of socket we are dealing with.
+    // SAFETY: systemd passed the descriptor to this very process, as checked with `LISTEN_PID`,
+    // and nothing else takes ownership of it since the variables have been removed
+    let listener = unsafe { std_net::TcpListener::from_raw_fd(SD_LISTEN_FDS_START) };
+    if listener.local_addr().is_ok() {
+        Some(ActivatedSocket::Tcp(listener))
+    } else {
+        let fd = listener.into_raw_fd();
+        // SAFETY: the descriptor has just been released by the tcp listener owning it
+        let listener = unsafe { std_unix::UnixListener::from_raw_fd(fd) };
+        Some(ActivatedSocket::Unix(listener))
+    }
+}
+
+/// Bind the unix socket, removing the one left behind by a server that was killed unless another
+/// server still listens on it. Other kinds of files are never removed.
+fn bind_unix_socket(path: &Path) -> io::Result<std_unix::UnixListener> {
+    let is_socket =
+        std::fs::symlink_metadata(path).map_or(false, |metadata| metadata.file_type().is_socket());
+    if is_socket {
+        if std_unix::UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("Another server listens on {}", path.to_string_lossy()),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    std_unix::UnixListener::bind(path)
+}
+
+/// Unix socket removed once the server stops, so that the next one can be bound to its path
+struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.0) {
+            warn!(error = %err, "Failed to remove the unix socket");
+        }
+    }
+}
+
+/// Ensure the base URL starts and ends with a `/` so that paths can be appended to it
+fn normalize_base_url(base_url: &str) -> String {
+    let segments: Vec<&str> = base_url.split('/').filter(|s| !s.is_empty()).collect();
+    if segments.is_empty() {
+        String::from("/")
+    } else {
+        format!("/{}/", segments.join("/"))
+    }
+}
+
+/// Match the segments of the base URL
+fn base_path(base_url: &str) -> BoxedFilter<()> {
+    base_url
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .fold(warp::any().boxed(), |filter, segment| {
+            filter.and(warp::path(segment.to_owned())).boxed()
+        })
 }
 
 fn convert_error<E: Into<Error>>(err: E) -> warp::Rejection {
     reject::custom(err.into())
 }
 
+/// View of the presentation, rendered with the options of the server unless they are overridden
+/// by the query of a request
+struct View {
+    highlighting: html::Highlighting,
+    options: html::Options,
+}
+
+fn parse_flag(name: &str, value: &str) -> Result<bool, Error> {
+    match value {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err(Error::InvalidQuery {
+            name: name.to_owned(),
+            reason: format!("expected 1 or 0, got {:?}", value),
+        }),
+    }
+}
+
+/// Apply the overrides of a request such as `?theme=InspiredGitHub&notes=1&profile=handout` to
+/// the render options, to preview another theme or the handout without restarting the server.
+/// Only the `theme`, `notes` and `profile` parameters are allowed to change the rendering, the
+/// other ones such as `watch` are left to the script.
+fn override_options(view: &View, query: &HashMap<String, String>) -> Result<html::Options, Error> {
+    let mut options = view.options.clone();
+    // Applied first so that the other parameters refine the profile
+    match query.get("profile").map(String::as_str) {
+        None | Some("default") => {}
+        Some("handout") => {
+            // Meant to be read or printed on its own, with all the media loaded
+            options.show_notes = true;
+            options.lazy_media = false;
+            options.sync = false;
+        }
+        Some(profile) => {
+            return Err(Error::InvalidQuery {
+                name: "profile".to_owned(),
+                reason: format!("expected default or handout, got {:?}", profile),
+            })
+        }
+    }
+    for (name, value) in query {
+        match name.as_str() {
+            "theme" if view.highlighting.has_theme(value) => options.theme = Some(value.clone()),
+            "theme" => {
+                return Err(Error::InvalidQuery {
+                    name: name.clone(),
+                    reason: format!("theme {:?} not found", value),
+                })
+            }
+            "notes" => options.show_notes = parse_flag(name, value)?,
+            _ => {}
+        }
+    }
+    Ok(options)
+}
+
 async fn get_slides(
+    query: HashMap<String, String>,
     paths: Arc<Paths>,
-    renderer: Arc<html::Renderer>,
+    view: Arc<View>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let css = if let Some(ref path) = paths.css {
-        let s = fs::read_to_string(path).await.map_err(convert_error)?;
-        Some(s)
+    let options = override_options(&view, &query).map_err(convert_error)?;
+    let renderer = view.highlighting.renderer(options).map_err(convert_error)?;
+    let markdown = paths.markdown().await.map_err(convert_error)?;
+    let (css, js) = paths.custom(&markdown).await.map_err(convert_error)?;
+    // Highlighting big decks takes a while, render on the blocking pool to keep serving the
+    // other viewers and the websockets meanwhile
+    let rendered = task::spawn_blocking(move || {
+        renderer
+            .render(markdown.clone(), css, js)
+            .map(|output| output.to_string())
+            .map_err(|err| {
+                error!("{}", err);
+                render_error_page(&err, &markdown)
+            })
+    })
+    .await
+    .map_err(convert_error)?;
+    Ok(match rendered {
+        Ok(html) => warp::reply::with_status(warp::reply::html(html), warp::http::StatusCode::OK),
+        Err(page) => warp::reply::with_status(
+            warp::reply::html(page),
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    })
+}
+
+const RENDER_ERROR_CSS: &str = "body { font-family: sans-serif; margin: 2em; }
+pre { padding: 1em; background: #f6f6f6; overflow: auto; }";
+
+/// Page explaining why the deck failed to render, with the lines of the markdown at fault when
+/// the error comes from the deck itself
+fn render_error_page(err: &Error, markdown: &str) -> String {
+    let mut page = String::from("<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    page.push_str(&format!(
+        "<title>Deck failed to render</title>\n<style>{}</style>\n</head>\n<body>\n\
+         <h1>Deck failed to render the slides</h1>\n<p>{}</p>\n",
+        RENDER_ERROR_CSS,
+        html::escape_html(&err.to_string())
+    ));
+    match excerpt::locate(err, markdown) {
+        Some(location) => {
+            let place = match location.slide {
+                Some(slide) => format!("Slide {}, line {}", slide, location.line),
+                None => format!("Front matter, line {}", location.line),
+            };
+            page.push_str(&format!(
+                "<h2>{}</h2>\n<pre>{}</pre>\n",
+                place,
+                html::escape_html(&excerpt::excerpt(markdown, &location))
+            ));
+        }
+        None => page.push_str("<p>Check the server logs</p>\n"),
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+/// Number of slides rendered with each theme on the preview page
+const PREVIEW_SLIDES: usize = 3;
+
+const PREVIEW_CSS: &str = "body { font-family: sans-serif; margin: 2em; }
+.themes { display: grid; grid-template-columns: repeat(auto-fill, minmax(480px, 1fr)); gap: 1.5em; }
+figure { margin: 0; }
+iframe { width: 100%; aspect-ratio: 16 / 9; border: 1px solid #ccc; }";
+
+/// Page showing the first slides of the deck rendered with each theme side by side, the slides
+/// of each preview can be navigated independently
+fn preview_page(previews: &[(&str, String)]) -> String {
+    let mut page = String::from("<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    page.push_str(&format!(
+        "<title>Themes</title>\n<style>{}</style>\n</head>\n<body>\n<div class=\"themes\">\n",
+        PREVIEW_CSS
+    ));
+    for (theme, html) in previews {
+        page.push_str(&format!(
+            "<figure>\n<iframe srcdoc=\"{}\" title=\"{}\"></iframe>\n\
+             <figcaption><a href=\"slides?theme={}\">{}</a></figcaption>\n</figure>\n",
+            html::escape_html(html),
+            html::escape_html(theme),
+            query_escape(theme),
+            html::escape_html(theme)
+        ));
+    }
+    page.push_str("</div>\n</body>\n</html>\n");
+    page
+}
+
+/// Percent-encode a query parameter value
+fn query_escape(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// File of the directory a request path points to, the directory being canonical. Paths escaping
+/// it, through `..` segments or symlinks leading out of it, and hidden files such as `.git` are
+/// refused.
+fn resolve_within(root: &Path, path: &str) -> Option<PathBuf> {
+    let mut file = root.to_path_buf();
+    for segment in path.split('/') {
+        let segment = percent_decode(segment)?;
+        if segment.is_empty() {
+            continue;
+        }
+        if segment.starts_with('.')
+            || segment.contains(|c: char| c == '/' || c == '\\' || c == '\0')
+        {
+            return None;
+        }
+        file.push(segment);
+    }
+    // Symlinks are resolved before checking where the file actually is
+    let file = file.canonicalize().ok()?;
+    if file.starts_with(root) {
+        Some(file)
     } else {
         None
+    }
+}
+
+/// Types of the files whose type guessed from their extension is missing or lacks the charset,
+/// browsers such as Firefox rendering the text ones incorrectly otherwise
+const CONTENT_TYPES: [(&str, &str); 14] = [
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("json", "application/json; charset=utf-8"),
+    ("svg", "image/svg+xml; charset=utf-8"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("vtt", "text/vtt; charset=utf-8"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("wasm", "application/wasm"),
+    // Terminal sessions recorded with asciinema
+    ("cast", "application/x-asciicast"),
+];
+
+/// Type of a served file, `None` to keep the one guessed by warp
+fn content_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    CONTENT_TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, content_type)| *content_type)
+}
+
+/// Files next to the deck, so that its images and media resolve, and files of the shared asset
+/// directories. warp answers the range requests that browsers send to seek within videos, which
+/// Safari even requires to play them.
+fn files_route(
+    base: BoxedFilter<()>,
+    dir: PathBuf,
+    asset_dirs: &[AssetDir],
+) -> BoxedFilter<(Box<dyn warp::Reply>,)> {
+    let mut files = dir_route(base.clone(), dir);
+    // The shared assets take precedence over the files of the deck under their prefix, the first
+    // directory given over the next ones
+    for asset_dir in asset_dirs.iter().rev() {
+        let prefix = asset_dir
+            .prefix
+            .split('/')
+            .fold(base.clone(), |filter, segment| {
+                filter.and(warp::path(segment.to_owned())).boxed()
+            });
+        files = dir_route(prefix, asset_dir.dir.clone())
+            .or(files)
+            .map(|reply| Box::new(reply) as Box<dyn warp::Reply>)
+            .boxed();
+    }
+    files
+}
+
+/// Files of the directory found under the path, served with their proper type
+fn dir_route(path: BoxedFilter<()>, dir: PathBuf) -> BoxedFilter<(Box<dyn warp::Reply>,)> {
+    warp::get()
+        .and(path)
+        .and(contained(dir.clone()))
+        .and(warp::path::peek())
+        .and(warp::fs::dir(dir))
+        .map(|file_path: warp::path::Peek, file: warp::fs::File| {
+            match content_type(Path::new(file_path.as_str())) {
+                Some(content_type) => {
+                    Box::new(warp::reply::with_header(file, "content-type", content_type))
+                        as Box<dyn warp::Reply>
+                }
+                None => Box::new(file),
+            }
+        })
+        .boxed()
+}
+
+/// Only let through the requests for files contained in the directory, the rest of the path
+/// being left to the filters serving them
+fn contained(root: PathBuf) -> BoxedFilter<()> {
+    // The directory is resolved once, nothing is served when it doesn't exist
+    let root = root.canonicalize().ok();
+    warp::path::peek()
+        .and_then(move |path: warp::path::Peek| {
+            let root = root.clone();
+            let requested = path.as_str().to_owned();
+            async move {
+                // Resolving the symlinks of the file reads the filesystem, off the executor
+                let found = match root {
+                    Some(root) => {
+                        task::spawn_blocking(move || resolve_within(&root, &requested).is_some())
+                            .await
+                            .unwrap_or(false)
+                    }
+                    None => false,
+                };
+                if found {
+                    Ok(())
+                } else {
+                    debug!(
+                        path = path.as_str(),
+                        "Refused a file outside of the directory"
+                    );
+                    Err(reject::not_found())
+                }
+            }
+        })
+        .untuple_one()
+        .boxed()
+}
+
+async fn get_preview(
+    paths: Arc<Paths>,
+    view: Arc<View>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let markdown = paths.markdown().await.map_err(convert_error)?;
+    let (css, js) = paths.custom(&markdown).await.map_err(convert_error)?;
+    let page = task::spawn_blocking(move || -> Result<String, Error> {
+        let mut previews = Vec::new();
+        for theme in view.highlighting.themes() {
+            // The previews don't follow the presentation so that each one can be browsed
+            let options = html::Options {
+                theme: Some(theme.to_owned()),
+                sync: false,
+                max_slides: Some(PREVIEW_SLIDES),
+                show_warnings: false,
+                ..view.options.clone()
+            };
+            let output = view.highlighting.renderer(options)?.render(
+                markdown.clone(),
+                css.clone(),
+                js.clone(),
+            )?;
+            previews.push((theme, output.to_string()));
+        }
+        Ok(preview_page(&previews))
+    })
+    .await
+    .map_err(convert_error)?
+    .map_err(convert_error)?;
+    Ok(warp::reply::html(page))
+}
+
+/// Page showing a single slide, by its number starting at 1, to be embedded in a scaled down
+/// frame as its thumbnail
+async fn get_thumbnail(
+    number: usize,
+    paths: Arc<Paths>,
+    view: Arc<View>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let markdown = paths.markdown().await.map_err(convert_error)?;
+    if number == 0 || number > slides::parse(&markdown).len() {
+        return Err(reject::not_found());
+    }
+    let (css, js) = paths.custom(&markdown).await.map_err(convert_error)?;
+    let options = html::Options {
+        slides: Some(slides::Selection::one(number)),
+        ..view.options.clone()
+    };
+    let renderer = view.highlighting.renderer(options).map_err(convert_error)?;
+    let page = task::spawn_blocking(move || {
+        renderer
+            .render(markdown, css, js)
+            .map(|output| output.to_string())
+    })
+    .await
+    .map_err(convert_error)?
+    .map_err(convert_error)?;
+    Ok(warp::reply::html(page))
+}
+
+/// Markdown of the slides as written in their file, before any conversion
+async fn get_source(paths: Option<Arc<Paths>>) -> Result<impl warp::Reply, warp::Rejection> {
+    let path = paths
+        .and_then(|paths| paths.file())
+        .ok_or_else(reject::not_found)?;
+    let markdown = fs::read_to_string(&path).await.map_err(convert_error)?;
+    Ok(warp::reply::with_header(
+        markdown,
+        "content-type",
+        "text/markdown; charset=utf-8",
+    ))
+}
+
+/// Save the slides sent by the editor. They are written next to the input file then moved over
+/// it, so that neither the watcher nor a concurrent request reads a partially written deck.
+async fn put_source(
+    remote: Option<SocketAddr>,
+    paths: Option<Arc<Paths>>,
+    markdown: impl AsRef<[u8]>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    // Anyone reaching the server could otherwise rewrite the files of the machine running it
+    if !from_loopback(remote) {
+        return Ok(warp::http::StatusCode::FORBIDDEN);
+    }
+    let markdown = markdown.as_ref();
+    let path = paths
+        .and_then(|paths| paths.file())
+        .ok_or_else(reject::not_found)?;
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".deck-edit");
+    let temporary = path.with_file_name(name);
+    fs::write(&temporary, markdown)
+        .await
+        .map_err(convert_error)?;
+    fs::rename(&temporary, &path).await.map_err(convert_error)?;
+    debug!(bytes = markdown.len(), "Saved the edited slides");
+    Ok(warp::http::StatusCode::NO_CONTENT)
+}
+
+/// Arguments launching the editor at a line of the given file, following the `+line` convention
+/// of vi, emacs or nano unless the editor is known to take a `file:line` argument instead
+fn editor_args(editor: &str, path: &Path, line: usize) -> Vec<OsString> {
+    let mut args: Vec<OsString> = editor.split_whitespace().map(OsString::from).collect();
+    let program = args
+        .first()
+        .and_then(|program| Path::new(program).file_name())
+        .and_then(|name| name.to_str())
+        .unwrap_or("")
+        .to_owned();
+    let location = format!("{}:{}", path.to_string_lossy(), line);
+    match program.as_str() {
+        "code" | "code-insiders" | "codium" => {
+            args.push("--goto".into());
+            args.push(location.into());
+        }
+        "subl" | "zed" => args.push(location.into()),
+        _ => {
+            args.push(format!("+{}", line).into());
+            args.push(path.into());
+        }
+    }
+    args
+}
+
+/// Address of the machine on the network of its default route, found by connecting a UDP
+/// socket to a documentation address, which doesn't send anything
+fn default_route_ip(v6: bool) -> Option<IpAddr> {
+    let (local, remote): (SocketAddr, SocketAddr) = if v6 {
+        ("[::]:0".parse().ok()?, "[2001:db8::1]:80".parse().ok()?)
+    } else {
+        ("0.0.0.0:0".parse().ok()?, "192.0.2.1:80".parse().ok()?)
     };
-    let js = if let Some(ref path) = paths.js {
-        let s = fs::read_to_string(path).await.map_err(convert_error)?;
-        Some(s)
+    let socket = std_net::UdpSocket::bind(local).ok()?;
+    socket.connect(remote).ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+/// Addresses the server can be reached at. Those listening on every interface are replaced by
+/// the loopback address, then the address of the machine on the network, of both IP versions
+/// for `::` which also accepts IPv4 connections on most systems.
+fn reachable_addrs(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let mut reachable = Vec::new();
+    for addr in addrs {
+        let ips = match addr.ip() {
+            IpAddr::V4(ip) if ip.is_unspecified() => {
+                vec![Some(IpAddr::from([127, 0, 0, 1])), default_route_ip(false)]
+            }
+            IpAddr::V6(ip) if ip.is_unspecified() => vec![
+                Some(IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1])),
+                default_route_ip(true),
+                default_route_ip(false),
+            ],
+            ip => vec![Some(ip)],
+        };
+        for ip in ips.into_iter().flatten() {
+            let addr = SocketAddr::new(ip, addr.port());
+            if !reachable.contains(&addr) {
+                reachable.push(addr);
+            }
+        }
+    }
+    reachable
+}
+
+/// Arguments opening the URL with the given browser command, split on whitespace, or with the
+/// default browser of the system
+fn browser_args(browser: Option<&str>, url: &str) -> Vec<OsString> {
+    let default = if cfg!(target_os = "macos") {
+        "open"
     } else {
-        None
+        "xdg-open"
+    };
+    let mut args: Vec<OsString> = browser
+        .filter(|browser| !browser.trim().is_empty())
+        .unwrap_or(default)
+        .split_whitespace()
+        .map(OsString::from)
+        .collect();
+    args.push(url.into());
+    args
+}
+
+/// Delay before launching the browser of a kiosk again after it crashed
+const BROWSER_RESTART_DELAY: Duration = Duration::from_secs(2);
+
+/// Launch the browser on the presentation, and launch it again whenever it crashes when
+/// `restart` is set. The server keeps running when it fails to start.
+async fn open_browser(args: Vec<OsString>, restart: bool) {
+    loop {
+        debug!(?args, "Opening the presentation in the browser");
+        let mut child = match process::Command::new(&args[0]).args(&args[1..]).spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                warn!(
+                    "Failed to open the browser with {}: {}",
+                    args[0].to_string_lossy(),
+                    err
+                );
+                return;
+            }
+        };
+        // Reap the browser once it is closed
+        match task::spawn_blocking(move || child.wait()).await {
+            Ok(Ok(status)) if restart && !status.success() => {
+                warn!("The browser exited with {}, restarting it", status)
+            }
+            _ => return,
+        }
+        time::delay_for(BROWSER_RESTART_DELAY).await;
+    }
+}
+
+/// Whether the request comes from the machine running the server. Requests without a remote
+/// address, received on a unix socket usually behind a reverse proxy, may come from anyone.
+fn from_loopback(remote: Option<SocketAddr>) -> bool {
+    remote.map_or(false, |addr| addr.ip().is_loopback())
+}
+
+/// Open the markdown file in `$VISUAL` or `$EDITOR` at the first line of the slide given by its
+/// index, e.g. `/api/open?slide=3`
+async fn open_in_editor(
+    query: HashMap<String, String>,
+    remote: Option<SocketAddr>,
+    paths: Option<Arc<Paths>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let paths = paths.ok_or_else(reject::not_found)?;
+    // Anyone reaching the server could otherwise launch programs on the machine running it
+    if !from_loopback(remote) {
+        return Ok(warp::http::StatusCode::FORBIDDEN);
+    }
+    // The lines of converted slides don't match the ones of the file
+    let path = match paths.file() {
+        Some(path) if paths.compat.is_none() => path,
+        _ => return Err(reject::not_found()),
+    };
+    let index = query
+        .get("slide")
+        .and_then(|index| index.parse::<usize>().ok())
+        .ok_or_else(|| {
+            convert_error(Error::InvalidQuery {
+                name: "slide".to_owned(),
+                reason: "expected the index of a slide".to_owned(),
+            })
+        })?;
+    let markdown = fs::read_to_string(&path).await.map_err(convert_error)?;
+    let line = slides::parse(&markdown)
+        .get(index)
+        .map(|slide| slide.lines.0)
+        .ok_or_else(reject::not_found)?;
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .ok_or_else(|| convert_error(Error::NoEditor))?;
+    let args = editor_args(&editor, &path, line);
+    debug!(?args, "Opening the slide in the editor");
+    let mut child = process::Command::new(&args[0])
+        .args(&args[1..])
+        .spawn()
+        .map_err(convert_error)?;
+    // Reap the editor once it is closed
+    task::spawn_blocking(move || child.wait());
+    Ok(warp::http::StatusCode::NO_CONTENT)
+}
+
+/// Serve another deck found next to the current one, e.g. `/api/deck?file=second-talk.md`, and
+/// reload the views on its first slide
+async fn switch_deck(
+    query: HashMap<String, String>,
+    remote: Option<SocketAddr>,
+    paths: Arc<Paths>,
+    state: SharedState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    // Only the machine running the server decides what the audience sees
+    if !from_loopback(remote) {
+        return Ok(warp::http::StatusCode::FORBIDDEN);
+    }
+    let current = paths.file().ok_or_else(reject::not_found)?;
+    let invalid = |reason: &str| {
+        convert_error(Error::InvalidQuery {
+            name: "file".to_owned(),
+            reason: reason.to_owned(),
+        })
     };
-    let markdown = fs::read_to_string(&paths.input)
+    // The images of the decks are served from the directory of the first one
+    let name = query
+        .get("file")
+        .map(Path::new)
+        .filter(|name| {
+            matches!(
+                name.components().collect::<Vec<_>>()[..],
+                [Component::Normal(_)]
+            )
+        })
+        .ok_or_else(|| invalid("expected the name of a deck in the directory of the served one"))?;
+    let path = current.with_file_name(name);
+    match fs::metadata(&path).await {
+        Ok(metadata) if metadata.is_file() => {}
+        _ => return Err(invalid("no such deck")),
+    }
+    info!("Switching to {}", path.to_string_lossy());
+    paths.switch(path.clone());
+    let markdown = paths.markdown().await.map_err(convert_error)?;
+    state
+        .lock()
         .await
+        .switch_deck(path, &markdown)
         .map_err(convert_error)?;
-    let html = renderer.render(markdown, css, js).map_err(convert_error)?;
-    Ok(warp::reply::html(format!("{}", html)))
+    Ok(warp::http::StatusCode::NO_CONTENT)
+}
+
+/// Views of each slide by the audience, only shown to the presenters when the server is paired
+async fn get_analytics(
+    query: HashMap<String, String>,
+    state: SharedState,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let state = state.lock().await;
+    let analytics = state.analytics.as_ref().ok_or_else(reject::not_found)?;
+    if state.token.is_some() && state.token.as_ref() != query.get("token") {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&()),
+            warp::http::StatusCode::FORBIDDEN,
+        ));
+    }
+    Ok(warp::reply::with_status(
+        warp::reply::json(&analytics.report()),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+async fn get_slides_metadata(paths: Arc<Paths>) -> Result<impl warp::Reply, warp::Rejection> {
+    let markdown = paths.markdown().await.map_err(convert_error)?;
+    let slides = slides::parse(&markdown);
+    Ok(warp::reply::json(&slides))
 }
 
 const ERROR_MESSAGE: &str = r#"
@@ -109,10 +1608,23 @@ const ERROR_MESSAGE: &str = r#"
 "#;
 
 async fn customize_error(err: warp::Rejection) -> Result<impl warp::Reply, warp::Rejection> {
-    if let Some(ref err) = err.find::<Error>() {
+    // The errors caused by the client are explained to it
+    let status = match err.find::<Error>() {
+        Some(Error::InvalidQuery { .. }) => Some(warp::http::StatusCode::BAD_REQUEST),
+        Some(Error::TooManyRequests) => Some(warp::http::StatusCode::TOO_MANY_REQUESTS),
+        Some(Error::TooManyConnections) => Some(warp::http::StatusCode::SERVICE_UNAVAILABLE),
+        _ => None,
+    };
+    if let (Some(status), Some(err)) = (status, err.find::<Error>()) {
+        let message = format!(
+            "<html>\n<body>\n<p>{}</p>\n</body>\n</html>\n",
+            html::escape_html(&err.to_string())
+        );
+        Ok(warp::reply::with_status(warp::reply::html(message), status))
+    } else if let Some(ref err) = err.find::<Error>() {
         error!("{}", err);
         Ok(warp::reply::with_status(
-            warp::reply::html(ERROR_MESSAGE),
+            warp::reply::html(ERROR_MESSAGE.to_owned()),
             warp::http::StatusCode::INTERNAL_SERVER_ERROR,
         ))
     } else {
@@ -122,115 +1634,1047 @@ async fn customize_error(err: warp::Rejection) -> Result<impl warp::Reply, warp:
     }
 }
 
-async fn handle_ws(ws: WebSocket, users: Users) -> Result<(), Box<dyn std::error::Error>> {
+/// Random token pairing the presenter view with the server
+fn generate_token() -> Result<String, Error> {
+    let mut bytes = [0; 16];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+async fn handle_ws(
+    ws: WebSocket,
+    state: SharedState,
+    token: Option<String>,
+    remote: Option<SocketAddr>,
+) -> Result<(), Box<dyn std::error::Error>> {
     let user_id = NEXT_USER_ID.fetch_add(1, Ordering::Relaxed);
 
     let (ws_tx, mut ws_rx) = ws.split();
     let (tx, rx) = mpsc::unbounded_channel();
     tokio::task::spawn(rx.forward(ws_tx).map(move |res| {
         if let Err(e) = res {
-            error!(
-                "Failed to send over a websocket, user_id: {}, error: {}",
-                user_id, e
-            )
+            error!(user_id, error = %e, "Failed to send over a websocket")
         }
     }));
 
     {
-        debug!("User connected, user_id: {}", user_id);
-        users.lock().await.insert(user_id, tx);
+        debug!(user_id, "User connected");
+        let mut state = state.lock().await;
+        if state.token.is_some() && token.is_some() {
+            if state.token == token {
+                debug!(user_id, "Presenter connected");
+                state.presenters.insert(user_id);
+            } else {
+                warn!(user_id, "Invalid token received from user");
+            }
+        }
+        for event in state.snapshot() {
+            let text = serde_json::to_string(&event)?;
+            tx.send(Ok(Message::text(text))).ok();
+        }
+        state.users.insert(user_id, tx);
+        if let Some(addr) = remote {
+            state.addresses.insert(user_id, addr.ip());
+        }
     }
 
+    // The user is forgotten below however the connection ends, an error included
+    let mut result: Result<(), Box<dyn std::error::Error>> = Ok(());
     while let Some(res) = ws_rx.next().await {
-        let msg = res?;
-        debug!(
-            "Message received from user, user_id: {}, msg: {:?}",
-            user_id, msg
-        );
+        let msg = match res {
+            Ok(msg) => msg,
+            Err(err) => {
+                result = Err(err.into());
+                break;
+            }
+        };
+        debug!(user_id, ?msg, "Message received from user");
+        let text = match msg.to_str() {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+        match serde_json::from_str(text) {
+            Ok(event) => {
+                if let Err(err) = state.lock().await.handle(user_id, event) {
+                    result = Err(err.into());
+                    break;
+                }
+            }
+            Err(err) => warn!(user_id, error = %err, "Invalid message received from user"),
+        }
     }
 
     {
-        debug!("User disconnected, user_id: {}", user_id);
-        users.lock().await.remove(&user_id);
+        debug!(user_id, "User disconnected");
+        let mut state = state.lock().await;
+        state.users.remove(&user_id);
+        state.presenters.remove(&user_id);
+        state.addresses.remove(&user_id);
+        if let Some(ref mut analytics) = state.analytics {
+            analytics.view(user_id, None);
+        }
     }
 
-    Ok(())
+    result
 }
 
 pub async fn start(config: Config) -> Result<(), Error> {
     let port = config.port;
+    let base_url = normalize_base_url(&config.base_url);
+    let base = base_path(&base_url);
+
+    let token = if config.pair {
+        Some(generate_token()?)
+    } else {
+        None
+    };
+    let state: SharedState = Arc::new(Mutex::new(State {
+        ask: config.ask,
+        question_limiter: Some(RateLimiter::new(QUESTION_RATE_LIMIT)),
+        token: token.clone(),
+        ..State::default()
+    }));
+
+    let input = if config.input == Path::new("-") {
+        let mut markdown = String::new();
+        io::stdin().read_to_string(&mut markdown)?;
+        Input::Buffered(markdown)
+    } else {
+        Input::File(config.input.clone())
+    };
+    let paths = {
+        let p = Paths {
+            input: RwLock::new(input),
+            switched: Notify::new(),
+            compat: config.compat,
+            slide_level: config.slide_level,
+            js: config.js.clone(),
+            css: config.css.clone(),
+        };
+        Arc::new(p)
+    };
 
-    let users = Arc::new(Mutex::new(HashMap::new()));
+    // Resume the session saved before the server was restarted
+    let mut saved: Option<SavedState> = match config.state {
+        Some(ref path) if path.exists() => {
+            info!("Resuming the session saved in {}", path.to_string_lossy());
+            Some(serde_json::from_str(&fs::read_to_string(path).await?)?)
+        }
+        _ => None,
+    };
+    let deck = saved.as_ref().and_then(|saved| saved.deck.clone());
+    if let (Some(deck), Some(_)) = (deck, paths.file()) {
+        info!(
+            "Serving {}, switched to before the restart",
+            deck.to_string_lossy()
+        );
+        paths.switch(deck);
+    }
+    let recording = saved.as_mut().and_then(|saved| saved.recording.take());
+    if let Some(saved) = saved {
+        let slides = slides::parse(&paths.markdown().await?).len();
+        state.lock().await.restore(saved, slides);
+    }
+    if let Some(path) = config.state {
+        let (saves, states) = mpsc::unbounded_channel();
+        task::spawn(write_states(path, states));
+        state.lock().await.saves = Some(saves);
+    }
+    if let Some(path) = config.record {
+        let markdown = paths.markdown().await?;
+        let slides = slides::parse(&markdown);
+        let ids = slides.iter().map(|slide| slide.id.clone()).collect();
+        let notes = if config.record_notes {
+            Some(slides.into_iter().map(|slide| slide.notes).collect())
+        } else {
+            None
+        };
+        info!("Recording the session to {}", path.to_string_lossy());
+        let (changes, notified) = mpsc::unbounded_channel();
+        let mut recorder = Recorder::new(ids, notes, changes);
+        if let Some(history) = recording {
+            recorder.resume(history);
+        }
+        {
+            let mut state = state.lock().await;
+            recorder.record(state.index);
+            state.recorder = Some(recorder);
+        }
+        task::spawn(write_report(path, state.clone(), notified, |state| {
+            state.recorder.as_ref().map(Recorder::session)
+        }));
+    }
+    if let Some(path) = config.analytics.clone() {
+        info!(
+            "Reporting the views of each slide to {}",
+            path.to_string_lossy()
+        );
+        let (changes, notified) = mpsc::unbounded_channel();
+        state.lock().await.analytics = Some(Analytics::new(changes));
+        task::spawn(write_report(path, state.clone(), notified, |state| {
+            let analytics = state.analytics.as_ref()?;
+            Some(serde_json::to_string_pretty(&analytics.report()))
+        }));
+    }
 
     // Setup routes
+    // The images and media of the deck are relative to its directory
+    let dir = match config.input.parent() {
+        Some(dir) if config.input != Path::new("-") && dir != Path::new("") => dir.to_owned(),
+        _ => PathBuf::from("."),
+    };
+    let highlighting = html::Highlighting::load(&config.theme_dirs)?;
+    let options = html::Options {
+        theme: config.theme,
+        deck_theme: config.deck_theme,
+        base_url: base_url.clone(),
+        section_dividers: config.section_dividers,
+        balance_headings: config.balance_headings,
+        // Every view follows the presented slide, not only the watched ones
+        sync: true,
+        duration: config.duration.map(|minutes| minutes * 60),
+        max_code_lines: config.max_code_lines,
+        inline_lang: config.inline_lang,
+        fullscreen: config.fullscreen,
+        wake_lock: config.wake_lock,
+        webcam_corner: config.webcam_corner,
+        kiosk: config.kiosk,
+        analytics: config.analytics.is_some(),
+        source_lines: config.watch,
+        base_dir: Some(dir.clone()),
+        asset_dirs: config.asset_dirs.clone(),
+        show_warnings: true,
+        ..html::Options::default()
+    };
+    // Fail early when the theme isn't found rather than on the first request
+    highlighting.renderer(options.clone())?;
+    let preview = {
+        let paths = paths.clone();
+        let view = Arc::new(View {
+            highlighting: highlighting.clone(),
+            options: options.clone(),
+        });
+        let preview_index = base
+            .clone()
+            .and(warp::path("preview"))
+            .and(warp::path::end());
+        warp::get()
+            .and(preview_index)
+            .and(warp::any().map(move || paths.clone()))
+            .and(warp::any().map(move || view.clone()))
+            .and_then(get_preview)
+    };
+
+    let thumbnails = {
+        let paths = paths.clone();
+        // Thumbnails don't follow the presentation and show their media right away, without
+        // any transition, so that they look like the slide as soon as they are loaded
+        let view = Arc::new(View {
+            highlighting: highlighting.clone(),
+            options: html::Options {
+                sync: false,
+                lazy_media: false,
+                animations: false,
+                wake_lock: false,
+                source_lines: false,
+                show_warnings: false,
+                ..options.clone()
+            },
+        });
+        let thumbnail_path = base
+            .clone()
+            .and(warp::path("api"))
+            .and(warp::path("thumb"))
+            .and(warp::path::param::<usize>())
+            .and(warp::path::end());
+        warp::get()
+            .and(thumbnail_path)
+            .and(warp::any().map(move || paths.clone()))
+            .and(warp::any().map(move || view.clone()))
+            .and_then(get_thumbnail)
+    };
+
     let slides = {
+        let paths = paths.clone();
+        let view = Arc::new(View {
+            highlighting: highlighting.clone(),
+            options: options.clone(),
+        });
+        let slides_index = base
+            .clone()
+            .and(warp::path("slides"))
+            .and(warp::path::end());
+        warp::get()
+            .and(slides_index)
+            .and(warp::query::<HashMap<String, String>>())
+            .and(warp::any().map(move || paths.clone()))
+            .and(warp::any().map(move || view.clone()))
+            .and_then(get_slides)
+    };
+
+    let presenter = {
+        let paths = paths.clone();
         let options = html::Options {
-            theme: config.theme,
-            theme_dirs: config.theme_dirs,
-            ..html::Options::default()
-        };
-        let renderer = {
-            let r = html::Renderer::try_new(options)?;
-            Arc::new(r)
-        };
-        let paths = {
-            let p = Paths {
-                input: config.input.clone(),
-                js: config.js.clone(),
-                css: config.css.clone(),
-            };
-            Arc::new(p)
+            presenter: true,
+            show_notes: true,
+            ..options
         };
-        let slides_index = warp::path("slides").and(warp::path::end());
+        let view = Arc::new(View {
+            highlighting,
+            options,
+        });
+        let presenter_index = base
+            .clone()
+            .and(warp::path("presenter"))
+            .and(warp::path::end());
         warp::get()
-            .and(slides_index)
+            .and(presenter_index)
+            .and(warp::query::<HashMap<String, String>>())
             .and(warp::any().map(move || paths.clone()))
-            .and(warp::any().map(move || renderer.clone()))
+            .and(warp::any().map(move || view.clone()))
             .and_then(get_slides)
     };
 
+    let api = {
+        let paths = paths.clone();
+        let slides_metadata = base
+            .clone()
+            .and(warp::path("api"))
+            .and(warp::path("slides"))
+            .and(warp::path::end());
+        warp::get()
+            .and(slides_metadata)
+            .and(warp::any().map(move || paths.clone()))
+            .and_then(get_slides_metadata)
+    };
+
+    let ask = {
+        let enabled = config.ask;
+        let ask_index = base.clone().and(warp::path("ask")).and(warp::path::end());
+        warp::get().and(ask_index).and_then(move || async move {
+            if enabled {
+                Ok(warp::reply::html(ASK_PAGE))
+            } else {
+                Err(reject::not_found())
+            }
+        })
+    };
+
+    let overlay = {
+        let overlay_index = base
+            .clone()
+            .and(warp::path("overlay"))
+            .and(warp::path::param::<String>())
+            .and(warp::path::end());
+        warp::get()
+            .and(overlay_index)
+            .and_then(|name: String| async move {
+                if OVERLAYS.contains(&name.as_str()) {
+                    Ok(warp::reply::html(OVERLAY_PAGE))
+                } else {
+                    Err(reject::not_found())
+                }
+            })
+    };
+
+    let open = {
+        // Slides are only opened in the editor while writing them
+        let paths = if config.watch {
+            Some(paths.clone())
+        } else {
+            None
+        };
+        let open_path = base
+            .clone()
+            .and(warp::path("api"))
+            .and(warp::path("open"))
+            .and(warp::path::end());
+        warp::post()
+            .and(open_path)
+            .and(warp::query::<HashMap<String, String>>())
+            .and(warp::addr::remote())
+            .and(warp::any().map(move || paths.clone()))
+            .and_then(open_in_editor)
+    };
+
+    // Only the slides read from a file can be edited
+    let editable = config.edit && paths.file().is_some();
+    if config.edit && !editable {
+        warn!("The slides are read from stdin and can't be edited");
+    }
+    let edit = {
+        let enabled = editable;
+        let edit_index = base.clone().and(warp::path("edit")).and(warp::path::end());
+        warp::get().and(edit_index).and_then(move || async move {
+            if enabled {
+                Ok(warp::reply::html(EDIT_PAGE))
+            } else {
+                Err(reject::not_found())
+            }
+        })
+    };
+    let source = {
+        let paths = if editable { Some(paths.clone()) } else { None };
+        let source_path = base
+            .clone()
+            .and(warp::path("api"))
+            .and(warp::path("source"))
+            .and(warp::path::end());
+        let paths = warp::any().map(move || paths.clone());
+        let read = warp::get()
+            .and(source_path.clone())
+            .and(paths.clone())
+            .and_then(get_source);
+        let write = warp::put()
+            .and(source_path)
+            .and(warp::addr::remote())
+            .and(paths)
+            .and(warp::body::content_length_limit(MAX_SOURCE_SIZE))
+            .and(warp::body::bytes())
+            .and_then(put_source);
+        read.or(write)
+    };
+
+    let analytics = {
+        let state = state.clone();
+        let analytics_path = base
+            .clone()
+            .and(warp::path("api"))
+            .and(warp::path("analytics"))
+            .and(warp::path::end());
+        warp::get()
+            .and(analytics_path)
+            .and(warp::query::<HashMap<String, String>>())
+            .and(warp::any().map(move || state.clone()))
+            .and_then(get_analytics)
+    };
+
+    let switch = {
+        let paths = paths.clone();
+        let state = state.clone();
+        let deck_path = base
+            .clone()
+            .and(warp::path("api"))
+            .and(warp::path("deck"))
+            .and(warp::path::end());
+        warp::post()
+            .and(deck_path)
+            .and(warp::query::<HashMap<String, String>>())
+            .and(warp::addr::remote())
+            .and(warp::any().map(move || paths.clone()))
+            .and(warp::any().map(move || state.clone()))
+            .and_then(switch_deck)
+    };
+
+    // Limits protecting the machine running the presentation once anyone can reach it
+    let public = config.hosts.iter().any(|host| !host.is_loopback());
+    let max_connections = config
+        .max_connections
+        .or_else(|| Some(DEFAULT_MAX_CONNECTIONS).filter(|_| public));
+    let rate_limit = config
+        .rate_limit
+        .or_else(|| Some(DEFAULT_RATE_LIMIT).filter(|_| public));
+    if let Some(max) = max_connections {
+        info!("Accepting at most {} websocket connections", max);
+    }
+    if let Some(max) = rate_limit {
+        info!(
+            "Accepting at most {} requests per minute from each address",
+            max
+        );
+    }
+
     let ws = {
-        let users = users.clone();
-        let users = warp::any().map(move || users.clone());
-        warp::path("ws")
+        let available = {
+            let connections = Connections::new(max_connections);
+            warp::any().and_then(move || {
+                let slot = Connections::reserve(&connections);
+                async move {
+                    slot.ok_or_else(|| {
+                        warn!("Refused a websocket connection, the limit is reached");
+                        convert_error(Error::TooManyConnections)
+                    })
+                }
+            })
+        };
+        let state = state.clone();
+        let state = warp::any().map(move || state.clone());
+        let ws_index = base.clone().and(warp::path("ws"));
+        ws_index
+            .and(available)
             .and(warp::ws())
-            .and(users)
-            .map(|ws: warp::ws::Ws, users: Users| {
-                let upgrade = move |socket| async {
-                    if let Err(err) = handle_ws(socket, users).await {
-                        error!("Failed to handle websocket, error: {}", err);
+            .and(state)
+            .and(warp::query::<HashMap<String, String>>())
+            .and(warp::addr::remote())
+            .map(
+                |slot: ConnectionSlot,
+                 ws: warp::ws::Ws,
+                 state: SharedState,
+                 mut query: HashMap<String, String>,
+                 remote: Option<SocketAddr>| {
+                    let token = query.remove("token");
+                    let upgrade = move |socket| async move {
+                        // The connection is counted until the websocket is closed
+                        let _slot = slot;
+                        if let Err(err) = handle_ws(socket, state, token, remote).await {
+                            error!(error = %err, "Failed to handle websocket");
+                        }
+                    };
+                    ws.on_upgrade(upgrade)
+                },
+            )
+    };
+    for asset_dir in &config.asset_dirs {
+        if !asset_dir.dir.is_dir() {
+            warn!(
+                "The asset directory {} doesn't exist",
+                asset_dir.dir.to_string_lossy()
+            );
+        }
+        info!(
+            "Serving {} at {}{}/",
+            asset_dir.dir.to_string_lossy(),
+            base_url,
+            asset_dir.prefix
+        );
+    }
+    let files = files_route(base.clone(), dir, &config.asset_dirs);
+    let within_rate = {
+        let limiter = rate_limit.map(|max| Arc::new(RateLimiter::new(max)));
+        warp::addr::remote()
+            .and_then(move |remote: Option<SocketAddr>| {
+                let limiter = limiter.clone();
+                async move {
+                    match (limiter, remote) {
+                        (Some(limiter), Some(addr)) if !limiter.allow(addr.ip()) => {
+                            debug!(%addr, "Rate limited a request");
+                            Err(convert_error(Error::TooManyRequests))
+                        }
+                        _ => Ok(()),
                     }
-                };
-                ws.on_upgrade(upgrade)
+                }
             })
+            .untuple_one()
     };
-    let routes = slides
-        .or(ws)
-        .with(warp::log("deck"))
+    let routes = within_rate
+        .and(
+            slides
+                .or(presenter)
+                .or(preview)
+                .or(api)
+                .or(thumbnails)
+                .or(analytics)
+                .or(ask)
+                .or(overlay)
+                .or(edit)
+                .or(source)
+                .or(open)
+                .or(switch)
+                .or(ws)
+                .or(files),
+        )
+        .with(warp::trace::request())
         .recover(customize_error);
 
-    // Configure server
-    let addr: SocketAddr = ([127, 0, 0, 1], port).into();
-    let server = warp::serve(routes).bind(addr);
+    // Keep the daemon alive for as long as the server is running
+    let _mdns = if config.mdns {
+        let markdown = paths.markdown().await?;
+        let (front_matter, _) = front_matter::parse(&markdown)?;
+        // Advertised under the same title as the page
+        let title = html::deck_title(front_matter.title, &slides::parse(&markdown))
+            .unwrap_or_else(|| config.input.to_string_lossy().into_owned());
+        if !public {
+            let hosts: Vec<String> = config.hosts.iter().map(IpAddr::to_string).collect();
+            warn!(
+                "The server only listens on {}, use --host to make it reachable from other devices",
+                hosts.join(", ")
+            );
+        }
+        info!(
+            "Advertising the presentation on the local network as {}",
+            title
+        );
+        Some(mdns::advertise(&title, &config.hosts, port)?)
+    } else {
+        None
+    };
 
-    let mut slides_url = format!("{}/slides", addr);
+    let mut slides_path = format!("{}slides", base_url);
     if config.watch {
-        slides_url.push_str("?watch=true");
-        info!("Watching {} for changes", config.input.to_string_lossy());
-        let mut files = vec![config.input];
-        if let Some(css) = config.css {
-            files.push(css.clone());
-        }
-        if let Some(js) = config.js {
-            files.push(js.clone());
+        slides_path.push_str("?watch=true");
+        // A deck read from stdin can't be switched to another one, so there is nothing to watch
+        match paths.file() {
+            Some(path) => {
+                info!("Watching {} for changes", path.to_string_lossy());
+                if let Some(interval) = config.poll {
+                    info!("Polling the files for changes every {:?}", interval);
+                }
+                tokio::task::spawn(keep_watching(paths.clone(), config.poll, state));
+            }
+            None => warn!("The slides are read from stdin and won't be watched for changes"),
         }
-        let f = watch_files(files, users);
-        tokio::task::spawn(f);
     }
 
-    info!("Go to {} to see your slides", slides_url);
+    let presenter_path = match token {
+        Some(ref token) => format!("{}presenter?token={}", base_url, token),
+        None => format!("{}presenter", base_url),
+    };
+    if token.is_some() {
+        info!(
+            "Only the views opened with the token can drive the presentation: {}",
+            presenter_path
+        );
+    }
 
-    server.await;
+    // The browser is opened on the loopback address when the server listens on all of them
+    let open = config.open || config.browser.is_some();
+    let kiosk = config.kiosk.is_some();
+    let browser = config.browser;
+    let open_slides = |addr: SocketAddr| {
+        if open {
+            let addr = reachable_addrs(&[addr])[0];
+            let url = format!("http://{}{}", addr, slides_path);
+            let args = browser_args(browser.as_deref(), &url);
+            task::spawn(open_browser(args, kiosk));
+        }
+    };
+    let server = warp::serve(routes.clone());
+    match (activated_socket(), config.uds) {
+        (Some(ActivatedSocket::Tcp(listener)), _) => {
+            let mut listener = TcpListener::from_std(listener)?;
+            info!(
+                "Go to {}{} to see your slides",
+                listener.local_addr()?,
+                slides_path
+            );
+            open_slides(listener.local_addr()?);
+            server.run_incoming(listener.incoming()).await;
+        }
+        (Some(ActivatedSocket::Unix(listener)), _) => {
+            let mut listener = UnixListener::from_std(listener)?;
+            info!("Serving {} on the socket passed by systemd", slides_path);
+            server.run_incoming(listener.incoming()).await;
+        }
+        (None, Some(path)) => {
+            if open {
+                warn!("The browser can't be opened on a unix socket");
+            }
+            let mut listener = UnixListener::from_std(bind_unix_socket(&path)?)?;
+            let _socket_file = SocketFile(path.clone());
+            info!(
+                "Serving {} on the unix socket {}",
+                slides_path,
+                path.to_string_lossy()
+            );
+            server.run_incoming(listener.incoming()).await;
+        }
+        (None, None) => {
+            let addrs: Vec<SocketAddr> = config
+                .hosts
+                .iter()
+                .map(|host| SocketAddr::new(*host, port))
+                .collect();
+            // Binding happens before the browser is opened so that it never finds the port
+            // closed
+            let servers: Vec<_> = addrs
+                .iter()
+                .map(|addr| warp::serve(routes.clone()).bind(*addr))
+                .collect();
+            let reachable = reachable_addrs(&addrs);
+            for addr in &reachable {
+                info!("Go to {}{} to see your slides", addr, slides_path);
+            }
+            let addr = match reachable.first() {
+                Some(addr) => *addr,
+                None => return Ok(()),
+            };
+            info!("Go to {}{} to present them", addr, presenter_path);
+            if config.ask {
+                info!("Questions can be asked at {}{}ask", addr, base_url);
+            }
+            if editable {
+                info!("Go to {}{}edit to edit them", addr, base_url);
+            }
+            open_slides(addr);
+            futures::future::join_all(servers).await;
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_browser_args() {
+        let url = "http://127.0.0.1:8000/slides";
+        assert_eq!(
+            vec!["chromium", "--kiosk", "--user-data-dir=/tmp/deck", url],
+            browser_args(Some("chromium --kiosk --user-data-dir=/tmp/deck"), url)
+        );
+        assert_eq!(2, browser_args(None, url).len());
+    }
+
+    #[test]
+    fn test_reachable_addrs() {
+        let addrs: Vec<SocketAddr> = vec![
+            "127.0.0.1:8000".parse().unwrap(),
+            "[::1]:8000".parse().unwrap(),
+        ];
+        assert_eq!(addrs, reachable_addrs(&addrs));
+        let reachable = reachable_addrs(&["[::]:8000".parse().unwrap()]);
+        assert_eq!("[::1]:8000", reachable[0].to_string());
+        assert!(reachable.iter().all(|addr| !addr.ip().is_unspecified()));
+    }
+
+    #[test]
+    fn test_from_loopback() {
+        assert!(from_loopback(Some("127.0.0.1:52000".parse().unwrap())));
+        assert!(from_loopback(Some("[::1]:52000".parse().unwrap())));
+        assert!(!from_loopback(Some("192.168.1.20:52000".parse().unwrap())));
+        assert!(!from_loopback(None));
+    }
+
+    #[tokio::test]
+    async fn test_put_source_remote() {
+        use warp::Reply;
+        let remote = Some("192.168.1.20:52000".parse().unwrap());
+        let reply = put_source(remote, None, b"# Title".to_vec())
+            .await
+            .expect("Failed to refuse the remote request");
+        assert_eq!(
+            warp::http::StatusCode::FORBIDDEN,
+            reply.into_response().status()
+        );
+        let local = Some("127.0.0.1:52000".parse().unwrap());
+        assert!(put_source(local, None, b"# Title".to_vec()).await.is_err());
+    }
+
+    #[test]
+    fn test_bind_unix_socket() {
+        let path = std::env::temp_dir().join(format!("deck-{}.sock", std::process::id()));
+        let listener = bind_unix_socket(&path).expect("Failed to bind the socket");
+        let in_use = bind_unix_socket(&path).map(|_| ()).unwrap_err();
+        assert_eq!(io::ErrorKind::AddrInUse, in_use.kind());
+        drop(listener);
+        drop(bind_unix_socket(&path).expect("Failed to replace the stale socket"));
+        std::fs::remove_file(&path).unwrap();
+
+        std::fs::write(&path, "").unwrap();
+        assert!(bind_unix_socket(&path).is_err());
+        assert!(path.is_file());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_normalize_base_url() {
+        assert_eq!("/", normalize_base_url(""));
+        assert_eq!("/", normalize_base_url("/"));
+        assert_eq!("/talks/rust/", normalize_base_url("talks/rust"));
+        assert_eq!("/talks/rust/", normalize_base_url("/talks//rust/"));
+    }
+
+    #[test]
+    fn test_override_options() {
+        let view = View {
+            highlighting: html::Highlighting::load(&[]).expect("Failed to load the highlighting"),
+            options: html::Options {
+                sync: true,
+                ..html::Options::default()
+            },
+        };
+        let query = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<HashMap<String, String>>()
+        };
+        let options = override_options(
+            &view,
+            &query(&[
+                ("theme", "InspiredGitHub"),
+                ("profile", "handout"),
+                ("notes", "0"),
+            ]),
+        )
+        .expect("Failed to override the options");
+        assert_eq!(Some("InspiredGitHub"), options.theme.as_deref());
+        assert!(!options.show_notes);
+        assert!(!options.lazy_media);
+        assert!(!options.sync);
+        assert!(override_options(&view, &query(&[("theme", "Unknown")])).is_err());
+        assert!(override_options(&view, &query(&[("notes", "yes")])).is_err());
+        assert!(override_options(&view, &query(&[("profile", "print")])).is_err());
+        let options = override_options(&view, &query(&[("watch", "true"), ("base_url", "/x/")]))
+            .expect("Failed to override the options");
+        assert_eq!("/", options.base_url);
+    }
+
+    #[test]
+    fn test_editor_args() {
+        let path = Path::new("talk/slides.md");
+        assert_eq!(
+            vec!["vim", "+12", "talk/slides.md"],
+            editor_args("vim", path, 12)
+        );
+        assert_eq!(
+            vec!["/usr/bin/code", "--wait", "--goto", "talk/slides.md:12"],
+            editor_args("/usr/bin/code --wait", path, 12)
+        );
+        assert_eq!(
+            vec!["subl", "talk/slides.md:3"],
+            editor_args("subl", path, 3)
+        );
+    }
+
+    #[test]
+    fn test_preview_page() {
+        let page = preview_page(&[("Solarized (dark)", "<p class=\"a\">A & B</p>".to_owned())]);
+        assert!(
+            page.contains("<iframe srcdoc=\"&lt;p class=&quot;a&quot;&gt;A &amp; B&lt;/p&gt;\"")
+        );
+        assert!(
+            page.contains("<a href=\"slides?theme=Solarized%20%28dark%29\">Solarized (dark)</a>")
+        );
+    }
+
+    #[test]
+    fn test_pairing() {
+        let mut state = State {
+            token: Some("secret".to_owned()),
+            ..State::default()
+        };
+        state.presenters.insert(1);
+        state.handle(2, Event::Goto { index: 3 }).unwrap();
+        assert_eq!(0, state.index);
+        state.handle(1, Event::Goto { index: 3 }).unwrap();
+        assert_eq!(3, state.index);
+    }
+
+    #[test]
+    fn test_stroke_limits() {
+        let mut state = State::default();
+        let stroke = |points| Event::Stroke {
+            index: 0,
+            points: vec![(0.5, 0.5); points],
+        };
+        state.handle(1, stroke(MAX_STROKE_POINTS + 1)).unwrap();
+        assert!(state.strokes.is_empty());
+        for _ in 0..=MAX_STROKES {
+            state.handle(1, stroke(2)).unwrap();
+        }
+        assert_eq!(MAX_STROKES, state.strokes.len());
+    }
+
+    #[test]
+    fn test_question_limits() {
+        let mut state = State {
+            ask: true,
+            question_limiter: Some(RateLimiter::new(2)),
+            ..State::default()
+        };
+        state.addresses.insert(1, IpAddr::from([192, 168, 1, 20]));
+        let ask = || Event::Ask {
+            text: "Why?".to_owned(),
+        };
+        for _ in 0..3 {
+            state.handle(1, ask()).unwrap();
+        }
+        assert_eq!(2, state.questions.len());
+        state.handle(1, Event::Upvote { id: 0 }).unwrap();
+        assert_eq!(0, state.questions[0].votes);
+        for _ in 0..MAX_QUESTIONS {
+            state.handle(2, ask()).unwrap();
+        }
+        assert_eq!(MAX_QUESTIONS, state.questions.len());
+    }
+
+    #[test]
+    fn test_poll_limits() {
+        let mut state = State::default();
+        let vote = |poll: &str, option| Event::Vote {
+            poll: poll.to_owned(),
+            option,
+        };
+        state
+            .handle(1, vote(&"x".repeat(MAX_POLL_ID_LENGTH + 1), 0))
+            .unwrap();
+        state
+            .handle(1, vote("slide-1-poll-1", MAX_POLL_OPTIONS))
+            .unwrap();
+        assert!(state.polls.is_empty());
+        state.handle(1, vote("slide-1-poll-1", 1)).unwrap();
+        state.handle(1, vote("slide-1-poll-1", 0)).unwrap();
+        assert_eq!(vec![0, 1], state.polls["slide-1-poll-1"].votes);
+    }
+
+    #[test]
+    fn test_saved_state() {
+        let (saves, mut states) = mpsc::unbounded_channel();
+        let mut state = State {
+            ask: true,
+            saves: Some(saves),
+            ..State::default()
+        };
+        state.handle(1, Event::Goto { index: 2 }).unwrap();
+        let ask = Event::Ask {
+            text: "Why?".to_owned(),
+        };
+        state.handle(1, ask).unwrap();
+        let vote = Event::Vote {
+            poll: "lunch".to_owned(),
+            option: 1,
+        };
+        state.handle(1, vote).unwrap();
+
+        let mut saved = states.try_recv().expect("Failed to save the state");
+        while let Ok(newer) = states.try_recv() {
+            saved = newer;
+        }
+        let parse = || serde_json::from_str(&saved).expect("Failed to parse the state");
+        let mut restarted = State::default();
+        restarted.restore(parse(), 5);
+        assert_eq!(2, restarted.index);
+        assert_eq!("Why?", restarted.questions[0].text);
+        assert_eq!(vec![0, 1], restarted.polls["lunch"].votes);
+        restarted.restore(parse(), 2);
+        assert_eq!(1, restarted.index);
+    }
+
+    #[tokio::test]
+    async fn test_write_states() {
+        let path = std::env::temp_dir().join(format!("deck-state-{}.json", std::process::id()));
+        let (saves, states) = mpsc::unbounded_channel();
+        saves.send("{\"index\":1}".to_owned()).unwrap();
+        saves.send("{\"index\":2}".to_owned()).unwrap();
+        drop(saves);
+        write_states(path.clone(), states).await;
+        let saved = std::fs::read_to_string(&path).expect("Failed to read the state");
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!("{\"index\":2}", saved);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2);
+        let viewer = IpAddr::from([192, 168, 1, 20]);
+        assert!(limiter.allow(viewer));
+        assert!(limiter.allow(viewer));
+        assert!(!limiter.allow(viewer));
+        assert!(limiter.allow(IpAddr::from([192, 168, 1, 21])));
+        for _ in 0..3 {
+            assert!(limiter.allow(IpAddr::from([127, 0, 0, 1])));
+        }
+    }
+
+    #[test]
+    fn test_connections() {
+        let connections = Connections::new(Some(2));
+        let first = Connections::reserve(&connections);
+        let second = Connections::reserve(&connections);
+        assert!(first.is_some() && second.is_some());
+        assert!(Connections::reserve(&connections).is_none());
+        drop(first);
+        assert!(Connections::reserve(&connections).is_some());
+        assert!(Connections::reserve(&Connections::new(None)).is_some());
+    }
+
+    #[test]
+    fn test_resolve_within() {
+        let root = std::env::temp_dir().join(format!("deck-files-{}", std::process::id()));
+        let deck = root.join("deck");
+        std::fs::create_dir_all(deck.join("images")).unwrap();
+        std::fs::write(deck.join("images/logo.png"), "").unwrap();
+        std::fs::write(deck.join(".env"), "").unwrap();
+        std::fs::write(root.join("secret.txt"), "").unwrap();
+        std::os::unix::fs::symlink("images/logo.png", deck.join("logo.png")).unwrap();
+        std::os::unix::fs::symlink("../secret.txt", deck.join("secret.txt")).unwrap();
+        let deck = deck.canonicalize().unwrap();
+
+        let logo = deck.join("images/logo.png").canonicalize().unwrap();
+        assert_eq!(Some(logo.clone()), resolve_within(&deck, "images/logo.png"));
+        assert_eq!(
+            Some(logo.clone()),
+            resolve_within(&deck, "images//logo%2Epng")
+        );
+        assert_eq!(Some(logo), resolve_within(&deck, "logo.png"));
+        assert_eq!(None, resolve_within(&deck, "../secret.txt"));
+        assert_eq!(
+            None,
+            resolve_within(&deck, "images/%2E%2E/%2E%2E/secret.txt")
+        );
+        assert_eq!(None, resolve_within(&deck, "..%2Fsecret.txt"));
+        assert_eq!(None, resolve_within(&deck, "secret.txt"));
+        assert_eq!(None, resolve_within(&deck, ".env"));
+        assert_eq!(None, resolve_within(&deck, "missing.png"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(
+            Some("image/svg+xml; charset=utf-8"),
+            content_type(Path::new("images/diagram.SVG"))
+        );
+        assert_eq!(
+            Some("application/x-asciicast"),
+            content_type(Path::new("demo.cast"))
+        );
+        assert_eq!(None, content_type(Path::new("photo.png")));
+        assert_eq!(None, content_type(Path::new("LICENSE")));
+    }
+
+    #[tokio::test]
+    async fn test_files_route() {
+        let dir = std::env::temp_dir().join(format!("deck-media-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("demo.mp4"), "0123456789").unwrap();
+        std::fs::write(dir.join("demo.cast"), "{}").unwrap();
+        let files = files_route(base_path("/talks/"), dir.clone(), &[]);
+
+        let res = warp::test::request()
+            .path("/talks/demo.mp4")
+            .header("range", "bytes=2-5")
+            .reply(&files)
+            .await;
+        assert_eq!(206, res.status());
+        assert_eq!("bytes 2-5/10", res.headers()["content-range"]);
+        assert_eq!(&b"2345"[..], res.body());
+
+        let res = warp::test::request()
+            .path("/talks/demo.cast")
+            .reply(&files)
+            .await;
+        assert_eq!(200, res.status());
+        assert_eq!("bytes", res.headers()["accept-ranges"]);
+        assert_eq!("application/x-asciicast", res.headers()["content-type"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_switch_deck() {
+        let mut state = State {
+            index: 4,
+            ask: true,
+            ..State::default()
+        };
+        let ask = Event::Ask {
+            text: "Why?".to_owned(),
+        };
+        state.handle(1, ask).unwrap();
+        let vote = Event::Vote {
+            poll: "lunch".to_owned(),
+            option: 1,
+        };
+        state.handle(1, vote).unwrap();
+        state
+            .switch_deck(PathBuf::from("talks/second.md"), "# Second\n")
+            .unwrap();
+        assert_eq!(0, state.index);
+        assert!(state.polls.is_empty());
+        assert_eq!(1, state.questions.len());
+        assert_eq!(Some(PathBuf::from("talks/second.md")), state.deck);
+    }
+}
@@ -0,0 +1,739 @@
+use crate::{
+    front_matter,
+    poll::{self, Poll},
+};
+use pulldown_cmark::{Event, Options as MarkdownOptions, Parser, Tag};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+use tracing::warn;
+
+/// Layout presets defined in `style.css`
+pub const LAYOUTS: [&str; 5] = ["title", "section", "two-col", "image-full", "quote"];
+
+/// A single slide of a deck along with the metadata extracted from its markup
+#[derive(Debug, Serialize)]
+pub struct Slide<'a> {
+    pub index: usize,
+    pub id: String,
+    pub title: Option<String>,
+    /// Level of the heading the title is taken from, 1 for `#`
+    #[serde(skip)]
+    pub title_level: Option<u32>,
+    pub notes: Option<String>,
+    /// Classes added to the container of the slide
+    pub classes: Vec<String>,
+    /// Name of the layout preset selected with a `layout` directive
+    pub layout: Option<String>,
+    pub polls: Vec<Poll>,
+    /// Time planned for the slide in seconds, set with a `time` directive
+    pub time: Option<u64>,
+    /// Countdown of a break slide, set with a `break` directive
+    pub countdown: Option<Countdown>,
+    /// First and last lines of the markdown the slide is written on, starting at 1
+    pub lines: (usize, usize),
+    #[serde(skip)]
+    pub events: Vec<Event<'a>>,
+}
+
+/// Countdown displayed on a break slide, e.g. `<!-- break: 10m chime -->`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Countdown {
+    /// Duration of the break in seconds
+    pub duration: u64,
+    /// Play a chime once the time is up
+    pub chime: bool,
+}
+
+struct SlideBuilder<'a> {
+    index: usize,
+    /// Id set with an `id` directive
+    id: Option<String>,
+    title: Option<String>,
+    title_level: Option<u32>,
+    in_title: bool,
+    notes: Vec<String>,
+    classes: Vec<String>,
+    layout: Option<String>,
+    polls: Vec<Poll>,
+    time: Option<u64>,
+    countdown: Option<Countdown>,
+    lines: (usize, usize),
+    events: Vec<Event<'a>>,
+}
+
+impl<'a> SlideBuilder<'a> {
+    fn new(index: usize) -> SlideBuilder<'a> {
+        SlideBuilder {
+            index,
+            id: None,
+            title: None,
+            title_level: None,
+            in_title: false,
+            notes: Vec::new(),
+            classes: Vec::new(),
+            layout: None,
+            polls: Vec::new(),
+            time: None,
+            countdown: None,
+            lines: (0, 0),
+            events: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, event: Event<'a>) {
+        match event {
+            Event::Start(Tag::Heading(level)) if self.title.is_none() => {
+                self.in_title = true;
+                self.title = Some(String::new());
+                self.title_level = Some(level);
+            }
+            Event::End(Tag::Heading(_)) => {
+                if self.in_title {
+                    self.strip_title_attributes();
+                }
+                self.in_title = false;
+            }
+            Event::Text(ref text) | Event::Code(ref text) if self.in_title => {
+                if let Some(ref mut title) = self.title {
+                    title.push_str(text);
+                }
+            }
+            Event::Html(ref html) => match parse_directive(html) {
+                Some(("notes", notes)) => {
+                    self.notes.push(notes.to_owned());
+                    return;
+                }
+                Some(("layout", layout)) => {
+                    if LAYOUTS.contains(&layout) {
+                        self.layout = Some(layout.to_owned());
+                    } else {
+                        warn!(
+                            "Unknown layout {:?} on slide {}, expected one of {}",
+                            layout,
+                            self.index + 1,
+                            LAYOUTS.join(", ")
+                        );
+                    }
+                    return;
+                }
+                Some(("id", id)) => {
+                    let id = slugify(id);
+                    if id.is_empty() {
+                        warn!("Invalid id on slide {}", self.index + 1);
+                    } else {
+                        self.id = Some(id);
+                    }
+                    return;
+                }
+                Some(("class", classes)) => {
+                    self.classes.extend(
+                        classes
+                            .split_whitespace()
+                            .filter(|class| is_valid_class(class))
+                            .map(String::from),
+                    );
+                    return;
+                }
+                Some(("time", time)) => {
+                    self.time = parse_duration(time);
+                    if self.time.is_none() {
+                        warn!(
+                            "Invalid time {:?} on slide {}, expected a duration such as 1m30s",
+                            time,
+                            self.index + 1
+                        );
+                    }
+                    return;
+                }
+                Some(("break", countdown)) => {
+                    self.countdown = parse_countdown(countdown);
+                    if self.countdown.is_none() {
+                        warn!(
+                            "Invalid break {:?} on slide {}, expected a duration optionally \
+                             followed by `chime`",
+                            countdown,
+                            self.index + 1
+                        );
+                    }
+                    return;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        self.events.push(event);
+    }
+
+    /// Remove the trailing `{.class}` attributes of the title heading and keep its classes
+    fn strip_title_attributes(&mut self) {
+        let text = match self.events.last_mut() {
+            Some(Event::Text(text)) => text,
+            _ => return,
+        };
+        let (stripped, classes) = match parse_attributes(text) {
+            Some(attributes) => attributes,
+            None => return,
+        };
+        let removed = text.len() - stripped.len();
+        *text = stripped.to_owned().into();
+        if let Some(ref mut title) = self.title {
+            title.truncate(title.len().saturating_sub(removed));
+        }
+        self.classes.extend(classes);
+    }
+
+    fn push_poll(&mut self, mut poll: Poll) {
+        poll.id = format!("slide-{}-poll-{}", self.index + 1, self.polls.len() + 1);
+        self.events.push(Event::Html(poll.to_html().into()));
+        self.polls.push(poll);
+    }
+
+    fn finish(self) -> Slide<'a> {
+        let notes = if self.notes.is_empty() {
+            None
+        } else {
+            Some(self.notes.join("\n\n"))
+        };
+        Slide {
+            index: self.index,
+            id: self.id.unwrap_or_default(),
+            title: self.title,
+            title_level: self.title_level,
+            notes,
+            classes: self.classes,
+            layout: self.layout,
+            polls: self.polls,
+            time: self.time,
+            countdown: self.countdown,
+            lines: self.lines,
+            events: self.events,
+        }
+    }
+}
+
+impl<'a> Slide<'a> {
+    /// Whether the slide only contains a single top level heading, such slides are used to
+    /// introduce a new part of the talk
+    pub fn is_section_divider(&self) -> bool {
+        let mut depth = 0;
+        let mut headings = 0;
+        for event in &self.events {
+            match event {
+                Event::Start(Tag::Heading(1)) if depth == 0 => {
+                    headings += 1;
+                    depth += 1;
+                }
+                Event::Start(_) if depth > 0 => depth += 1,
+                Event::End(_) if depth > 0 => depth -= 1,
+                _ if depth == 0 => return false,
+                _ => {}
+            }
+        }
+        headings == 1
+    }
+
+    /// Whether the slide only contains a single quote, which is then displayed in large type
+    pub fn is_quote(&self) -> bool {
+        let mut depth = 0;
+        let mut quotes = 0;
+        for event in &self.events {
+            match event {
+                Event::Start(Tag::BlockQuote) if depth == 0 => {
+                    quotes += 1;
+                    depth += 1;
+                }
+                Event::Start(_) if depth > 0 => depth += 1,
+                Event::End(_) if depth > 0 => depth -= 1,
+                _ if depth == 0 => return false,
+                _ => {}
+            }
+        }
+        quotes == 1
+    }
+}
+
+/// Extracts the key and the value of a directive written as an HTML comment such as
+/// `<!-- notes: Remember to breathe -->`
+pub fn parse_directive(html: &str) -> Option<(&str, &str)> {
+    let inner = html.trim().strip_prefix("<!--")?.strip_suffix("-->")?;
+    let separator = inner.find(':')?;
+    let key = inner[..separator].trim();
+    let is_valid_key = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !is_valid_key {
+        return None;
+    }
+    Some((key, inner[separator + 1..].trim()))
+}
+
+/// Parses the value of a `break` directive, a duration optionally followed by `chime`
+fn parse_countdown(s: &str) -> Option<Countdown> {
+    let mut words = s.split_whitespace();
+    let duration = parse_duration(words.next()?)?;
+    let mut chime = false;
+    for word in words {
+        match word {
+            "chime" => chime = true,
+            _ => return None,
+        }
+    }
+    Some(Countdown { duration, chime })
+}
+
+/// Parses durations such as `90`, `90s`, `2m` or `1m30s` into seconds
+fn parse_duration(s: &str) -> Option<u64> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+    if let Ok(seconds) = s.parse() {
+        return Some(seconds);
+    }
+    let (minutes, rest) = match s.find('m') {
+        Some(i) => (s[..i].trim().parse::<u64>().ok()?, &s[i + 1..]),
+        None => (0, s),
+    };
+    let rest = rest.trim();
+    let seconds = if rest.is_empty() {
+        0
+    } else {
+        rest.strip_suffix('s')?.trim().parse::<u64>().ok()?
+    };
+    Some(minutes * 60 + seconds)
+}
+
+fn is_valid_class(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Splits a text such as the heading `Heading {.center .dark}` into the text and its classes
+pub fn parse_attributes(text: &str) -> Option<(&str, Vec<String>)> {
+    let text = text.trim_end().strip_suffix('}')?;
+    let start = text.rfind('{')?;
+    let classes = text[start + 1..]
+        .split_whitespace()
+        .map(|class| {
+            let name = class.strip_prefix('.')?;
+            if is_valid_class(name) {
+                Some(name.to_owned())
+            } else {
+                None
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if classes.is_empty() {
+        return None;
+    }
+    Some((text[..start].trim_end(), classes))
+}
+
+/// Splits the markdown input into slides using horizontal rules as separators, the front matter
+/// is skipped
+pub fn parse(input: &str) -> Vec<Slide> {
+    // Lines are counted from the start of the document, front matter included
+    let newlines: Vec<usize> = input.match_indices('\n').map(|(i, _)| i).collect();
+    let (_, body) = front_matter::split(input);
+    let offset = input.len() - body.len();
+    let line_at = |position: usize| match newlines.binary_search(&(offset + position)) {
+        Ok(i) | Err(i) => i + 1,
+    };
+    let lines = |span: Option<(usize, usize)>, fallback: usize| match span {
+        Some((start, end)) => (line_at(start), line_at(end.saturating_sub(1).max(start))),
+        None => (line_at(fallback), line_at(fallback)),
+    };
+    let input = body;
+    let mut opts = MarkdownOptions::empty();
+    opts.insert(MarkdownOptions::ENABLE_TABLES);
+    opts.insert(MarkdownOptions::ENABLE_TASKLISTS);
+    opts.insert(MarkdownOptions::ENABLE_FOOTNOTES);
+    let parser = Parser::new_ext(input, opts).into_offset_iter();
+    let mut polls = poll::find(input).into_iter().peekable();
+    let mut poll_range = None;
+
+    let mut slides = Vec::new();
+    let mut builder = SlideBuilder::new(0);
+    let mut html: Option<String> = None;
+    // Bytes spanned by the events of the current slide
+    let mut span: Option<(usize, usize)> = None;
+    for (event, range) in parser {
+        if !matches!(event, Event::Rule) {
+            span = Some(span.map_or((range.start, range.end), |(start, end)| {
+                (start.min(range.start), end.max(range.end))
+            }));
+        }
+        // Events of a poll block are replaced by the poll itself
+        while polls
+            .peek()
+            .map_or(false, |(next_range, _)| next_range.end <= range.start)
+        {
+            polls.next();
+        }
+        if let Some((next_range, poll)) =
+            polls.next_if(|(next_range, _)| next_range.contains(&range.start))
+        {
+            if let Some(text) = html.take() {
+                builder.push(Event::Html(text.into()));
+            }
+            builder.push_poll(poll);
+            poll_range = Some(next_range);
+        }
+        if let Some(ref poll_range) = poll_range {
+            if poll_range.contains(&range.start) {
+                continue;
+            }
+        }
+        // HTML blocks are emitted line by line, merge them so that multi-line comments can be
+        // inspected as a whole.
+        if let Event::Html(ref text) = event {
+            html.get_or_insert_with(String::new).push_str(text);
+            continue;
+        }
+        if let Some(text) = html.take() {
+            builder.push(Event::Html(text.into()));
+        }
+        if let Event::Rule = event {
+            builder.lines = lines(span.take(), range.start);
+            slides.push(builder.finish());
+            builder = SlideBuilder::new(slides.len());
+        } else {
+            builder.push(event);
+        }
+    }
+    if let Some(text) = html.take() {
+        builder.push(Event::Html(text.into()));
+    }
+    builder.lines = lines(span, input.len());
+    slides.push(builder.finish());
+    place_footnotes(&mut slides);
+    assign_ids(&mut slides);
+    slides
+}
+
+/// Anchor derived from the title of a slide, e.g. `Why Rust?` becomes `why-rust`
+fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_owned()
+}
+
+/// Derive the id of each slide from its title, unless set with an `id` directive, so that links
+/// and timings keyed to it survive slides being inserted or reordered. Slides without a title
+/// fall back to their position and ids already taken are suffixed with `-2`, `-3`...
+fn assign_ids(slides: &mut [Slide]) {
+    let mut taken = HashSet::new();
+    for slide in slides.iter_mut() {
+        let mut id = std::mem::take(&mut slide.id);
+        if id.is_empty() {
+            id = slide.title.as_deref().map(slugify).unwrap_or_default();
+        }
+        if id.is_empty() {
+            id = format!("slide-{}", slide.index + 1);
+        }
+        let mut unique = id.clone();
+        let mut n = 1;
+        while !taken.insert(unique.clone()) {
+            n += 1;
+            unique = format!("{}-{}", id, n);
+        }
+        slide.id = unique;
+    }
+}
+
+/// Move the footnote definitions, usually written at the end of the deck, to the bottom of the
+/// slides referencing them. A last slide only made of definitions is removed.
+fn place_footnotes(slides: &mut Vec<Slide>) {
+    let referenced: HashSet<String> = slides
+        .iter()
+        .flat_map(|slide| slide.events.iter())
+        .filter_map(|event| match event {
+            Event::FootnoteReference(label) => Some(label.to_string()),
+            _ => None,
+        })
+        .collect();
+    let mut definitions = HashMap::new();
+    let mut emptied = false;
+    for slide in slides.iter_mut() {
+        let had_events = !slide.events.is_empty();
+        let mut events = Vec::with_capacity(slide.events.len());
+        let mut definition: Option<(String, Vec<Event>)> = None;
+        for event in slide.events.drain(..) {
+            if let Event::Start(Tag::FootnoteDefinition(ref label)) = event {
+                if referenced.contains(&**label) {
+                    definition = Some((label.to_string(), Vec::new()));
+                }
+            }
+            match definition {
+                Some((_, ref mut definition_events)) => definition_events.push(event),
+                None => events.push(event),
+            }
+            if let Some(Event::End(Tag::FootnoteDefinition(_))) =
+                definition.as_ref().and_then(|(_, events)| events.last())
+            {
+                let (label, definition_events) = definition.take().unwrap_or_default();
+                definitions.entry(label).or_insert(definition_events);
+            }
+        }
+        emptied = had_events && events.is_empty();
+        slide.events = events;
+    }
+    if emptied && slides.len() > 1 {
+        slides.pop();
+    }
+
+    for slide in slides.iter_mut() {
+        let mut labels = Vec::new();
+        for event in &slide.events {
+            if let Event::FootnoteReference(label) = event {
+                if !labels.contains(label) {
+                    labels.push(label.clone());
+                }
+            }
+        }
+        let footnotes: Vec<Event> = labels
+            .iter()
+            .filter_map(|label| definitions.get(&**label))
+            .flatten()
+            .cloned()
+            .collect();
+        if footnotes.is_empty() {
+            continue;
+        }
+        slide
+            .events
+            .push(Event::Html("<div class=\"footnotes\">\n".into()));
+        slide.events.extend(footnotes);
+        slide.events.push(Event::Html("</div>\n".into()));
+    }
+}
+
+/// Slides kept when rendering a subset of the deck, written as a list of numbers and ranges
+/// starting at 1, e.g. `1,5-10`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection(Vec<(usize, usize)>);
+
+impl Selection {
+    /// Select a single slide, by its number starting at 1
+    pub fn one(number: usize) -> Selection {
+        Selection(vec![(number, number)])
+    }
+
+    /// Whether the slide at the given index is selected
+    pub fn contains(&self, index: usize) -> bool {
+        self.0
+            .iter()
+            .any(|&(first, last)| (first..=last).contains(&(index + 1)))
+    }
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Selection, String> {
+        let number = |n: &str| match n.trim().parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!(
+                "Invalid slide number {:?}, slides start at 1",
+                n.trim()
+            )),
+        };
+        let mut ranges = Vec::new();
+        for range in s.split(',') {
+            let (first, last) = match range.split_once('-') {
+                Some((first, last)) => (number(first)?, number(last)?),
+                None => (number(range)?, number(range)?),
+            };
+            if first > last {
+                return Err(format!("Invalid range of slides {:?}", range.trim()));
+            }
+            ranges.push((first, last));
+        }
+        Ok(Selection(ranges))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let input = r#"
+# Slide 1
+
+<!-- notes: Introduce
+yourself -->
+
+---
+
+Some `code` without a title
+
+---
+
+## Slide `3`
+"#;
+        let slides = parse(input);
+        assert_eq!(3, slides.len());
+        assert_eq!("slide-1", slides[0].id);
+        assert_eq!(Some("Slide 1"), slides[0].title.as_deref());
+        assert_eq!(Some("Introduce\nyourself"), slides[0].notes.as_deref());
+        assert_eq!(None, slides[1].title);
+        assert_eq!(None, slides[1].notes);
+        assert_eq!(Some("Slide 3"), slides[2].title.as_deref());
+        assert_eq!(Some(2), slides[2].title_level);
+        let lines: Vec<(usize, usize)> = slides.iter().map(|slide| slide.lines).collect();
+        assert_eq!(vec![(2, 5), (9, 9), (13, 13)], lines);
+
+        let slides = parse("---\ntitle: Deck\n---\n# One\n\n---\n\n# Two\n");
+        assert_eq!((4, 4), slides[0].lines);
+        assert_eq!((8, 8), slides[1].lines);
+    }
+
+    #[test]
+    fn test_assign_ids() {
+        let input = r#"
+# Why Rust?
+
+---
+
+# Why Rust?
+
+---
+
+No title
+
+---
+
+<!-- id: Demo -->
+# Live
+"#;
+        let slides = parse(input);
+        let ids: Vec<&str> = slides.iter().map(|slide| slide.id.as_str()).collect();
+        assert_eq!(vec!["why-rust", "why-rust-2", "slide-3", "demo"], ids);
+        assert_eq!("étape-2-init", slugify("  Étape 2 — `init` "));
+    }
+
+    #[test]
+    fn test_parse_classes() {
+        let slides = parse("## Closing words {.center .dark}\n\n# Not a title {.ignored}\n");
+        assert_eq!(Some("Closing words"), slides[0].title.as_deref());
+        assert_eq!(vec!["center", "dark"], slides[0].classes);
+        let slides = parse("<!-- class: lead in<valid -->\n# Title\n");
+        assert_eq!(vec!["lead"], slides[0].classes);
+        assert_eq!(None, parse_attributes("Sets {a, b}"));
+        assert_eq!(None, parse_attributes("Braces {}"));
+    }
+
+    #[test]
+    fn test_is_section_divider() {
+        let slides = parse(
+            "# Part `1`\n\n---\n\n# Part 2\n\nIntro\n\n---\n\n## Part 3\n\n---\n\n# A\n# B\n",
+        );
+        let dividers: Vec<bool> = slides.iter().map(Slide::is_section_divider).collect();
+        assert_eq!(vec![true, false, false, false], dividers);
+        let slides = parse("> Quote\n> -- Author\n\n---\n\n> Quote\n\nComment\n");
+        let quotes: Vec<bool> = slides.iter().map(Slide::is_quote).collect();
+        assert_eq!(vec![true, false], quotes);
+    }
+
+    #[test]
+    fn test_parse_polls() {
+        let slides = parse("# Vote\n\n:::poll Favorite?\n- Vim\n- Emacs\n:::\n\nAfter\n");
+        assert_eq!(1, slides[0].polls.len());
+        assert_eq!("slide-1-poll-1", slides[0].polls[0].id);
+        let html: Vec<&Event> = slides[0]
+            .events
+            .iter()
+            .filter(|e| matches!(e, Event::Html(_)))
+            .collect();
+        assert_eq!(1, html.len());
+        assert_eq!(
+            Some(&Event::Text("After".into())),
+            slides[0].events.iter().rev().nth(1)
+        );
+    }
+
+    #[test]
+    fn test_parse_footnotes() {
+        let slides = parse("One[^a]\n\n---\n\nTwo[^b] and[^a]\n\n---\n\n[^a]: A\n\n[^b]: B\n");
+        assert_eq!(2, slides.len());
+        let labels = |slide: &Slide| -> Vec<String> {
+            slide
+                .events
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Start(Tag::FootnoteDefinition(label)) => Some(label.to_string()),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(vec!["a"], labels(&slides[0]));
+        assert_eq!(vec!["b", "a"], labels(&slides[1]));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(Some(90), parse_duration("90"));
+        assert_eq!(Some(90), parse_duration("90s"));
+        assert_eq!(Some(120), parse_duration("2m"));
+        assert_eq!(Some(90), parse_duration("1m30s"));
+        assert_eq!(None, parse_duration("soon"));
+        assert_eq!(None, parse_duration(""));
+    }
+
+    #[test]
+    fn test_parse_countdown() {
+        let slides = parse("# Break\n<!-- break: 10m chime -->\n");
+        assert_eq!(
+            Some(Countdown {
+                duration: 600,
+                chime: true
+            }),
+            slides[0].countdown
+        );
+        assert_eq!(None, parse_countdown("10m loudly"));
+        assert_eq!(Some(90), parse_countdown("1m30s").map(|c| c.duration));
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        let slides = parse("<!-- layout: two-col -->\n\n---\n\n<!-- layout: unknown -->\n");
+        assert_eq!(Some("two-col"), slides[0].layout.as_deref());
+        assert!(slides[0].events.is_empty());
+        assert_eq!(None, slides[1].layout);
+    }
+
+    #[test]
+    fn test_parse_directive() {
+        assert_eq!(
+            Some(("notes", "Hello")),
+            parse_directive("<!-- notes: Hello -->\n")
+        );
+        assert_eq!(None, parse_directive("<!-- Hello -->"));
+        assert_eq!(None, parse_directive("<div>notes: Hello</div>"));
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        let selection: Selection = "1, 5-7".parse().expect("Failed to parse selection");
+        let selected: Vec<usize> = (0..10).filter(|&i| selection.contains(i)).collect();
+        assert_eq!(vec![0, 4, 5, 6], selected);
+        assert!(Selection::one(3).contains(2) && !Selection::one(3).contains(3));
+        assert!("0".parse::<Selection>().is_err());
+        assert!("7-5".parse::<Selection>().is_err());
+        assert!("1,".parse::<Selection>().is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use crate::{assets, error::Error, slides::Slide};
+use pulldown_cmark::{Event, Tag};
+use std::{collections::HashMap, fs, path::Path};
+
+/// Elements removed along with their content when sanitizing, as they can run scripts, embed
+/// arbitrary documents or restyle the whole page once inlined
+const FORBIDDEN_ELEMENTS: [&str; 6] = [
+    "script",
+    "foreignobject",
+    "iframe",
+    "embed",
+    "object",
+    "style",
+];
+
+/// Elements changing the value of another attribute, removed when they target a link as they
+/// could turn it into a script URL
+const ANIMATION_ELEMENTS: [&str; 2] = ["animate", "set"];
+
+/// Load the local SVG images of the slides so that they can be inlined, they are optionally
+/// sanitized. Files without an `<svg>` element are left as images.
+pub fn load(
+    slides: &[Slide],
+    base_dir: &Path,
+    sanitize: bool,
+) -> Result<HashMap<String, String>, Error> {
+    let mut svgs = HashMap::new();
+    for slide in slides {
+        for event in &slide.events {
+            let url = match event {
+                Event::Start(Tag::Image(_, url, _)) => url,
+                _ => continue,
+            };
+            let is_svg = url.to_ascii_lowercase().ends_with(".svg");
+            if !is_svg || !assets::is_local(url) || svgs.contains_key(&**url) {
+                continue;
+            }
+            let source = fs::read_to_string(base_dir.join(&**url))?;
+            if let Some(svg) = extract(&source) {
+                let svg = if sanitize {
+                    self::sanitize(svg)
+                } else {
+                    svg.to_owned()
+                };
+                svgs.insert(url.to_string(), svg);
+            }
+        }
+    }
+    Ok(svgs)
+}
+
+/// Strip the XML declaration, doctype and comments that may surround the `<svg>` element
+fn extract(source: &str) -> Option<&str> {
+    let start = source.find("<svg")?;
+    let end = source.rfind("</svg>")? + "</svg>".len();
+    if end <= start {
+        return None;
+    }
+    Some(&source[start..end])
+}
+
+/// Byte length of the tag starting the input, ignoring the `>` within quoted attribute values
+fn tag_len(input: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_href(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name == "href" || name.ends_with(":href")
+}
+
+/// Value of an attribute without its quotes
+fn unquote(value: Option<&str>) -> &str {
+    value
+        .unwrap_or_default()
+        .trim_matches(|c| c == '"' || c == '\'')
+}
+
+fn is_allowed_attribute(name: &str, value: Option<&str>) -> bool {
+    if name.is_empty() || name.to_ascii_lowercase().starts_with("on") {
+        return false;
+    }
+    if is_href(name) {
+        let value: String = unquote(value)
+            .chars()
+            .filter(|c| !c.is_whitespace() && !c.is_control())
+            .collect();
+        let value = value.to_ascii_lowercase();
+        let is_script = value.starts_with("javascript:")
+            || (value.starts_with("data:") && !value.starts_with("data:image/"))
+            || value.starts_with("data:image/svg");
+        return !is_script;
+    }
+    true
+}
+
+/// Whether the character ends the name of an element or attribute. Browsers treat a `/` as
+/// whitespace between attributes, e.g. in `<svg/onload=...>`.
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || c == '/'
+}
+
+/// Name of the element and attributes with their quoted value of a start tag
+fn parse_tag(tag: &str) -> (&str, Vec<(&str, Option<&str>)>) {
+    let inner = tag.trim_start_matches('<').trim_end_matches('>');
+    let inner = inner.strip_suffix('/').unwrap_or(inner);
+    let name_end = inner.find(is_separator).unwrap_or(inner.len());
+    let mut attributes = Vec::new();
+    let mut rest = &inner[name_end..];
+    loop {
+        rest = rest.trim_start_matches(is_separator);
+        if rest.is_empty() {
+            break;
+        }
+        let name_end = rest
+            .find(|c: char| is_separator(c) || c == '=')
+            .unwrap_or(rest.len());
+        let name = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let len = match after.chars().next() {
+                    Some(quote) if quote == '"' || quote == '\'' => {
+                        after[1..].find(quote).map_or(after.len(), |i| i + 2)
+                    }
+                    _ => after.find(char::is_whitespace).unwrap_or(after.len()),
+                };
+                rest = &after[len..];
+                Some(&after[..len])
+            }
+            None => None,
+        };
+        attributes.push((name, value));
+    }
+    (&inner[..name_end], attributes)
+}
+
+/// Whether an animation element sets a link, e.g. `<set attributeName="href" to="...">`
+fn animates_href(tag: &str) -> bool {
+    parse_tag(tag).1.into_iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("attributename") && is_href(unquote(value).trim())
+    })
+}
+
+/// Remove the event handlers and script URLs from the attributes of a start tag
+fn sanitize_tag(tag: &str) -> String {
+    let self_closing = tag.ends_with("/>");
+    let (name, attributes) = parse_tag(tag);
+    let mut output = format!("<{}", name);
+    for (name, value) in attributes {
+        if is_allowed_attribute(name, value) {
+            output.push(' ');
+            output.push_str(name);
+            if let Some(value) = value {
+                output.push('=');
+                output.push_str(value);
+            }
+        }
+    }
+    if self_closing {
+        output.push('/');
+    }
+    output.push('>');
+    output
+}
+
+/// Remove the scripts, embedded documents, event handlers and `javascript:` links of an SVG
+pub fn sanitize(svg: &str) -> String {
+    let mut output = String::with_capacity(svg.len());
+    let mut rest = svg;
+    // Forbidden element being removed along with the depth of its nested elements of the same name
+    let mut skipped: Option<(String, usize)> = None;
+    while let Some(start) = rest.find('<') {
+        if skipped.is_none() {
+            output.push_str(&rest[..start]);
+        }
+        rest = &rest[start..];
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |i| i + "-->".len());
+            rest = &rest[end..];
+            continue;
+        }
+        if rest.starts_with("<![CDATA[") {
+            let end = rest.find("]]>").map_or(rest.len(), |i| i + "]]>".len());
+            if skipped.is_none() {
+                output.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+            continue;
+        }
+        let len = match tag_len(rest) {
+            Some(len) => len,
+            None => {
+                rest = "";
+                break;
+            }
+        };
+        let tag = &rest[..len];
+        rest = &rest[len..];
+        let is_closing = tag.starts_with("</");
+        let is_self_closing = tag.ends_with("/>");
+        let name = tag
+            .trim_start_matches(|c| c == '<' || c == '/')
+            .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if let Some((ref skipped_name, ref mut depth)) = skipped {
+            if name == *skipped_name {
+                if is_closing {
+                    *depth -= 1;
+                } else if !is_self_closing {
+                    *depth += 1;
+                }
+            }
+            if *depth == 0 {
+                skipped = None;
+            }
+            continue;
+        }
+        let is_forbidden = FORBIDDEN_ELEMENTS.contains(&name.as_str())
+            || (ANIMATION_ELEMENTS.contains(&name.as_str()) && !is_closing && animates_href(tag));
+        if is_forbidden {
+            if !is_closing && !is_self_closing {
+                skipped = Some((name, 1));
+            }
+            continue;
+        }
+        if is_closing {
+            output.push_str(tag);
+        } else if !tag.starts_with("<!") && !tag.starts_with("<?") {
+            output.push_str(&sanitize_tag(tag));
+        }
+    }
+    if skipped.is_none() {
+        output.push_str(rest);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        let source = "<?xml version=\"1.0\"?>\n<!-- Drawn by hand -->\n\
+                      <svg xmlns=\"http://www.w3.org/2000/svg\" onload=\"alert(1)\">\
+                      <script>if (a > b) {}</script>\
+                      <a xlink:href=\" javascript:alert(1)\" class='link'>\
+                      <rect width=\"10\" data-label=\"a > b\"/></a>\
+                      <foreignObject><div><foreignObject/></div></foreignObject>\
+                      <text>1 &lt; 2</text></svg>\n";
+        let svg = extract(source).expect("Failed to find the svg element");
+        assert_eq!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\">\
+             <a class='link'><rect width=\"10\" data-label=\"a > b\"/></a>\
+             <text>1 &lt; 2</text></svg>",
+            sanitize(svg)
+        );
+        assert_eq!("<svg></svg>", sanitize("<svg/onload=alert(1)></svg>"));
+        assert_eq!(
+            "<svg><image href=\"a.png\"/><rect width=10/></svg>",
+            sanitize("<svg><image/onerror=alert(1) href=\"a.png\"/><rect width=10/></svg>")
+        );
+        assert_eq!(
+            "<svg><a><animate attributeName=\"x\" to=\"1\"/></a></svg>",
+            sanitize(
+                "<svg><a><animate attributeName=\"href\" values=\"javascript:alert(1)\"/>\
+                 <animate attributeName=\"x\" to=\"1\"/></a></svg>"
+            )
+        );
+        assert_eq!(
+            "<svg><a></a></svg>",
+            sanitize(
+                "<svg><a><set attributeName=\"xlink:href\" to=\"javascript:alert(1)\"></set>\
+                 </a><style>svg { background: url(x) }</style></svg>"
+            )
+        );
+    }
+}
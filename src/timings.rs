@@ -0,0 +1,124 @@
+use std::{fmt, time::Duration};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+
+/// Stand-in for `std::time::Instant`, which panics in the browser: nothing is timed there
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Copy)]
+pub struct Instant;
+
+#[cfg(target_arch = "wasm32")]
+impl Instant {
+    pub fn now() -> Instant {
+        Instant
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Duration::default()
+    }
+}
+
+/// Number of slides listed, from the slowest to render, when reporting the timings per slide
+const SLOWEST_SLIDES: usize = 10;
+
+/// Time spent in each phase of a build, reported with `--timings`
+#[derive(Debug, Default)]
+pub struct Timings {
+    phases: Vec<(&'static str, Duration)>,
+    /// Time spent rendering each slide, by index, along with its title
+    slides: Vec<(usize, Option<String>, Duration)>,
+    /// Also report the slowest slides
+    pub per_slide: bool,
+}
+
+impl Timings {
+    /// Add the given duration to a phase, phases are reported in the order they first ran
+    pub fn record(&mut self, phase: &'static str, duration: Duration) {
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += duration,
+            None => self.phases.push((phase, duration)),
+        }
+    }
+
+    /// Run the given function and record how long it took
+    pub fn time<T, F: FnOnce() -> T>(&mut self, phase: &'static str, f: F) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(phase, start.elapsed());
+        result
+    }
+
+    pub fn record_slide(&mut self, index: usize, title: Option<String>, duration: Duration) {
+        self.slides.push((index, title, duration));
+    }
+
+    /// Append the timings of a later step of the build
+    pub fn extend(&mut self, other: Timings) {
+        for (phase, duration) in other.phases {
+            self.record(phase, duration);
+        }
+        self.slides.extend(other.slides);
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .phases
+            .iter()
+            .map(|(phase, _)| phase.len())
+            .max()
+            .unwrap_or(0)
+            .max("total".len());
+        for (phase, duration) in &self.phases {
+            writeln!(f, "{:<w$}  {:>9.1}ms", phase, millis(*duration), w = width)?;
+        }
+        let total: Duration = self.phases.iter().map(|(_, duration)| *duration).sum();
+        writeln!(f, "{:<w$}  {:>9.1}ms", "total", millis(total), w = width)?;
+        if self.per_slide && !self.slides.is_empty() {
+            let mut slides: Vec<&(usize, Option<String>, Duration)> = self.slides.iter().collect();
+            slides.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+            writeln!(f, "slowest slides:")?;
+            for (index, title, duration) in slides.into_iter().take(SLOWEST_SLIDES) {
+                write!(f, "  slide {:<4} {:>9.1}ms", index + 1, millis(*duration))?;
+                match title {
+                    Some(title) => writeln!(f, "  {}", title)?,
+                    None => writeln!(f)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timings() {
+        let mut timings = Timings::default();
+        timings.record("parse", Duration::from_millis(2));
+        let mut rendering = Timings::default();
+        rendering.record("highlight", Duration::from_millis(5));
+        rendering.record("parse", Duration::from_millis(1));
+        rendering.record_slide(0, Some("Intro".to_owned()), Duration::from_millis(1));
+        rendering.record_slide(1, None, Duration::from_millis(4));
+        timings.extend(rendering);
+        timings.per_slide = true;
+        assert_eq!(
+            "parse            3.0ms\n\
+             highlight        5.0ms\n\
+             total            8.0ms\n\
+             slowest slides:\n  \
+             slide 2          4.0ms\n  \
+             slide 1          1.0ms  Intro\n",
+            timings.to_string()
+        );
+    }
+}
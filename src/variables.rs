@@ -0,0 +1,207 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use tracing::warn;
+
+/// CSS custom properties exposed by the style on `:root`, by group, each one being named
+/// `--deck-{group}-{name}`
+const VARIABLES: [(&str, &[&str]); 3] = [
+    ("color", &["background", "text", "accent"]),
+    ("font", &["body", "code", "size"]),
+    ("spacing", &["slide-padding", "gap"]),
+];
+
+/// Minimum contrast ratio of the text against its background, from the WCAG level AA
+const MIN_TEXT_CONTRAST: f64 = 4.5;
+
+/// Minimum contrast ratio of the accent against the background, as it colors graphics and large
+/// elements rather than body text
+const MIN_ACCENT_CONTRAST: f64 = 3.0;
+
+/// Default colors of the light and dark appearances, as set in `style.css`
+const LIGHT_COLORS: [(&str, &str); 3] = [
+    ("background", "#ffffff"),
+    ("text", "#000000"),
+    ("accent", "#4a90d9"),
+];
+const DARK_COLORS: [(&str, &str); 3] = [
+    ("background", "#1d1f21"),
+    ("text", "#eeeeee"),
+    ("accent", "#81a2be"),
+];
+
+/// Values of the custom properties overridden in the `theme` of the front matter, e.g.
+/// `theme: {colors: {accent: "#ff6600"}}`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Variables {
+    pub colors: BTreeMap<String, String>,
+    pub fonts: BTreeMap<String, String>,
+    pub spacing: BTreeMap<String, String>,
+    /// Colors of the dark appearance, toggled by the viewers
+    pub dark: BTreeMap<String, String>,
+}
+
+/// Declarations of the custom properties of a group, unknown ones and values that could escape
+/// the declaration are skipped with a warning
+fn declarations(group: &str, names: &[&str], values: &BTreeMap<String, String>) -> String {
+    let mut declarations = String::new();
+    for (name, value) in values {
+        // Names are written with underscores in YAML keys
+        let name = name.replace('_', "-");
+        if !names.contains(&name.as_str()) {
+            warn!(
+                "Unknown {} {:?} in the theme of the front matter, expected one of {}",
+                group,
+                name,
+                names.join(", ")
+            );
+            continue;
+        }
+        if value.contains(|c: char| matches!(c, ';' | '{' | '}' | '<' | '>')) {
+            warn!("Invalid value {:?} of the {} {:?}", value, group, name);
+            continue;
+        }
+        declarations.push_str(&format!("--deck-{}-{}:{};", group, name, value));
+    }
+    declarations
+}
+
+/// Parse a `#rgb` or `#rrggbb` color, or `black` and `white`, into its channels
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let color = color.trim();
+    let hex = match color.to_ascii_lowercase().as_str() {
+        "black" => return Some([0, 0, 0]),
+        "white" => return Some([255, 255, 255]),
+        _ => color.strip_prefix('#')?,
+    };
+    let digits: Vec<u8> = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()?;
+    match digits.as_slice() {
+        [r, g, b] => Some([r * 17, g * 17, b * 17]),
+        [r1, r2, g1, g2, b1, b2] => Some([r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2]),
+        _ => None,
+    }
+}
+
+/// Relative luminance of a color, as defined by the WCAG
+fn luminance(color: [u8; 3]) -> f64 {
+    let channel = |c: u8| {
+        let c = f64::from(c) / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color[0]) + 0.7152 * channel(color[1]) + 0.0722 * channel(color[2])
+}
+
+/// Contrast ratio between two colors, from 1 to 21
+fn contrast(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+impl Variables {
+    /// Pairs of colors overridden in the theme whose contrast is too low to be read, in either
+    /// appearance. Colors that can't be parsed, such as named colors or `var()`, are skipped.
+    pub fn contrast_issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        let appearances = [
+            ("light", &LIGHT_COLORS, &self.colors),
+            ("dark", &DARK_COLORS, &self.dark),
+        ];
+        for (appearance, defaults, overrides) in appearances.iter() {
+            let color = |name: &str| {
+                overrides
+                    .get(name)
+                    .map(String::as_str)
+                    .or_else(|| defaults.iter().find(|(n, _)| *n == name).map(|(_, c)| *c))
+                    .unwrap_or_default()
+            };
+            for (name, minimum) in
+                [("text", MIN_TEXT_CONTRAST), ("accent", MIN_ACCENT_CONTRAST)].iter()
+            {
+                // Only the custom choices are reported, not the defaults
+                if !overrides.contains_key(*name) && !overrides.contains_key("background") {
+                    continue;
+                }
+                let (foreground, background) = (color(*name), color("background"));
+                if let (Some(fg), Some(bg)) = (parse_color(foreground), parse_color(background)) {
+                    let ratio = contrast(fg, bg);
+                    if ratio < *minimum {
+                        issues.push(format!(
+                            "theme: {} color {} on the {} background {} has a contrast ratio of {:.1}:1, below {}:1",
+                            name, foreground, appearance, background, ratio, minimum
+                        ));
+                    }
+                }
+            }
+        }
+        issues
+    }
+
+    /// Rules overriding the custom properties, if any
+    pub fn to_css(&self) -> Option<String> {
+        let mut css = String::new();
+        let groups = [&self.colors, &self.fonts, &self.spacing];
+        let light: String = VARIABLES
+            .iter()
+            .zip(groups.iter())
+            .map(|((group, names), values)| declarations(group, names, values))
+            .collect();
+        if !light.is_empty() {
+            css.push_str(&format!(":root{{{}}}", light));
+        }
+        let (group, names) = VARIABLES[0];
+        let dark = declarations(group, names, &self.dark);
+        if !dark.is_empty() {
+            css.push_str(&format!(":root.dark{{{}}}", dark));
+        }
+        Some(css).filter(|css| !css.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_css() {
+        assert_eq!(None, Variables::default().to_css());
+        let variables: Variables = serde_yaml::from_str(
+            "colors: {accent: '#ff6600', link: blue}\nfonts: {body: 'Inter, sans-serif'}\nspacing: {slide_padding: 2em, gap: '1em}'}\ndark: {accent: '#ffa500'}\n",
+        )
+        .expect("Failed to parse variables");
+        assert_eq!(
+            Some(
+                ":root{--deck-color-accent:#ff6600;--deck-font-body:Inter, sans-serif;\
+                 --deck-spacing-slide-padding:2em;}:root.dark{--deck-color-accent:#ffa500;}"
+                    .to_owned()
+            ),
+            variables.to_css()
+        );
+    }
+
+    #[test]
+    fn test_contrast_issues() {
+        assert!(Variables::default().contrast_issues().is_empty());
+        assert_eq!(Some([255, 102, 0]), parse_color("#f60"));
+        assert!((contrast([0, 0, 0], [255, 255, 255]) - 21.0).abs() < 1e-9);
+        let variables: Variables = serde_yaml::from_str(
+            "colors: {text: '#888888', background: white, accent: var(--x)}\ndark: {accent: '#333'}\n",
+        )
+        .expect("Failed to parse variables");
+        assert_eq!(
+            vec![
+                "theme: text color #888888 on the light background white has a contrast ratio \
+                 of 3.5:1, below 4.5:1",
+                "theme: accent color #333 on the dark background #1d1f21 has a contrast ratio \
+                 of 1.3:1, below 3:1",
+            ],
+            variables.contrast_issues()
+        );
+    }
+}
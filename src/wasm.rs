@@ -0,0 +1,30 @@
+//! Bindings of the renderer for the browser, so that an editor running client-side renders the
+//! slides exactly as `deck build` does. Built with
+//! `wasm-pack build --target web -- --no-default-features --features wasm`.
+use crate::{
+    error::Error,
+    html::{Highlighting, Options},
+};
+use wasm_bindgen::prelude::*;
+
+thread_local! {
+    // Loading the syntaxes and themes is the slow part of the rendering, it is done once on the
+    // first call
+    static HIGHLIGHTING: Highlighting =
+        Highlighting::load(&[]).expect("Failed to load the default syntaxes and themes");
+}
+
+/// Render a Markdown presentation to a self-contained HTML page with the default options
+#[wasm_bindgen]
+pub fn render(markdown: &str) -> Result<String, JsValue> {
+    HIGHLIGHTING
+        .with(|highlighting| -> Result<String, Error> {
+            let output = highlighting.renderer(Options::default())?.render(
+                markdown.to_owned(),
+                None,
+                None,
+            )?;
+            Ok(output.to_string())
+        })
+        .map_err(|err| JsValue::from_str(&err.to_string()))
+}